@@ -0,0 +1,275 @@
+//! A minimal stereo pass-through CLAP plugin, built as a cdylib and loaded
+//! by the integration tests.
+//!
+//! Each process call reports what it was given as output events at time 0:
+//! parameter 0 carries `steady_time`, parameter 1 `frames_count` and
+//! parameter 2 the transport's beat position, if any. A transport event in
+//! the input is reported as parameter 2 at its own time, and a note-off
+//! ends its voice at once with a `CLAP_EVENT_NOTE_END`.
+//!
+//! `clap_entry.init()` fails if the entry is already initialized, so a host
+//! that initializes the same loaded library twice without `deinit()` gets a
+//! load error.
+
+use clap_sys::entry::clap_plugin_entry;
+use clap_sys::events::{
+    clap_event_header, clap_event_note, clap_event_param_value, clap_event_transport,
+    clap_output_events, CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_NOTE_END, CLAP_EVENT_NOTE_OFF,
+    CLAP_EVENT_PARAM_VALUE, CLAP_EVENT_TRANSPORT,
+};
+use clap_sys::ext::audio_ports::{
+    clap_audio_port_info, clap_plugin_audio_ports, CLAP_AUDIO_PORT_IS_MAIN, CLAP_EXT_AUDIO_PORTS,
+    CLAP_PORT_STEREO,
+};
+use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::fixedpoint::CLAP_BEATTIME_FACTOR;
+use clap_sys::host::clap_host;
+use clap_sys::id::CLAP_INVALID_ID;
+use clap_sys::plugin::{clap_plugin, clap_plugin_descriptor};
+use clap_sys::process::{clap_process, clap_process_status, CLAP_PROCESS_CONTINUE};
+use clap_sys::string_sizes::CLAP_NAME_SIZE;
+use clap_sys::version::CLAP_VERSION;
+use std::ffi::{c_char, c_void, CStr};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+struct Descriptor(clap_plugin_descriptor);
+
+// Safety: the descriptor only points at static strings.
+unsafe impl Sync for Descriptor {}
+
+static FEATURES: [Features; 2] = [Features(c"audio-effect".as_ptr()), Features(ptr::null())];
+
+#[repr(transparent)]
+struct Features(*const c_char);
+
+// Safety: the features only point at static strings.
+unsafe impl Sync for Features {}
+
+static DESCRIPTOR: Descriptor = Descriptor(clap_plugin_descriptor {
+    clap_version: CLAP_VERSION,
+    id: c"com.clap-host.test-plugin".as_ptr(),
+    name: c"Test Plugin".as_ptr(),
+    vendor: c"clap-host".as_ptr(),
+    url: c"".as_ptr(),
+    manual_url: c"".as_ptr(),
+    support_url: c"".as_ptr(),
+    version: c"1.0.0".as_ptr(),
+    description: c"Stereo pass-through".as_ptr(),
+    features: FEATURES.as_ptr() as *const *const c_char,
+});
+
+#[allow(non_upper_case_globals)]
+#[no_mangle]
+pub static clap_entry: clap_plugin_entry = clap_plugin_entry {
+    clap_version: CLAP_VERSION,
+    init: Some(entry_init),
+    deinit: Some(entry_deinit),
+    get_factory: Some(entry_get_factory),
+};
+
+unsafe extern "C" fn entry_init(_plugin_path: *const c_char) -> bool {
+    !INITIALIZED.swap(true, Ordering::SeqCst)
+}
+
+unsafe extern "C" fn entry_deinit() {
+    INITIALIZED.store(false, Ordering::SeqCst);
+}
+
+unsafe extern "C" fn entry_get_factory(factory_id: *const c_char) -> *const c_void {
+    if CStr::from_ptr(factory_id) == CLAP_PLUGIN_FACTORY_ID {
+        &FACTORY as *const clap_plugin_factory as *const c_void
+    } else {
+        ptr::null()
+    }
+}
+
+static FACTORY: clap_plugin_factory = clap_plugin_factory {
+    get_plugin_count: Some(factory_get_plugin_count),
+    get_plugin_descriptor: Some(factory_get_plugin_descriptor),
+    create_plugin: Some(factory_create_plugin),
+};
+
+unsafe extern "C" fn factory_get_plugin_count(_factory: *const clap_plugin_factory) -> u32 {
+    1
+}
+
+unsafe extern "C" fn factory_get_plugin_descriptor(
+    _factory: *const clap_plugin_factory,
+    index: u32,
+) -> *const clap_plugin_descriptor {
+    if index == 0 {
+        &DESCRIPTOR.0
+    } else {
+        ptr::null()
+    }
+}
+
+unsafe extern "C" fn factory_create_plugin(
+    _factory: *const clap_plugin_factory,
+    _host: *const clap_host,
+    plugin_id: *const c_char,
+) -> *const clap_plugin {
+    if CStr::from_ptr(plugin_id) != CStr::from_ptr(DESCRIPTOR.0.id) {
+        return ptr::null();
+    }
+    Box::into_raw(Box::new(clap_plugin {
+        desc: &DESCRIPTOR.0,
+        plugin_data: ptr::null_mut(),
+        init: Some(plugin_init),
+        destroy: Some(plugin_destroy),
+        activate: Some(plugin_activate),
+        deactivate: Some(plugin_deactivate),
+        start_processing: Some(plugin_start_processing),
+        stop_processing: Some(plugin_stop_processing),
+        reset: Some(plugin_reset),
+        process: Some(plugin_process),
+        get_extension: Some(plugin_get_extension),
+        on_main_thread: Some(plugin_on_main_thread),
+    }))
+}
+
+unsafe extern "C" fn plugin_init(_plugin: *const clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_destroy(plugin: *const clap_plugin) {
+    drop(Box::from_raw(plugin as *mut clap_plugin));
+}
+
+unsafe extern "C" fn plugin_activate(
+    _plugin: *const clap_plugin,
+    _sample_rate: f64,
+    _min_frames: u32,
+    _max_frames: u32,
+) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_deactivate(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_start_processing(_plugin: *const clap_plugin) -> bool {
+    true
+}
+
+unsafe extern "C" fn plugin_stop_processing(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_reset(_plugin: *const clap_plugin) {}
+
+unsafe extern "C" fn plugin_process(
+    _plugin: *const clap_plugin,
+    process: *const clap_process,
+) -> clap_process_status {
+    let process = &*process;
+    let frames = process.frames_count as usize;
+    if process.audio_inputs_count > 0 && process.audio_outputs_count > 0 {
+        let input = &*process.audio_inputs;
+        let output = &*process.audio_outputs;
+        if !input.data32.is_null() && !output.data32.is_null() {
+            for channel in 0..input.channel_count.min(output.channel_count) as usize {
+                let from = *input.data32.add(channel);
+                let to = *output.data32.add(channel);
+                if from != to {
+                    ptr::copy_nonoverlapping(from, to, frames);
+                }
+            }
+        }
+    }
+
+    let out = &*process.out_events;
+    push_param(out, 0, 0, process.steady_time as f64);
+    push_param(out, 0, 1, process.frames_count as f64);
+    if !process.transport.is_null() {
+        push_param(out, 0, 2, beats(&*process.transport));
+    }
+    let input = &*process.in_events;
+    for index in 0..input.size.unwrap()(input) {
+        let header = &*input.get.unwrap()(input, index);
+        if header.space_id != CLAP_CORE_EVENT_SPACE_ID {
+            continue;
+        }
+        match header.type_ {
+            CLAP_EVENT_TRANSPORT => {
+                let transport = &*(header as *const _ as *const clap_event_transport);
+                push_param(out, header.time, 2, beats(transport));
+            }
+            CLAP_EVENT_NOTE_OFF => {
+                let mut end = *(header as *const _ as *const clap_event_note);
+                end.header.type_ = CLAP_EVENT_NOTE_END;
+                out.try_push.unwrap()(out, &end.header);
+            }
+            _ => {}
+        }
+    }
+    CLAP_PROCESS_CONTINUE
+}
+
+fn beats(transport: &clap_event_transport) -> f64 {
+    transport.song_pos_beats as f64 / CLAP_BEATTIME_FACTOR as f64
+}
+
+unsafe fn push_param(out: &clap_output_events, time: u32, param_id: u32, value: f64) {
+    let event = clap_event_param_value {
+        header: clap_event_header {
+            size: std::mem::size_of::<clap_event_param_value>() as u32,
+            time,
+            space_id: CLAP_CORE_EVENT_SPACE_ID,
+            type_: CLAP_EVENT_PARAM_VALUE,
+            flags: 0,
+        },
+        param_id,
+        cookie: ptr::null_mut(),
+        note_id: -1,
+        port_index: -1,
+        channel: -1,
+        key: -1,
+        value,
+    };
+    out.try_push.unwrap()(out, &event.header);
+}
+
+unsafe extern "C" fn plugin_get_extension(
+    _plugin: *const clap_plugin,
+    id: *const c_char,
+) -> *const c_void {
+    if CStr::from_ptr(id) == CLAP_EXT_AUDIO_PORTS {
+        &AUDIO_PORTS as *const clap_plugin_audio_ports as *const c_void
+    } else {
+        ptr::null()
+    }
+}
+
+unsafe extern "C" fn plugin_on_main_thread(_plugin: *const clap_plugin) {}
+
+static AUDIO_PORTS: clap_plugin_audio_ports = clap_plugin_audio_ports {
+    count: Some(audio_ports_count),
+    get: Some(audio_ports_get),
+};
+
+unsafe extern "C" fn audio_ports_count(_plugin: *const clap_plugin, _is_input: bool) -> u32 {
+    1
+}
+
+unsafe extern "C" fn audio_ports_get(
+    _plugin: *const clap_plugin,
+    index: u32,
+    _is_input: bool,
+    info: *mut clap_audio_port_info,
+) -> bool {
+    if index != 0 {
+        return false;
+    }
+    let info = &mut *info;
+    info.id = 0;
+    info.name = [0; CLAP_NAME_SIZE];
+    for (dst, src) in info.name.iter_mut().zip(c"Main".to_bytes()) {
+        *dst = *src as c_char;
+    }
+    info.flags = CLAP_AUDIO_PORT_IS_MAIN;
+    info.channel_count = 2;
+    info.port_type = CLAP_PORT_STEREO.as_ptr();
+    info.in_place_pair = CLAP_INVALID_ID;
+    true
+}
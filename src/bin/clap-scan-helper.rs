@@ -0,0 +1,6 @@
+//! Child process used by `IsolatedScanner` to open a single `.clap` bundle
+//! without risking the host process.
+
+fn main() {
+    std::process::exit(clap_host::scan::isolated::helper_main());
+}
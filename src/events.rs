@@ -4,21 +4,28 @@
 //! `input_events_get` have the correct C memory layout for plugins to cast.
 
 use crate::types::{
-    MidiData, MidiEvent, NoteExpressionType, NoteExpressionValue, ParameterChanges, ParameterPoint,
-    ParameterQueue,
+    MidiData, MidiEvent, NoteEnd, NoteExpressionType, NoteExpressionValue, ParameterChanges,
+    ParameterModulations, ParameterPoint, ParameterQueue, TransportChange, TransportFields,
+    TransportInfo,
 };
 use clap_sys::events::{
     clap_event_header, clap_event_midi, clap_event_midi_sysex, clap_event_note,
     clap_event_note_expression, clap_event_param_gesture, clap_event_param_mod,
-    clap_event_param_value, clap_input_events, clap_output_events, CLAP_CORE_EVENT_SPACE_ID,
-    CLAP_EVENT_MIDI, CLAP_EVENT_MIDI_SYSEX, CLAP_EVENT_NOTE_CHOKE, CLAP_EVENT_NOTE_END,
-    CLAP_EVENT_NOTE_EXPRESSION, CLAP_EVENT_NOTE_OFF, CLAP_EVENT_NOTE_ON,
+    clap_event_param_value, clap_event_transport, clap_input_events, clap_output_events,
+    CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_MIDI, CLAP_EVENT_MIDI_SYSEX, CLAP_EVENT_NOTE_CHOKE,
+    CLAP_EVENT_NOTE_END, CLAP_EVENT_NOTE_EXPRESSION, CLAP_EVENT_NOTE_OFF, CLAP_EVENT_NOTE_ON,
     CLAP_EVENT_PARAM_GESTURE_BEGIN, CLAP_EVENT_PARAM_GESTURE_END, CLAP_EVENT_PARAM_MOD,
-    CLAP_EVENT_PARAM_VALUE, CLAP_NOTE_EXPRESSION_BRIGHTNESS, CLAP_NOTE_EXPRESSION_EXPRESSION,
-    CLAP_NOTE_EXPRESSION_PAN, CLAP_NOTE_EXPRESSION_PRESSURE, CLAP_NOTE_EXPRESSION_TUNING,
-    CLAP_NOTE_EXPRESSION_VIBRATO, CLAP_NOTE_EXPRESSION_VOLUME,
+    CLAP_EVENT_PARAM_VALUE, CLAP_EVENT_TRANSPORT, CLAP_NOTE_EXPRESSION_BRIGHTNESS,
+    CLAP_NOTE_EXPRESSION_EXPRESSION, CLAP_NOTE_EXPRESSION_PAN, CLAP_NOTE_EXPRESSION_PRESSURE,
+    CLAP_NOTE_EXPRESSION_TUNING, CLAP_NOTE_EXPRESSION_VIBRATO, CLAP_NOTE_EXPRESSION_VOLUME,
+    CLAP_TRANSPORT_HAS_BEATS_TIMELINE, CLAP_TRANSPORT_HAS_SECONDS_TIMELINE,
+    CLAP_TRANSPORT_HAS_TEMPO, CLAP_TRANSPORT_HAS_TIME_SIGNATURE, CLAP_TRANSPORT_IS_LOOP_ACTIVE,
+    CLAP_TRANSPORT_IS_PLAYING, CLAP_TRANSPORT_IS_RECORDING,
 };
+use clap_sys::fixedpoint::{CLAP_BEATTIME_FACTOR, CLAP_SECTIME_FACTOR};
+use std::ops::Range;
 use std::ptr;
+use std::sync::Arc;
 
 /// CLAP event wrapping the actual C structs for correct memory layout.
 ///
@@ -37,11 +44,12 @@ pub enum ClapEvent {
     ParamMod(clap_event_param_mod),
     ParamGestureBegin(clap_event_param_gesture),
     ParamGestureEnd(clap_event_param_gesture),
-    /// Sysex owns the data buffer; the inner C struct's `buffer` pointer
-    /// points into `_data`. Must not be moved after construction.
+    Transport(clap_event_transport),
+    /// Sysex keeps its data alive; the inner C struct's `buffer` pointer
+    /// points into `_data`, which doesn't move with the event.
     MidiSysex {
         inner: clap_event_midi_sysex,
-        _data: Vec<u8>,
+        _data: Arc<[u8]>,
     },
 }
 
@@ -62,10 +70,63 @@ impl ClapEvent {
             ClapEvent::ParamMod(e) => &e.header,
             ClapEvent::ParamGestureBegin(e) => &e.header,
             ClapEvent::ParamGestureEnd(e) => &e.header,
+            ClapEvent::Transport(e) => &e.header,
             ClapEvent::MidiSysex { inner, .. } => &inner.header,
         }
     }
 
+    pub(crate) fn header_mut(&mut self) -> &mut clap_event_header {
+        match self {
+            ClapEvent::NoteOn(e) => &mut e.header,
+            ClapEvent::NoteOff(e) => &mut e.header,
+            ClapEvent::NoteChoke(e) => &mut e.header,
+            ClapEvent::NoteEnd(e) => &mut e.header,
+            ClapEvent::Midi(e) => &mut e.header,
+            ClapEvent::NoteExpression(e) => &mut e.header,
+            ClapEvent::ParamValue(e) => &mut e.header,
+            ClapEvent::ParamMod(e) => &mut e.header,
+            ClapEvent::ParamGestureBegin(e) => &mut e.header,
+            ClapEvent::ParamGestureEnd(e) => &mut e.header,
+            ClapEvent::Transport(e) => &mut e.header,
+            ClapEvent::MidiSysex { inner, .. } => &mut inner.header,
+        }
+    }
+
+    /// Set the note ID of a note or note expression event. Other events
+    /// are unchanged.
+    pub fn with_note_id(mut self, note_id: i32) -> Self {
+        match &mut self {
+            ClapEvent::NoteOn(e)
+            | ClapEvent::NoteOff(e)
+            | ClapEvent::NoteChoke(e)
+            | ClapEvent::NoteEnd(e) => e.note_id = note_id,
+            ClapEvent::NoteExpression(e) => e.note_id = note_id,
+            _ => {}
+        }
+        self
+    }
+
+    /// Set the port of a note, note expression or MIDI event. Other events
+    /// are unchanged. MIDI and sysex events have no any-port wildcard, so
+    /// -1 puts them on port 0.
+    pub fn with_port_index(mut self, port_index: i16) -> Self {
+        match &mut self {
+            ClapEvent::NoteOn(e)
+            | ClapEvent::NoteOff(e)
+            | ClapEvent::NoteChoke(e)
+            | ClapEvent::NoteEnd(e) => e.port_index = port_index,
+            ClapEvent::NoteExpression(e) => e.port_index = port_index,
+            ClapEvent::Midi(e) => e.port_index = port_index.max(0) as u16,
+            ClapEvent::MidiSysex { inner, .. } => inner.port_index = port_index.max(0) as u16,
+            _ => {}
+        }
+        self
+    }
+
+    pub fn transport(time: u32, transport: &TransportInfo) -> Self {
+        ClapEvent::Transport(build_clap_transport(transport, time))
+    }
+
     pub fn note_on(time: u32, channel: i16, key: i16, velocity: f64) -> Self {
         ClapEvent::NoteOn(clap_event_note {
             header: clap_event_header {
@@ -114,6 +175,26 @@ impl ClapEvent {
         })
     }
 
+    /// A sysex event holding `data`. An `Arc<[u8]>` is shared, not copied.
+    pub fn sysex(time: u32, port_index: u16, data: impl Into<Arc<[u8]>>) -> Self {
+        let data = data.into();
+        ClapEvent::MidiSysex {
+            inner: clap_event_midi_sysex {
+                header: clap_event_header {
+                    size: std::mem::size_of::<clap_event_midi_sysex>() as u32,
+                    time,
+                    space_id: CLAP_CORE_EVENT_SPACE_ID,
+                    type_: CLAP_EVENT_MIDI_SYSEX,
+                    flags: 0,
+                },
+                port_index,
+                buffer: data.as_ptr(),
+                size: data.len() as u32,
+            },
+            _data: data,
+        }
+    }
+
     pub fn param_value(time: u32, param_id: u32, value: f64) -> Self {
         ClapEvent::ParamValue(clap_event_param_value {
             header: clap_event_header {
@@ -133,6 +214,27 @@ impl ClapEvent {
         })
     }
 
+    /// A modulation `amount` for every voice. Target a voice by setting
+    /// the note fields on the returned event.
+    pub fn param_mod(time: u32, param_id: u32, amount: f64) -> Self {
+        ClapEvent::ParamMod(clap_event_param_mod {
+            header: clap_event_header {
+                size: std::mem::size_of::<clap_event_param_mod>() as u32,
+                time,
+                space_id: CLAP_CORE_EVENT_SPACE_ID,
+                type_: CLAP_EVENT_PARAM_MOD,
+                flags: 0,
+            },
+            param_id,
+            cookie: ptr::null_mut(),
+            note_id: -1,
+            port_index: -1,
+            channel: -1,
+            key: -1,
+            amount,
+        })
+    }
+
     pub fn note_expression(
         time: u32,
         expression_type: NoteExpressionType,
@@ -166,35 +268,47 @@ impl ClapEvent {
         })
     }
 
+    /// Convert a [`MidiEvent`]. Sysex events share the message's bytes
+    /// rather than copying them. Empty sysex messages are dropped.
     pub fn from_midi_event(event: &MidiEvent) -> Option<Self> {
         let time = event.sample_offset as u32;
         let channel = event.channel as i16;
+        let port = event.port_index;
+        // MIDI-dialect events have no any-port wildcard
+        let midi_port = port.max(0) as u16;
 
         match event.data {
-            MidiData::NoteOn { key, velocity } => {
-                Some(ClapEvent::note_on(time, channel, key as i16, velocity))
-            }
-            MidiData::NoteOff { key, velocity } => {
-                Some(ClapEvent::note_off(time, channel, key as i16, velocity))
+            MidiData::Sysex(ref data) => {
+                (!data.is_empty()).then(|| ClapEvent::sysex(time, midi_port, data.clone()))
             }
+            MidiData::NoteOn { key, velocity } => Some(
+                ClapEvent::note_on(time, channel, key as i16, velocity)
+                    .with_note_id(event.note_id)
+                    .with_port_index(port),
+            ),
+            MidiData::NoteOff { key, velocity } => Some(
+                ClapEvent::note_off(time, channel, key as i16, velocity)
+                    .with_note_id(event.note_id)
+                    .with_port_index(port),
+            ),
             MidiData::ControlChange { controller, value } => Some(ClapEvent::midi(
                 time,
-                0,
+                midi_port,
                 [0xB0 | (channel as u8), controller, value],
             )),
             MidiData::ProgramChange { program } => Some(ClapEvent::midi(
                 time,
-                0,
+                midi_port,
                 [0xC0 | (channel as u8), program, 0],
             )),
             MidiData::ChannelPressure { pressure } => Some(ClapEvent::midi(
                 time,
-                0,
+                midi_port,
                 [0xD0 | (channel as u8), pressure, 0],
             )),
             MidiData::PitchBend { value } => Some(ClapEvent::midi(
                 time,
-                0,
+                midi_port,
                 [
                     0xE0 | (channel as u8),
                     (value & 0x7F) as u8,
@@ -205,7 +319,7 @@ impl ClapEvent {
                 let pressure_byte = (pressure * 127.0) as u8;
                 Some(ClapEvent::midi(
                     time,
-                    0,
+                    midi_port,
                     [0xA0 | (channel as u8), key, pressure_byte],
                 ))
             }
@@ -221,6 +335,8 @@ impl ClapEvent {
                     key: e.key as u8,
                     velocity: e.velocity,
                 },
+                note_id: e.note_id,
+                port_index: e.port_index,
             }),
             ClapEvent::NoteOff(e) => Some(MidiEvent {
                 sample_offset: e.header.time as i32,
@@ -229,6 +345,8 @@ impl ClapEvent {
                     key: e.key as u8,
                     velocity: e.velocity,
                 },
+                note_id: e.note_id,
+                port_index: e.port_index,
             }),
             ClapEvent::Midi(e) => {
                 let status = e.data[0];
@@ -263,11 +381,38 @@ impl ClapEvent {
                     sample_offset: e.header.time as i32,
                     channel,
                     data,
+                    note_id: -1,
+                    port_index: e.port_index as i16,
                 })
             }
             _ => None,
         }
     }
+
+    pub fn to_note_end(&self) -> Option<NoteEnd> {
+        match self {
+            ClapEvent::NoteEnd(e) => Some(NoteEnd {
+                sample_offset: e.header.time as i32,
+                port_index: e.port_index,
+                channel: e.channel,
+                key: e.key,
+                note_id: e.note_id,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Receives the events a plugin emitted during a process call, in the order
+/// it pushed them. See [`ClapInstance::process_with_sink`](crate::ClapInstance::process_with_sink).
+pub trait OutputEventSink {
+    fn event(&mut self, event: &ClapEvent);
+}
+
+impl<F: FnMut(&ClapEvent)> OutputEventSink for F {
+    fn event(&mut self, event: &ClapEvent) {
+        self(event)
+    }
 }
 
 pub trait EventList {
@@ -284,6 +429,8 @@ pub trait EventList {
 pub struct InputEventList {
     pub(crate) list: clap_input_events,
     pub(crate) events: Vec<ClapEvent>,
+    /// The events the plugin sees, if not all of them.
+    window: Option<Range<usize>>,
 }
 
 impl InputEventList {
@@ -295,9 +442,15 @@ impl InputEventList {
                 get: Some(input_events_get),
             },
             events: Vec::new(),
+            window: None,
         }
     }
 
+    /// An empty list that can hold `capacity` events before reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::from_events(Vec::with_capacity(capacity))
+    }
+
     pub fn from_events(events: Vec<ClapEvent>) -> Self {
         Self {
             list: clap_input_events {
@@ -306,6 +459,7 @@ impl InputEventList {
                 get: Some(input_events_get),
             },
             events,
+            window: None,
         }
     }
 
@@ -328,11 +482,32 @@ impl InputEventList {
     pub fn add_param_changes(&mut self, changes: &ParameterChanges) -> &mut Self {
         for queue in &changes.queues {
             for point in &queue.points {
-                self.events.push(ClapEvent::param_value(
-                    point.sample_offset as u32,
-                    queue.param_id,
-                    point.value,
-                ));
+                let mut event =
+                    ClapEvent::param_value(point.sample_offset as u32, queue.param_id, point.value);
+                if let ClapEvent::ParamValue(e) = &mut event {
+                    e.note_id = point.note_id;
+                    e.port_index = point.port_index;
+                    e.channel = point.channel;
+                    e.key = point.key;
+                }
+                self.events.push(event);
+            }
+        }
+        self
+    }
+
+    pub fn add_param_modulations(&mut self, modulations: &ParameterModulations) -> &mut Self {
+        for queue in &modulations.queues {
+            for point in &queue.points {
+                let mut event =
+                    ClapEvent::param_mod(point.sample_offset as u32, queue.param_id, point.amount);
+                if let ClapEvent::ParamMod(e) = &mut event {
+                    e.note_id = queue.note_id;
+                    e.port_index = queue.port_index;
+                    e.channel = queue.channel;
+                    e.key = queue.key;
+                }
+                self.events.push(event);
             }
         }
         self
@@ -350,8 +525,30 @@ impl InputEventList {
         self
     }
 
+    pub fn add_transport_changes(&mut self, changes: &[TransportChange]) -> &mut Self {
+        for change in changes {
+            self.events.push(ClapEvent::transport(
+                change.sample_offset,
+                &change.transport,
+            ));
+        }
+        self
+    }
+
+    /// Stable sort by sample time, in place and without allocating, since it
+    /// runs on the audio thread. Events are usually added one type at a
+    /// time, so each ascending run is merged into the sorted prefix.
     pub fn sort_by_time(&mut self) -> &mut Self {
-        self.events.sort_by_key(|e| e.header().time);
+        let events = &mut self.events[..];
+        let mut sorted = events.len().min(1);
+        while sorted < events.len() {
+            let run = 1 + events[sorted..]
+                .windows(2)
+                .take_while(|w| w[0].header().time <= w[1].header().time)
+                .count();
+            merge_by_time(&mut events[..sorted + run], sorted);
+            sorted += run;
+        }
         self
     }
 
@@ -362,6 +559,38 @@ impl InputEventList {
     pub fn events(&self) -> &[ClapEvent] {
         &self.events
     }
+
+    /// Show the plugin only `range` of the events, e.g. those of one
+    /// sub-block. `None` shows all of them. Reset by `clear()`.
+    pub(crate) fn set_window(&mut self, range: Option<Range<usize>>) {
+        self.window = range;
+    }
+
+    fn visible(&self) -> &[ClapEvent] {
+        match &self.window {
+            Some(range) => &self.events[range.clone()],
+            None => &self.events,
+        }
+    }
+}
+
+/// Merge the sorted halves `events[..mid]` and `events[mid..]` in place,
+/// rotating each block of right-hand events ahead of the left-hand events
+/// that come after them. Events with equal times keep their order.
+fn merge_by_time(events: &mut [ClapEvent], mut mid: usize) {
+    let mut left = 0;
+    while left < mid && mid < events.len() {
+        let time = events[mid].header().time;
+        left += events[left..mid].partition_point(|e| e.header().time <= time);
+        if left == mid {
+            break;
+        }
+        let time = events[left].header().time;
+        let moved = events[mid..].partition_point(|e| e.header().time < time);
+        events[left..mid + moved].rotate_left(mid - left);
+        left += moved;
+        mid += moved;
+    }
 }
 
 impl Default for InputEventList {
@@ -377,23 +606,24 @@ impl EventList for InputEventList {
 
     fn clear(&mut self) {
         self.events.clear();
+        self.window = None;
     }
 }
 
 unsafe extern "C" fn input_events_size(list: *const clap_input_events) -> u32 {
     let event_list = &*(list as *const InputEventList);
-    event_list.events.len() as u32
+    event_list.visible().len() as u32
 }
 
 unsafe extern "C" fn input_events_get(
     list: *const clap_input_events,
     index: u32,
 ) -> *const clap_event_header {
-    let event_list = &*(list as *const InputEventList);
-    if index >= event_list.events.len() as u32 {
+    let events = (*(list as *const InputEventList)).visible();
+    if index >= events.len() as u32 {
         return ptr::null();
     }
-    event_list.events[index as usize].header() as *const _
+    events[index as usize].header() as *const _
 }
 
 #[repr(C)]
@@ -413,6 +643,13 @@ impl OutputEventList {
         }
     }
 
+    /// An empty list that can hold `capacity` events before reallocating.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut list = Self::new();
+        list.events.reserve(capacity);
+        list
+    }
+
     pub fn as_raw_mut(&mut self) -> *mut clap_output_events {
         &mut self.list as *mut _ as *mut _
     }
@@ -426,69 +663,92 @@ impl OutputEventList {
     }
 
     pub fn to_midi_events(&self) -> Vec<MidiEvent> {
-        self.events
-            .iter()
-            .filter_map(|e| e.to_midi_event())
-            .collect()
+        let mut events = Vec::new();
+        self.read_midi_events(&mut events);
+        events
     }
 
+    /// Append the MIDI events to `out`, reusing its storage.
+    pub fn read_midi_events(&self, out: &mut Vec<MidiEvent>) {
+        out.extend(self.events.iter().filter_map(|e| e.to_midi_event()));
+    }
+
+    /// Voices the plugin reported as finished.
+    pub fn to_note_ends(&self) -> Vec<NoteEnd> {
+        let mut ends = Vec::new();
+        self.read_note_ends(&mut ends);
+        ends
+    }
+
+    pub fn read_note_ends(&self, out: &mut Vec<NoteEnd>) {
+        out.extend(self.events.iter().filter_map(|e| e.to_note_end()));
+    }
+
+    /// Parameter changes grouped into one queue per parameter, in the order
+    /// each parameter first appears.
     pub fn to_param_changes(&self) -> ParameterChanges {
         let mut changes = ParameterChanges::new();
-        let mut queues: std::collections::HashMap<u32, ParameterQueue> =
-            std::collections::HashMap::new();
+        self.read_param_changes(&mut changes);
+        changes
+    }
 
+    /// Add the parameter changes to `changes`, appending to an existing
+    /// queue for the same parameter.
+    pub fn read_param_changes(&self, changes: &mut ParameterChanges) {
         for event in &self.events {
             if let ClapEvent::ParamValue(e) = event {
-                queues
-                    .entry(e.param_id)
-                    .or_insert_with(|| ParameterQueue::new(e.param_id))
-                    .points
-                    .push(ParameterPoint {
-                        sample_offset: e.header.time as i32,
-                        value: e.value,
-                    });
+                let point = ParameterPoint {
+                    sample_offset: e.header.time as i32,
+                    value: e.value,
+                    note_id: e.note_id,
+                    port_index: e.port_index,
+                    channel: e.channel,
+                    key: e.key,
+                };
+                match changes.queues.iter_mut().find(|q| q.param_id == e.param_id) {
+                    Some(queue) => queue.points.push(point),
+                    None => {
+                        let mut queue = ParameterQueue::new(e.param_id);
+                        queue.points.push(point);
+                        changes.add_queue(queue);
+                    }
+                }
             }
         }
-
-        for (_, queue) in queues {
-            changes.add_queue(queue);
-        }
-        changes
     }
 
     pub fn to_note_expressions(&self) -> Vec<NoteExpressionValue> {
-        self.events
-            .iter()
-            .filter_map(|e| {
-                if let ClapEvent::NoteExpression(ne) = e {
-                    let expression_type = match ne.expression_id {
-                        id if id == CLAP_NOTE_EXPRESSION_VOLUME => NoteExpressionType::Volume,
-                        id if id == CLAP_NOTE_EXPRESSION_PAN => NoteExpressionType::Pan,
-                        id if id == CLAP_NOTE_EXPRESSION_TUNING => NoteExpressionType::Tuning,
-                        id if id == CLAP_NOTE_EXPRESSION_VIBRATO => NoteExpressionType::Vibrato,
-                        id if id == CLAP_NOTE_EXPRESSION_BRIGHTNESS => {
-                            NoteExpressionType::Brightness
-                        }
-                        id if id == CLAP_NOTE_EXPRESSION_PRESSURE => NoteExpressionType::Pressure,
-                        id if id == CLAP_NOTE_EXPRESSION_EXPRESSION => {
-                            NoteExpressionType::Expression
-                        }
-                        _ => return None,
-                    };
-                    Some(NoteExpressionValue {
-                        sample_offset: ne.header.time as i32,
-                        note_id: ne.note_id,
-                        port_index: ne.port_index,
-                        channel: ne.channel,
-                        key: ne.key,
-                        expression_type,
-                        value: ne.value,
-                    })
-                } else {
-                    None
-                }
-            })
-            .collect()
+        let mut expressions = Vec::new();
+        self.read_note_expressions(&mut expressions);
+        expressions
+    }
+
+    pub fn read_note_expressions(&self, out: &mut Vec<NoteExpressionValue>) {
+        out.extend(self.events.iter().filter_map(|e| {
+            if let ClapEvent::NoteExpression(ne) = e {
+                let expression_type = match ne.expression_id {
+                    id if id == CLAP_NOTE_EXPRESSION_VOLUME => NoteExpressionType::Volume,
+                    id if id == CLAP_NOTE_EXPRESSION_PAN => NoteExpressionType::Pan,
+                    id if id == CLAP_NOTE_EXPRESSION_TUNING => NoteExpressionType::Tuning,
+                    id if id == CLAP_NOTE_EXPRESSION_VIBRATO => NoteExpressionType::Vibrato,
+                    id if id == CLAP_NOTE_EXPRESSION_BRIGHTNESS => NoteExpressionType::Brightness,
+                    id if id == CLAP_NOTE_EXPRESSION_PRESSURE => NoteExpressionType::Pressure,
+                    id if id == CLAP_NOTE_EXPRESSION_EXPRESSION => NoteExpressionType::Expression,
+                    _ => return None,
+                };
+                Some(NoteExpressionValue {
+                    sample_offset: ne.header.time as i32,
+                    note_id: ne.note_id,
+                    port_index: ne.port_index,
+                    channel: ne.channel,
+                    key: ne.key,
+                    expression_type,
+                    value: ne.value,
+                })
+            } else {
+                None
+            }
+        }));
     }
 }
 
@@ -573,9 +833,9 @@ unsafe extern "C" fn output_events_try_push(
         CLAP_EVENT_MIDI_SYSEX => {
             let e = &*(event as *const clap_event_midi_sysex);
             if !e.buffer.is_null() && e.size > 0 {
-                let data = std::slice::from_raw_parts(e.buffer, e.size as usize).to_vec();
-                // Build the inner struct with a pointer into the owned Vec.
-                // The Vec is stored alongside and won't be moved independently.
+                let data: Arc<[u8]> = std::slice::from_raw_parts(e.buffer, e.size as usize).into();
+                // Build the inner struct with a pointer into the shared buffer,
+                // which is stored alongside and doesn't move with the event.
                 let inner = clap_event_midi_sysex {
                     header: *header,
                     port_index: e.port_index,
@@ -592,6 +852,59 @@ unsafe extern "C" fn output_events_try_push(
     }
 }
 
+/// Convert a [`TransportInfo`] to a CLAP transport event at `time`. Only
+/// the fields marked known get their `CLAP_TRANSPORT_HAS_*` flag.
+pub(crate) fn build_clap_transport(transport: &TransportInfo, time: u32) -> clap_event_transport {
+    let known = transport.known;
+    let mut flags: u32 = 0;
+    if known.contains(TransportFields::TEMPO) {
+        flags |= CLAP_TRANSPORT_HAS_TEMPO;
+    }
+    if known.contains(TransportFields::BEATS_TIMELINE) {
+        flags |= CLAP_TRANSPORT_HAS_BEATS_TIMELINE;
+    }
+    if known.contains(TransportFields::SECONDS_TIMELINE) {
+        flags |= CLAP_TRANSPORT_HAS_SECONDS_TIMELINE;
+    }
+    if known.contains(TransportFields::TIME_SIGNATURE) {
+        flags |= CLAP_TRANSPORT_HAS_TIME_SIGNATURE;
+    }
+    if transport.playing {
+        flags |= CLAP_TRANSPORT_IS_PLAYING;
+    }
+    if transport.recording {
+        flags |= CLAP_TRANSPORT_IS_RECORDING;
+    }
+    if transport.cycle_active {
+        flags |= CLAP_TRANSPORT_IS_LOOP_ACTIVE;
+    }
+
+    let beats = |b: f64| (b * CLAP_BEATTIME_FACTOR as f64).round() as i64;
+    let seconds = |s: f64| (s * CLAP_SECTIME_FACTOR as f64).round() as i64;
+    clap_event_transport {
+        header: clap_event_header {
+            size: std::mem::size_of::<clap_event_transport>() as u32,
+            time,
+            space_id: CLAP_CORE_EVENT_SPACE_ID,
+            type_: CLAP_EVENT_TRANSPORT,
+            flags: 0,
+        },
+        flags,
+        song_pos_beats: beats(transport.song_pos_beats),
+        song_pos_seconds: seconds(transport.song_pos_seconds),
+        tempo: transport.tempo,
+        tempo_inc: transport.tempo_inc,
+        loop_start_beats: beats(transport.loop_start_beats),
+        loop_end_beats: beats(transport.loop_end_beats),
+        loop_start_seconds: seconds(transport.loop_start_seconds),
+        loop_end_seconds: seconds(transport.loop_end_seconds),
+        bar_start: beats(transport.bar_start),
+        bar_number: transport.bar_number,
+        tsig_num: transport.time_sig_numerator as u16,
+        tsig_denom: transport.time_sig_denominator as u16,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -707,7 +1020,7 @@ mod tests {
         // Verify the data was copied (not just pointer stored)
         match &output.events()[0] {
             ClapEvent::MidiSysex { _data, .. } => {
-                assert_eq!(_data, &sysex_data);
+                assert_eq!(&_data[..], &sysex_data[..]);
             }
             _ => panic!("Expected MidiSysex event"),
         }
@@ -742,4 +1055,95 @@ mod tests {
         }
         assert!(output.events().is_empty());
     }
+
+    #[test]
+    fn test_build_clap_transport_flags_only_known_fields() {
+        let transport = TransportInfo::unknown().with_tempo(90.0).with_playing(true);
+        let raw = build_clap_transport(&transport, 0);
+        assert_eq!(
+            raw.flags,
+            CLAP_TRANSPORT_HAS_TEMPO | CLAP_TRANSPORT_IS_PLAYING
+        );
+
+        let raw = build_clap_transport(&TransportInfo::new(), 0);
+        let all = CLAP_TRANSPORT_HAS_TEMPO
+            | CLAP_TRANSPORT_HAS_BEATS_TIMELINE
+            | CLAP_TRANSPORT_HAS_SECONDS_TIMELINE
+            | CLAP_TRANSPORT_HAS_TIME_SIGNATURE;
+        assert_eq!(raw.flags, all);
+        assert_eq!(
+            build_clap_transport(&TransportInfo::default(), 0).flags,
+            all
+        );
+    }
+
+    #[test]
+    fn test_build_clap_transport_rounds_positions() {
+        // 0.1 beats is 214748364.8 in fixed point, 1/3 s is 715827882.67
+        let transport = TransportInfo::new()
+            .with_position(0.1, 1.0 / 3.0)
+            .with_loop(true, 0.1, 4.0);
+        let raw = build_clap_transport(&transport, 0);
+        assert_eq!(raw.song_pos_beats, 214748365);
+        assert_eq!(raw.song_pos_seconds, 715827883);
+        assert_eq!(raw.loop_start_beats, 214748365);
+        assert_eq!(raw.loop_end_beats, 4 * CLAP_BEATTIME_FACTOR);
+    }
+
+    #[test]
+    fn test_build_clap_transport_ramp_and_loop_seconds() {
+        let transport = TransportInfo::new()
+            .with_tempo(120.0)
+            .with_tempo_inc(0.001)
+            .with_loop(true, 4.0, 8.0)
+            .with_loop_seconds(2.0, 4.0);
+        let raw = build_clap_transport(&transport, 64);
+        assert_eq!(raw.header.time, 64);
+        assert_eq!(raw.header.type_, CLAP_EVENT_TRANSPORT);
+        assert_eq!(raw.tempo_inc, 0.001);
+        assert_eq!(raw.loop_start_beats, 4 * CLAP_BEATTIME_FACTOR);
+        assert_eq!(raw.loop_start_seconds, 2 * CLAP_SECTIME_FACTOR);
+        assert_eq!(raw.loop_end_seconds, 4 * CLAP_SECTIME_FACTOR);
+        assert_ne!(raw.flags & CLAP_TRANSPORT_IS_LOOP_ACTIVE, 0);
+    }
+
+    #[test]
+    fn test_transport_changes_sorted_with_other_events() {
+        let mut list = InputEventList::new();
+        list.add_midi(&MidiEvent::note_on(100, 0, 60, 100));
+        list.add_transport_changes(&[TransportChange {
+            sample_offset: 50,
+            transport: TransportInfo::new().with_tempo(140.0),
+        }]);
+        list.sort_by_time();
+
+        match &list.events()[0] {
+            ClapEvent::Transport(e) => {
+                assert_eq!(e.header.time, 50);
+                assert_eq!(e.tempo, 140.0);
+            }
+            _ => panic!("Expected Transport"),
+        }
+        assert_eq!(list.events()[1].header().time, 100);
+    }
+
+    #[test]
+    fn test_window_limits_what_the_plugin_sees() {
+        let mut list = InputEventList::new();
+        for time in [0, 10, 20] {
+            list.add_midi(&MidiEvent::note_on(time, 0, 60, 100));
+        }
+        let raw = list.as_raw();
+        list.set_window(Some(1..3));
+        unsafe {
+            assert_eq!(input_events_size(raw), 2);
+            assert_eq!((*input_events_get(raw, 0)).time, 10);
+            assert!(input_events_get(raw, 2).is_null());
+        }
+        list.clear();
+        list.add_midi(&MidiEvent::note_on(0, 0, 60, 100));
+        unsafe {
+            assert_eq!(input_events_size(raw), 1);
+        }
+    }
 }
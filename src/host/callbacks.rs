@@ -1,6 +1,7 @@
 #[cfg(unix)]
 use super::state::PosixFdEntry;
 use super::state::{HostState, TimerEntry};
+use crate::cstr_to_string;
 use crate::types::{TransportRequest, UndoChange};
 use clap_sys::ext::ambisonic::{clap_host_ambisonic, CLAP_PORT_AMBISONIC};
 use clap_sys::ext::audio_ports::{clap_host_audio_ports, CLAP_PORT_MONO, CLAP_PORT_STEREO};
@@ -346,13 +347,26 @@ pub(super) static HOST_PRESET_LOAD: clap_host_preset_load = clap_host_preset_loa
 };
 
 unsafe extern "C" fn host_preset_load_on_error(
-    _host: *const clap_host,
+    host: *const clap_host,
     _location_kind: u32,
-    _location: *const c_char,
+    location: *const c_char,
     _load_key: *const c_char,
-    _os_error: i32,
-    _msg: *const c_char,
+    os_error: i32,
+    msg: *const c_char,
 ) {
+    if let Some(state) = get_host_state(host) {
+        let mut message = if msg.is_null() {
+            format!("Failed to load preset {}", cstr_to_string(location))
+        } else {
+            cstr_to_string(msg)
+        };
+        if os_error != 0 {
+            message = format!("{} (os error {})", message, os_error);
+        }
+        if let Ok(mut error) = state.processing.preset_error.lock() {
+            *error = Some(message);
+        }
+    }
 }
 
 unsafe extern "C" fn host_preset_load_loaded(
@@ -38,6 +38,8 @@ pub struct ProcessingState {
     pub tail_changed: AtomicBool,
     pub state_dirty: AtomicBool,
     pub preset_loaded: AtomicBool,
+    /// Message from the last `clap_host_preset_load.on_error` call.
+    pub preset_error: Mutex<Option<String>>,
     pub thread_pool_pending: AtomicU32,
 }
 
@@ -48,6 +50,7 @@ impl ProcessingState {
             tail_changed: AtomicBool::new(false),
             state_dirty: AtomicBool::new(false),
             preset_loaded: AtomicBool::new(false),
+            preset_error: Mutex::new(None),
             thread_pool_pending: AtomicU32::new(0),
         }
     }
@@ -2,24 +2,33 @@
 
 use super::ClapInstance;
 use crate::error::{ClapError, Result};
-use crate::events::{InputEventList, OutputEventList};
-use crate::types::{AudioBuffer, MidiEvent, NoteExpressionValue, ParameterChanges, TransportInfo};
-use clap_sys::audio_buffer::clap_audio_buffer;
-use clap_sys::events::{
-    clap_event_header, clap_event_transport, CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_TRANSPORT,
-    CLAP_TRANSPORT_HAS_BEATS_TIMELINE, CLAP_TRANSPORT_HAS_SECONDS_TIMELINE,
-    CLAP_TRANSPORT_HAS_TEMPO, CLAP_TRANSPORT_HAS_TIME_SIGNATURE, CLAP_TRANSPORT_IS_LOOP_ACTIVE,
-    CLAP_TRANSPORT_IS_PLAYING, CLAP_TRANSPORT_IS_RECORDING,
+use crate::events::{
+    build_clap_transport, ClapEvent, EventList, InputEventList, OutputEventList, OutputEventSink,
+};
+use crate::types::{
+    AudioBuffer, AudioPortFlags, InputPort, MidiEvent, NoteEnd, NoteExpressionValue,
+    ParameterChanges, ParameterModulations, PortBuffer, ProcessStatus, TransportChange,
+    TransportInfo,
 };
-use clap_sys::fixedpoint::{CLAP_BEATTIME_FACTOR, CLAP_SECTIME_FACTOR};
+use clap_sys::audio_buffer::clap_audio_buffer;
 use clap_sys::process::{clap_process, CLAP_PROCESS_CONTINUE, CLAP_PROCESS_ERROR};
+use smallvec::SmallVec;
+use std::any::Any;
 use std::ptr;
 
 #[derive(Debug, Clone, Default)]
 pub struct ProcessOutput {
+    pub status: ProcessStatus,
+    /// Latency in samples the plugin reported on each output port, indexed
+    /// by port. The plugin's own processing delay is reported separately by
+    /// [`ClapInstance::get_latency`].
+    pub output_latencies: SmallVec<[u32; 4]>,
     pub midi_events: Vec<MidiEvent>,
     pub param_changes: ParameterChanges,
     pub note_expressions: Vec<NoteExpressionValue>,
+    /// Voices the plugin finished during the block, with the note IDs they
+    /// were started with.
+    pub note_ends: Vec<NoteEnd>,
 }
 
 /// All inputs for a single process call. Use `..Default::default()` to fill
@@ -36,23 +45,39 @@ pub struct ProcessOutput {
 pub struct ProcessContext<'a> {
     pub midi: &'a [MidiEvent],
     pub params: Option<&'a ParameterChanges>,
+    /// Modulation sent as `CLAP_EVENT_PARAM_MOD`, on top of `params`.
+    pub modulations: Option<&'a ParameterModulations>,
     pub expressions: &'a [NoteExpressionValue],
+    /// Transport state at the start of the block.
     pub transport: Option<&'a TransportInfo>,
+    /// Tempo or position changes inside the block, sent as transport events
+    /// at their sample offsets.
+    pub transport_changes: &'a [TransportChange],
+}
+
+/// Initial event capacity of the preallocated input and output event lists.
+const EVENT_CAPACITY: usize = 1024;
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for f32 {}
+    impl Sealed for f64 {}
 }
 
 /// Trait abstracting over f32/f64 for CLAP audio buffer construction.
+/// Implemented for `f32` and `f64` only.
 ///
 /// CLAP's `clap_audio_buffer` has separate `data32` and `data64` fields.
 /// Each implementation populates the correct field and nulls the other.
-pub trait ClapSample: Copy + Default + 'static {
+pub trait ClapSample: sealed::Sealed + Copy + Default + PartialEq + 'static {
     fn requires_f64() -> bool;
 
-    fn build_port_buffers(
-        port_channels: &[u32],
-        ptrs: &mut Vec<*mut Self>,
-        scratch: &mut Vec<Vec<Self>>,
-        num_samples: usize,
-    ) -> Vec<clap_audio_buffer>;
+    fn to_f64(self) -> f64;
+
+    fn from_f64(value: f64) -> Self;
+
+    fn port_buffer(channels: *mut *mut Self, channel_count: u32) -> clap_audio_buffer;
 }
 
 impl ClapSample for f32 {
@@ -60,28 +85,22 @@ impl ClapSample for f32 {
         false
     }
 
-    fn build_port_buffers(
-        port_channels: &[u32],
-        ptrs: &mut Vec<*mut f32>,
-        scratch: &mut Vec<Vec<f32>>,
-        num_samples: usize,
-    ) -> Vec<clap_audio_buffer> {
-        pad_scratch(port_channels, ptrs, scratch, num_samples);
-        let mut offset = 0usize;
-        port_channels
-            .iter()
-            .map(|&ch_count| {
-                let buf = clap_audio_buffer {
-                    data32: ptrs[offset..].as_mut_ptr(),
-                    data64: ptr::null_mut(),
-                    channel_count: ch_count,
-                    latency: 0,
-                    constant_mask: 0,
-                };
-                offset += ch_count as usize;
-                buf
-            })
-            .collect()
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    fn port_buffer(channels: *mut *mut f32, channel_count: u32) -> clap_audio_buffer {
+        clap_audio_buffer {
+            data32: channels,
+            data64: ptr::null_mut(),
+            channel_count,
+            latency: 0,
+            constant_mask: 0,
+        }
     }
 }
 
@@ -90,42 +109,747 @@ impl ClapSample for f64 {
         true
     }
 
-    fn build_port_buffers(
-        port_channels: &[u32],
-        ptrs: &mut Vec<*mut f64>,
-        scratch: &mut Vec<Vec<f64>>,
-        num_samples: usize,
-    ) -> Vec<clap_audio_buffer> {
-        pad_scratch(port_channels, ptrs, scratch, num_samples);
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn port_buffer(channels: *mut *mut f64, channel_count: u32) -> clap_audio_buffer {
+        clap_audio_buffer {
+            data32: ptr::null_mut(),
+            data64: channels,
+            channel_count,
+            latency: 0,
+            constant_mask: 0,
+        }
+    }
+}
+
+/// Channel pointer arrays and scratch channels for one sample type.
+///
+/// Scratch channels stand in for channels the caller's [`AudioBuffer`]
+/// doesn't provide, so every port the plugin declared gets valid memory.
+#[derive(Default)]
+pub(crate) struct SampleBuffers<T> {
+    input_ptrs: Vec<*mut T>,
+    output_ptrs: Vec<*mut T>,
+    input_scratch: Vec<Vec<T>>,
+    output_scratch: Vec<Vec<T>>,
+}
+
+impl<T: ClapSample> SampleBuffers<T> {
+    /// Pick the preallocated storage for sample type `T`.
+    fn of<'a>(
+        f32_buffers: &'a mut SampleBuffers<f32>,
+        f64_buffers: &'a mut SampleBuffers<f64>,
+    ) -> &'a mut Self {
+        if let Some(buffers) = (f32_buffers as &mut dyn Any).downcast_mut() {
+            return buffers;
+        }
+        (f64_buffers as &mut dyn Any)
+            .downcast_mut()
+            .expect("ClapSample is only implemented for f32 and f64")
+    }
+
+    fn new(input_channels: usize, output_channels: usize, max_frames: usize) -> Self {
+        Self {
+            input_ptrs: Vec::with_capacity(input_channels),
+            output_ptrs: Vec::with_capacity(output_channels),
+            input_scratch: vec![vec![T::default(); max_frames]; input_channels],
+            output_scratch: vec![vec![T::default(); max_frames]; output_channels],
+        }
+    }
+}
+
+/// Storage reused by every process call, sized at activation from
+/// `max_frames` and the port channel counts so that steady-state processing
+/// does no heap allocation.
+#[derive(Default)]
+pub struct ProcessBuffers {
+    input_events: InputEventList,
+    output_events: OutputEventList,
+    input_ports: Vec<clap_audio_buffer>,
+    output_ports: Vec<clap_audio_buffer>,
+    /// Per-port precision chosen for [`ClapInstance::process_mixed`].
+    input_f64: Vec<bool>,
+    output_f64: Vec<bool>,
+    f32_buffers: SampleBuffers<f32>,
+    f64_buffers: SampleBuffers<f64>,
+}
+
+impl ProcessBuffers {
+    pub(super) fn new(
+        input_port_channels: &[u32],
+        output_port_channels: &[u32],
+        max_frames: u32,
+        supports_f64: bool,
+    ) -> Self {
+        let input_channels = input_port_channels.iter().map(|&c| c as usize).sum();
+        let output_channels = output_port_channels.iter().map(|&c| c as usize).sum();
+        let max_frames = max_frames as usize;
+        Self {
+            input_events: InputEventList::with_capacity(EVENT_CAPACITY),
+            output_events: OutputEventList::with_capacity(EVENT_CAPACITY),
+            input_ports: Vec::with_capacity(input_port_channels.len()),
+            output_ports: Vec::with_capacity(output_port_channels.len()),
+            input_f64: Vec::with_capacity(input_port_channels.len()),
+            output_f64: Vec::with_capacity(output_port_channels.len()),
+            f32_buffers: SampleBuffers::new(input_channels, output_channels, max_frames),
+            f64_buffers: if supports_f64 {
+                SampleBuffers::new(input_channels, output_channels, max_frames)
+            } else {
+                SampleBuffers::default()
+            },
+        }
+    }
+
+    /// Point the CLAP port buffers at `n` samples of the caller's channels
+    /// starting at `offset`, padding missing channels with scratch memory.
+    /// Input scratch is zeroed each block.
+    fn prepare_ports<T: ClapSample>(
+        &mut self,
+        buffer: &mut AudioBuffer<T>,
+        offset: usize,
+        n: usize,
+        input_port_channels: &[u32],
+        output_port_channels: &[u32],
+    ) {
+        let samples = SampleBuffers::<T>::of(&mut self.f32_buffers, &mut self.f64_buffers);
+
+        samples.input_ptrs.clear();
+        samples
+            .input_ptrs
+            .extend(buffer.inputs.iter().map(|s| s[offset..].as_ptr() as *mut T));
+        let provided = samples.input_ptrs.len();
+        pad_channels(
+            input_port_channels,
+            &mut samples.input_ptrs,
+            &mut samples.input_scratch,
+            n,
+            true,
+        );
+        // Zeroed scratch channels are always constant
+        let input_mask = (buffer.input_constant_mask & channel_bits(0, provided))
+            | channel_bits(provided, samples.input_ptrs.len() - provided);
+        fill_ports(
+            input_port_channels,
+            &mut samples.input_ptrs,
+            &mut self.input_ports,
+            input_mask,
+            buffer.input_latencies,
+        );
+
+        samples.output_ptrs.clear();
+        samples
+            .output_ptrs
+            .extend(buffer.outputs.iter_mut().map(|s| s[offset..].as_mut_ptr()));
+        pad_channels(
+            output_port_channels,
+            &mut samples.output_ptrs,
+            &mut samples.output_scratch,
+            n,
+            false,
+        );
+        fill_ports(
+            output_port_channels,
+            &mut samples.output_ptrs,
+            &mut self.output_ports,
+            0,
+            &[],
+        );
+    }
+
+    /// Point the CLAP port buffers at the caller's ports one to one,
+    /// starting `offset` samples in. The layout must already have been
+    /// validated.
+    fn prepare_port_buffer<T: ClapSample>(
+        &mut self,
+        buffer: &mut PortBuffer<T>,
+        offset: usize,
+        in_place_pairs: &[Option<usize>],
+    ) {
+        let samples = SampleBuffers::<T>::of(&mut self.f32_buffers, &mut self.f64_buffers);
+
+        samples.input_ptrs.clear();
+        for (i, port) in buffer.inputs.iter().enumerate() {
+            match in_place_source(port, in_place_pairs, i) {
+                // Same pointers as the paired output port
+                Some(output) => samples.input_ptrs.extend(
+                    buffer.outputs[output]
+                        .channels
+                        .iter_mut()
+                        .map(|s| s[offset..].as_mut_ptr()),
+                ),
+                None => samples
+                    .input_ptrs
+                    .extend(port.channels.iter().map(|s| s[offset..].as_ptr() as *mut T)),
+            }
+        }
+        self.input_ports.clear();
+        let mut first = 0usize;
+        for (i, port) in buffer.inputs.iter().enumerate() {
+            let channel_count = input_channel_count(buffer, in_place_pairs, i) as u32;
+            let mut raw = T::port_buffer(samples.input_ptrs[first..].as_mut_ptr(), channel_count);
+            raw.constant_mask = port.constant_mask & channel_bits(0, channel_count as usize);
+            raw.latency = port.latency;
+            self.input_ports.push(raw);
+            first += channel_count as usize;
+        }
+
+        samples.output_ptrs.clear();
+        for port in buffer.outputs.iter_mut() {
+            samples
+                .output_ptrs
+                .extend(port.channels.iter_mut().map(|s| s[offset..].as_mut_ptr()));
+        }
+        self.output_ports.clear();
+        let mut first = 0usize;
+        for port in buffer.outputs.iter() {
+            let channel_count = port.channels.len() as u32;
+            self.output_ports.push(T::port_buffer(
+                samples.output_ptrs[first..].as_mut_ptr(),
+                channel_count,
+            ));
+            first += channel_count as usize;
+        }
+    }
+
+    /// Like [`prepare_port_buffer`](Self::prepare_port_buffer), but each
+    /// port gets the precision picked by [`choose_precision`]. Channels of
+    /// ports whose precision differs from `T` are converted through
+    /// scratch memory.
+    fn prepare_mixed_ports<T: ClapSample>(
+        &mut self,
+        buffer: &mut PortBuffer<T>,
+        offset: usize,
+        n: usize,
+        in_place_pairs: &[Option<usize>],
+    ) {
+        let (f32s, f64s) = (&mut self.f32_buffers, &mut self.f64_buffers);
+
+        f32s.input_ptrs.clear();
+        f64s.input_ptrs.clear();
+        let (mut next32, mut next64) = (0, 0);
+        for (i, (port, &wide)) in buffer.inputs.iter().zip(&self.input_f64).enumerate() {
+            let mut bind = |ptr: *mut T, channel: &[T]| {
+                if wide {
+                    let ptr =
+                        bind_channel(ptr, Some(channel), &mut f64s.input_scratch, &mut next64, n);
+                    f64s.input_ptrs.push(ptr);
+                } else {
+                    let ptr =
+                        bind_channel(ptr, Some(channel), &mut f32s.input_scratch, &mut next32, n);
+                    f32s.input_ptrs.push(ptr);
+                }
+            };
+            match in_place_source(port, in_place_pairs, i) {
+                Some(output) => {
+                    for channel in buffer.outputs[output].channels.iter_mut() {
+                        let channel = &mut channel[offset..offset + n];
+                        bind(channel.as_mut_ptr(), channel);
+                    }
+                }
+                None => {
+                    for channel in port.channels {
+                        let channel = &channel[offset..offset + n];
+                        bind(channel.as_ptr() as *mut T, channel);
+                    }
+                }
+            }
+        }
+        self.input_ports.clear();
+        let (mut offset32, mut offset64) = (0, 0);
+        for (i, (port, &wide)) in buffer.inputs.iter().zip(&self.input_f64).enumerate() {
+            let channel_count = input_channel_count(buffer, in_place_pairs, i);
+            let mut raw = if wide {
+                offset64 += channel_count;
+                f64::port_buffer(
+                    f64s.input_ptrs[offset64 - channel_count..].as_mut_ptr(),
+                    channel_count as u32,
+                )
+            } else {
+                offset32 += channel_count;
+                f32::port_buffer(
+                    f32s.input_ptrs[offset32 - channel_count..].as_mut_ptr(),
+                    channel_count as u32,
+                )
+            };
+            raw.constant_mask = port.constant_mask & channel_bits(0, channel_count);
+            raw.latency = port.latency;
+            self.input_ports.push(raw);
+        }
+
+        f32s.output_ptrs.clear();
+        f64s.output_ptrs.clear();
+        let (mut next32, mut next64) = (0, 0);
+        for (port, &wide) in buffer.outputs.iter_mut().zip(&self.output_f64) {
+            for channel in port.channels.iter_mut() {
+                let ptr = channel[offset..].as_mut_ptr();
+                if wide {
+                    let ptr = bind_channel(ptr, None, &mut f64s.output_scratch, &mut next64, n);
+                    f64s.output_ptrs.push(ptr);
+                } else {
+                    let ptr = bind_channel(ptr, None, &mut f32s.output_scratch, &mut next32, n);
+                    f32s.output_ptrs.push(ptr);
+                }
+            }
+        }
+        self.output_ports.clear();
+        let (mut offset32, mut offset64) = (0, 0);
+        for (port, &wide) in buffer.outputs.iter().zip(&self.output_f64) {
+            let channel_count = port.channels.len();
+            self.output_ports.push(if wide {
+                offset64 += channel_count;
+                f64::port_buffer(
+                    f64s.output_ptrs[offset64 - channel_count..].as_mut_ptr(),
+                    channel_count as u32,
+                )
+            } else {
+                offset32 += channel_count;
+                f32::port_buffer(
+                    f32s.output_ptrs[offset32 - channel_count..].as_mut_ptr(),
+                    channel_count as u32,
+                )
+            });
+        }
+    }
+
+    /// Convert `n` samples of outputs rendered into scratch memory back to
+    /// `T` at `offset`, then [`read_back_ports`](Self::read_back_ports).
+    fn read_back_mixed_ports<T: ClapSample>(
+        &self,
+        buffer: &mut PortBuffer<T>,
+        offset: usize,
+        n: usize,
+    ) {
+        let (mut next32, mut next64) = (0, 0);
+        for (port, &wide) in buffer.outputs.iter_mut().zip(&self.output_f64) {
+            if wide == T::requires_f64() {
+                continue;
+            }
+            for channel in port.channels.iter_mut() {
+                if wide {
+                    convert(
+                        &self.f64_buffers.output_scratch[next64][..n],
+                        &mut channel[offset..offset + n],
+                    );
+                    next64 += 1;
+                } else {
+                    convert(
+                        &self.f32_buffers.output_scratch[next32][..n],
+                        &mut channel[offset..offset + n],
+                    );
+                    next32 += 1;
+                }
+            }
+        }
+        self.read_back_ports(buffer, offset);
+    }
+
+    /// Copy the mask and latency the plugin wrote on each output port back
+    /// to the caller's ports, for the sub-block starting at `offset`.
+    fn read_back_ports<T: PartialEq>(&self, buffer: &mut PortBuffer<T>, offset: usize) {
+        for (port, raw) in buffer.outputs.iter_mut().zip(&self.output_ports) {
+            let mask = raw.constant_mask & channel_bits(0, port.channels.len());
+            port.constant_mask =
+                merge_constant_mask(port.constant_mask, mask, &port.channels[..], offset);
+            port.latency = raw.latency;
+        }
+    }
+
+    /// Flatten the `constant_mask` the plugin wrote on each output port
+    /// into the caller's output channel numbering. Padded channels are
+    /// dropped.
+    fn output_constant_mask(&self, provided: usize) -> u64 {
+        let mut mask = 0;
         let mut offset = 0usize;
-        port_channels
-            .iter()
-            .map(|&ch_count| {
-                let buf = clap_audio_buffer {
-                    data32: ptr::null_mut(),
-                    data64: ptrs[offset..].as_mut_ptr(),
-                    channel_count: ch_count,
-                    latency: 0,
-                    constant_mask: 0,
-                };
-                offset += ch_count as usize;
-                buf
-            })
-            .collect()
+        for port in &self.output_ports {
+            if offset < 64 {
+                mask |=
+                    (port.constant_mask & channel_bits(0, port.channel_count as usize)) << offset;
+            }
+            offset += port.channel_count as usize;
+        }
+        mask & channel_bits(0, provided)
+    }
+
+    /// Show the plugin the input events of the sub-block starting at
+    /// `offset`, at most `max_len` samples long and ending early at a
+    /// parameter change if `split_at_params`. Events from `first_event`
+    /// on that fall in the sub-block are rebased to its start; the last
+    /// sub-block also takes any events past the end. Returns the sub-block
+    /// length and the index after its last event.
+    fn window_events(
+        &mut self,
+        offset: usize,
+        max_len: usize,
+        num_samples: usize,
+        first_event: usize,
+        split_at_params: bool,
+    ) -> (usize, usize) {
+        let events = &mut self.input_events.events;
+        let mut n = max_len;
+        if split_at_params {
+            let next_param = events[first_event..].iter().find(|e| {
+                matches!(e, ClapEvent::ParamValue(_)) && e.header().time as usize > offset
+            });
+            if let Some(event) = next_param {
+                n = n.min(event.header().time as usize - offset);
+            }
+        }
+        let end = offset + n;
+        let last_event = if end >= num_samples {
+            events.len()
+        } else {
+            first_event
+                + events[first_event..].partition_point(|e| (e.header().time as usize) < end)
+        };
+        for event in &mut events[first_event..last_event] {
+            let header = event.header_mut();
+            header.time = header.time.saturating_sub(offset as u32);
+        }
+        self.input_events.set_window(Some(first_event..last_event));
+        (n, last_event)
+    }
+
+    /// Refill the input event list from the process context.
+    fn prepare_events(&mut self, ctx: &ProcessContext<'_>) {
+        self.input_events.clear();
+        if !ctx.midi.is_empty() {
+            self.input_events.add_midi_events(ctx.midi);
+        }
+        if let Some(params) = ctx.params.filter(|p| !p.is_empty()) {
+            self.input_events.add_param_changes(params);
+        }
+        if let Some(modulations) = ctx.modulations.filter(|m| !m.is_empty()) {
+            self.input_events.add_param_modulations(modulations);
+        }
+        if !ctx.expressions.is_empty() {
+            self.input_events.add_note_expressions(ctx.expressions);
+        }
+        if !ctx.transport_changes.is_empty() {
+            self.input_events
+                .add_transport_changes(ctx.transport_changes);
+        }
+        self.input_events.sort_by_time();
+        self.output_events.clear();
     }
 }
 
-fn pad_scratch<T: Copy + Default>(
+fn pad_channels<T: Copy + Default>(
     port_channels: &[u32],
     ptrs: &mut Vec<*mut T>,
     scratch: &mut Vec<Vec<T>>,
     num_samples: usize,
+    zero: bool,
 ) {
     let total_needed: usize = port_channels.iter().map(|&c| c as usize).sum();
-    while ptrs.len() < total_needed {
-        scratch.push(vec![T::default(); num_samples]);
-        let buf = scratch.last_mut().expect("just pushed");
-        ptrs.push(buf.as_mut_ptr());
+    let provided = ptrs.len();
+    for i in provided..total_needed {
+        let channel = scratch_channel(scratch, i - provided, num_samples);
+        if zero {
+            channel.fill(T::default());
+        }
+        ptrs.push(channel.as_mut_ptr());
+    }
+}
+
+/// Pick 64-bit for a port that supports it and either prefers it or matches
+/// the host's sample type. Ports flagged `REQUIRES_COMMON_SAMPLE_SIZE` then
+/// all use 64-bit only if they all support it and one of them picked it.
+fn choose_precision(
+    input_flags: &[AudioPortFlags],
+    output_flags: &[AudioPortFlags],
+    host_f64: bool,
+    input_f64: &mut Vec<bool>,
+    output_f64: &mut Vec<bool>,
+) {
+    let pick = |flags: &AudioPortFlags| {
+        flags.contains(AudioPortFlags::SUPPORTS_64BIT)
+            && (host_f64 || flags.contains(AudioPortFlags::PREFERS_64BIT))
+    };
+    input_f64.clear();
+    input_f64.extend(input_flags.iter().map(pick));
+    output_f64.clear();
+    output_f64.extend(output_flags.iter().map(pick));
+
+    let common = || {
+        input_flags
+            .iter()
+            .zip(input_f64.iter())
+            .chain(output_flags.iter().zip(output_f64.iter()))
+            .filter(|(flags, _)| flags.contains(AudioPortFlags::REQUIRES_COMMON_SAMPLE_SIZE))
+    };
+    let wide = common().any(|(_, &wide)| wide)
+        && common().all(|(flags, _)| flags.contains(AudioPortFlags::SUPPORTS_64BIT));
+    let ports = input_flags
+        .iter()
+        .zip(input_f64.iter_mut())
+        .chain(output_flags.iter().zip(output_f64.iter_mut()));
+    for (flags, port_wide) in ports {
+        if flags.contains(AudioPortFlags::REQUIRES_COMMON_SAMPLE_SIZE) {
+            *port_wide = wide;
+        }
+    }
+}
+
+/// Pointer the plugin gets for one host channel in precision `S`: the
+/// channel itself if `S` is `T`, otherwise the next scratch channel, filled
+/// from `input` if given.
+fn bind_channel<T: ClapSample, S: ClapSample>(
+    channel: *mut T,
+    input: Option<&[T]>,
+    scratch: &mut Vec<Vec<S>>,
+    next: &mut usize,
+    num_samples: usize,
+) -> *mut S {
+    if T::requires_f64() == S::requires_f64() {
+        return channel.cast::<S>();
+    }
+    let converted = scratch_channel(scratch, *next, num_samples);
+    *next += 1;
+    if let Some(input) = input {
+        convert(input, converted);
+    }
+    converted.as_mut_ptr()
+}
+
+fn convert<A: ClapSample, B: ClapSample>(from: &[A], to: &mut [B]) {
+    for (to, &from) in to.iter_mut().zip(from) {
+        *to = B::from_f64(from.to_f64());
+    }
+}
+
+/// Scratch channel `index`, at least `num_samples` long. Only grows if
+/// activation under-sized the scratch, e.g. a block longer than max_frames.
+fn scratch_channel<T: Copy + Default>(
+    scratch: &mut Vec<Vec<T>>,
+    index: usize,
+    num_samples: usize,
+) -> &mut [T] {
+    if scratch.len() <= index {
+        scratch.resize_with(index + 1, Vec::new);
+    }
+    let channel = &mut scratch[index];
+    if channel.len() < num_samples {
+        channel.resize(num_samples, T::default());
+    }
+    &mut channel[..num_samples]
+}
+
+/// Port layout used by the flat [`AudioBuffer`] API: a direction with no
+/// declared ports still gets one stereo port.
+pub(super) fn flat_port_channels(port_channels: &[u32]) -> &[u32] {
+    if port_channels.is_empty() {
+        &[2]
+    } else {
+        port_channels
+    }
+}
+
+/// Check a [`PortBuffer`] against the plugin's declared port layout.
+/// In-place inputs must be paired with an output port; that port's channels
+/// are checked with the outputs.
+fn validate_ports<T>(
+    buffer: &PortBuffer<T>,
+    input_port_channels: &[u32],
+    output_port_channels: &[u32],
+    in_place_pairs: &[Option<usize>],
+) -> Result<()> {
+    for (i, port) in buffer.inputs.iter().enumerate() {
+        let declared = input_port_channels.get(i).copied().unwrap_or(0);
+        if port.is_in_place() && declared > 0 && in_place_output(in_place_pairs, i).is_none() {
+            return Err(ClapError::ProcessError(format!(
+                "Input port {} is in place but the plugin pairs no output port with it",
+                i
+            )));
+        }
+    }
+    let inputs = buffer.inputs.iter().enumerate().map(|(i, p)| {
+        if p.is_in_place() && in_place_output(in_place_pairs, i).is_some() {
+            None
+        } else {
+            Some(p.channels)
+        }
+    });
+    validate_direction("input", inputs, input_port_channels, buffer.num_samples)?;
+    let outputs = buffer.outputs.iter().map(|p| Some(&p.channels[..]));
+    validate_direction("output", outputs, output_port_channels, buffer.num_samples)
+}
+
+/// The output port whose channels `port` reads in place, if it is marked
+/// in place and paired.
+fn in_place_source<T>(
+    port: &InputPort<T>,
+    in_place_pairs: &[Option<usize>],
+    input: usize,
+) -> Option<usize> {
+    if port.is_in_place() {
+        in_place_output(in_place_pairs, input)
+    } else {
+        None
+    }
+}
+
+/// Channels the plugin sees on input port `input`.
+fn input_channel_count<T>(
+    buffer: &PortBuffer<T>,
+    in_place_pairs: &[Option<usize>],
+    input: usize,
+) -> usize {
+    let port = &buffer.inputs[input];
+    match in_place_source(port, in_place_pairs, input) {
+        Some(output) => buffer.outputs[output].channels.len(),
+        None => port.channels.len(),
+    }
+}
+
+/// The output port an input port is paired with, if any.
+fn in_place_output(in_place_pairs: &[Option<usize>], input: usize) -> Option<usize> {
+    in_place_pairs.get(input).copied().flatten()
+}
+
+/// `None` ports are in place and skipped.
+fn validate_direction<'a, C: AsRef<[T]> + 'a, T: 'a>(
+    direction: &str,
+    ports: impl ExactSizeIterator<Item = Option<&'a [C]>>,
+    port_channels: &[u32],
+    num_samples: usize,
+) -> Result<()> {
+    if ports.len() != port_channels.len() {
+        return Err(ClapError::ProcessError(format!(
+            "Plugin declares {} {} ports, got {}",
+            port_channels.len(),
+            direction,
+            ports.len()
+        )));
+    }
+    for (i, (channels, &expected)) in ports.zip(port_channels).enumerate() {
+        let Some(channels) = channels else {
+            continue;
+        };
+        if channels.len() != expected as usize {
+            return Err(ClapError::ProcessError(format!(
+                "Channel count mismatch on {} port {}: got {}, plugin declares {}",
+                direction,
+                i,
+                channels.len(),
+                expected
+            )));
+        }
+        if let Some(ch) = channels.iter().position(|c| c.as_ref().len() < num_samples) {
+            return Err(ClapError::ProcessError(format!(
+                "Channel {} of {} port {} is shorter than {} samples",
+                ch, direction, i, num_samples
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Fold the constant mask of the sub-block at `offset` into the mask of
+/// the sub-blocks before it. A channel stays constant only if it is
+/// constant in every sub-block with the same value.
+fn merge_constant_mask<T: PartialEq, C: AsRef<[T]>>(
+    mask: u64,
+    block_mask: u64,
+    channels: &[C],
+    offset: usize,
+) -> u64 {
+    if offset == 0 {
+        return block_mask;
+    }
+    let mut merged = mask & block_mask;
+    for (ch, channel) in channels.iter().enumerate().take(64) {
+        let channel = channel.as_ref();
+        if merged & (1 << ch) != 0 && channel.get(offset) != channel.first() {
+            merged &= !(1 << ch);
+        }
+    }
+    merged
+}
+
+/// Transport for the sub-block starting at `offset`: the latest transport
+/// change at or before it, or the block's transport, advanced to `offset`.
+/// Of changes at the same offset, the last one given wins.
+fn sub_block_transport(
+    ctx: &ProcessContext<'_>,
+    offset: usize,
+    sample_rate: f64,
+) -> Option<TransportInfo> {
+    if offset == 0 {
+        return ctx.transport.copied();
+    }
+    ctx.transport_changes
+        .iter()
+        .filter(|c| c.sample_offset as usize <= offset)
+        .max_by_key(|c| c.sample_offset)
+        .map(|c| advance_transport(&c.transport, offset - c.sample_offset as usize, sample_rate))
+        .or_else(|| {
+            ctx.transport
+                .map(|t| advance_transport(t, offset, sample_rate))
+        })
+}
+
+/// Transport state `samples` into a block that started with `transport`,
+/// assuming playback continued at the block's tempo. Stopped transports
+/// don't move.
+fn advance_transport(transport: &TransportInfo, samples: usize, sample_rate: f64) -> TransportInfo {
+    let mut advanced = *transport;
+    if !transport.playing || samples == 0 {
+        return advanced;
+    }
+    let seconds = samples as f64 / sample_rate;
+    // Average tempo over a ramp
+    let tempo = transport.tempo + transport.tempo_inc * samples as f64 / 2.0;
+    advanced.tempo += transport.tempo_inc * samples as f64;
+    advanced.song_pos_seconds += seconds;
+    advanced.song_pos_beats += seconds * tempo / 60.0;
+    if transport.time_sig_denominator > 0 && transport.time_sig_numerator > 0 {
+        let bar_length =
+            transport.time_sig_numerator as f64 * 4.0 / transport.time_sig_denominator as f64;
+        let bars = ((advanced.song_pos_beats - transport.bar_start) / bar_length).floor();
+        if bars > 0.0 {
+            advanced.bar_start += bars * bar_length;
+            advanced.bar_number += bars as i32;
+        }
+    }
+    advanced
+}
+
+/// Bits `start..start + count` of a channel mask, clamped to 64 channels.
+fn channel_bits(start: usize, count: usize) -> u64 {
+    let end = (start + count).min(64);
+    if start >= end {
+        return 0;
+    }
+    (u64::MAX >> (64 - (end - start))) << start
+}
+
+/// Build one CLAP port buffer per port. `constant_mask` is in flat channel
+/// numbering and is split into each port's own mask. `latencies` is indexed
+/// by port; missing entries are 0.
+fn fill_ports<T: ClapSample>(
+    port_channels: &[u32],
+    ptrs: &mut [*mut T],
+    ports: &mut Vec<clap_audio_buffer>,
+    constant_mask: u64,
+    latencies: &[u32],
+) {
+    ports.clear();
+    let mut offset = 0usize;
+    for (i, &channel_count) in port_channels.iter().enumerate() {
+        let mut port = T::port_buffer(ptrs[offset..].as_mut_ptr(), channel_count);
+        port.latency = latencies.get(i).copied().unwrap_or(0);
+        if offset < 64 {
+            port.constant_mask =
+                (constant_mask >> offset) & channel_bits(0, channel_count as usize);
+        }
+        ports.push(port);
+        offset += channel_count as usize;
     }
 }
 
@@ -136,6 +860,21 @@ impl ClapInstance {
     /// `AudioBuffer64` for f64. The f64 path automatically checks that the
     /// plugin advertises 64-bit support.
     ///
+    /// Event lists, port buffers and scratch channels are allocated once in
+    /// [`activate`](Self::activate) and reused, and input events of mixed
+    /// types are sorted in place, so sending a block allocates nothing.
+    /// Only the returned [`ProcessOutput`] may allocate, to hold output MIDI,
+    /// note expressions, note ends, or more parameter points than fit
+    /// inline. Use [`process_into`](Self::process_into) to reuse its
+    /// storage, or [`process_with_sink`](Self::process_with_sink) to
+    /// consume output events without converting them.
+    ///
+    /// A block longer than `max_frames` is processed as several sub-blocks
+    /// of at most `max_frames` samples, as are the port-based variants.
+    /// MIDI, parameter, expression and transport events are delivered to
+    /// the sub-block they fall in with their times rebased; output events
+    /// come back timed relative to the whole block.
+    ///
     /// ```ignore
     /// plugin.process(&mut buffer, &ProcessContext {
     ///     midi: &[MidiEvent::note_on(0, 0, 60, 100)],
@@ -148,109 +887,367 @@ impl ClapInstance {
         buffer: &mut AudioBuffer<T>,
         ctx: &ProcessContext<'_>,
     ) -> Result<ProcessOutput> {
-        if T::requires_f64() && !self.supports_f64 {
-            return Err(ClapError::ProcessError(format!(
-                "Plugin '{}' does not support 64-bit audio processing \
-                 (CLAP_AUDIO_PORT_SUPPORTS_64BITS not set)",
-                self.info.name
-            )));
+        let status = self.process_impl(buffer, ctx)?;
+        Ok(self.process_output(status))
+    }
+
+    /// Like [`process`](Self::process), but hands every event the plugin
+    /// emitted to `sink` instead of converting them into a [`ProcessOutput`].
+    /// All event types are delivered, including note end, choke, parameter
+    /// gestures and sysex, and nothing is allocated to do so.
+    ///
+    /// ```ignore
+    /// plugin.process_with_sink(&mut buffer, &ctx, &mut |event: &ClapEvent| {
+    ///     if let ClapEvent::NoteEnd(e) = event {
+    ///         voices.release(e.note_id);
+    ///     }
+    /// })?;
+    /// ```
+    pub fn process_with_sink<T: ClapSample, S: OutputEventSink + ?Sized>(
+        &mut self,
+        buffer: &mut AudioBuffer<T>,
+        ctx: &ProcessContext<'_>,
+        sink: &mut S,
+    ) -> Result<ProcessStatus> {
+        let status = self.process_impl(buffer, ctx)?;
+        for event in self.buffers.output_events.events() {
+            sink.event(event);
         }
-        let empty_params = ParameterChanges::new();
-        let params = ctx.params.unwrap_or(&empty_params);
-        self.process_impl(buffer, ctx.midi, params, ctx.expressions, ctx.transport)
+        Ok(status)
     }
 
-    fn process_impl<T: ClapSample>(
+    /// Like [`process`](Self::process), but refills a caller-owned `output`
+    /// instead of returning a new one. Its vectors keep their capacity, so
+    /// once they have grown to fit a block's events, processing doesn't
+    /// allocate.
+    ///
+    /// ```ignore
+    /// let mut output = ProcessOutput::default();
+    /// loop {
+    ///     plugin.process_into(&mut buffer, &ctx, &mut output)?;
+    ///     handle(&output.midi_events);
+    /// }
+    /// ```
+    pub fn process_into<T: ClapSample>(
         &mut self,
         buffer: &mut AudioBuffer<T>,
-        midi_events: &[MidiEvent],
-        param_changes: &ParameterChanges,
-        note_expressions: &[NoteExpressionValue],
-        transport: Option<&TransportInfo>,
-    ) -> Result<ProcessOutput> {
-        self.start_processing()?;
+        ctx: &ProcessContext<'_>,
+        output: &mut ProcessOutput,
+    ) -> Result<()> {
+        let status = self.process_impl(buffer, ctx)?;
+        self.fill_output(status, output);
+        Ok(())
+    }
+
+    /// Events the plugin emitted during the last process call. Cleared at
+    /// the start of the next one.
+    pub fn output_events(&self) -> &OutputEventList {
+        &self.buffers.output_events
+    }
 
-        let num_samples = buffer.num_samples as u32;
+    /// Latency in samples the plugin reported on each output port during
+    /// the last process call.
+    pub fn output_latencies(&self) -> impl ExactSizeIterator<Item = u32> + '_ {
+        self.buffers.output_ports.iter().map(|port| port.latency)
+    }
 
-        let mut input_events = InputEventList::new();
-        if !midi_events.is_empty() {
-            input_events.add_midi_events(midi_events);
-        }
-        if !param_changes.is_empty() {
-            input_events.add_param_changes(param_changes);
+    /// Process audio laid out by port. Each entry of `buffer.inputs` and
+    /// `buffer.outputs` is passed as the plugin port with the same index,
+    /// so sidechain inputs, aux outputs and multi-out instruments are
+    /// routed explicitly. Nothing is padded: a layout that doesn't match
+    /// the plugin's declared ports, or a channel shorter than
+    /// `num_samples`, is a [`ClapError::ProcessError`]. An input port built
+    /// with [`InputPort::in_place`] is given the same channel pointers as
+    /// its paired output port.
+    ///
+    /// Each output port's `constant_mask` and `latency` are updated from
+    /// what the plugin reported.
+    ///
+    /// ```ignore
+    /// let main: [&[f32]; 2] = [&main_l, &main_r];
+    /// let sidechain: [&[f32]; 1] = [&key];
+    /// let inputs = [InputPort::new(&main), InputPort::new(&sidechain)];
+    /// let mut outputs = [OutputPort::new(&mut main_out)];
+    /// let mut buffer = PortBuffer::new(&inputs, &mut outputs, 512, 48000.0);
+    /// plugin.process_ports(&mut buffer, &ctx)?;
+    /// ```
+    pub fn process_ports<T: ClapSample>(
+        &mut self,
+        buffer: &mut PortBuffer<T>,
+        ctx: &ProcessContext<'_>,
+    ) -> Result<ProcessOutput> {
+        let status = self.process_ports_impl(buffer, ctx)?;
+        Ok(self.process_output(status))
+    }
+
+    /// [`process_ports`](Self::process_ports) with output events handed to
+    /// `sink`, as in [`process_with_sink`](Self::process_with_sink).
+    pub fn process_ports_with_sink<T: ClapSample, S: OutputEventSink + ?Sized>(
+        &mut self,
+        buffer: &mut PortBuffer<T>,
+        ctx: &ProcessContext<'_>,
+        sink: &mut S,
+    ) -> Result<ProcessStatus> {
+        let status = self.process_ports_impl(buffer, ctx)?;
+        for event in self.buffers.output_events.events() {
+            sink.event(event);
         }
-        if !note_expressions.is_empty() {
-            input_events.add_note_expressions(note_expressions);
+        Ok(status)
+    }
+
+    /// [`process_ports`](Self::process_ports) refilling a caller-owned
+    /// `output`, as in [`process_into`](Self::process_into).
+    pub fn process_ports_into<T: ClapSample>(
+        &mut self,
+        buffer: &mut PortBuffer<T>,
+        ctx: &ProcessContext<'_>,
+        output: &mut ProcessOutput,
+    ) -> Result<()> {
+        let status = self.process_ports_impl(buffer, ctx)?;
+        self.fill_output(status, output);
+        Ok(())
+    }
+
+    /// Process audio laid out by port, choosing the sample precision of
+    /// each port separately. A port gets 64-bit samples if it supports them
+    /// and either prefers them (`CLAP_AUDIO_PORT_PREFERS_64BITS`) or the
+    /// host buffer is f64; otherwise 32-bit. Ports flagged
+    /// `CLAP_AUDIO_PORT_REQUIRES_COMMON_SAMPLE_SIZE` all get the same
+    /// precision. Where a port's precision differs from `T`, samples are
+    /// converted through preallocated scratch channels in both directions.
+    ///
+    /// Layout rules and write-back are the same as
+    /// [`process_ports`](Self::process_ports). Unlike it, f64 host buffers
+    /// work with any plugin.
+    pub fn process_mixed<T: ClapSample>(
+        &mut self,
+        buffer: &mut PortBuffer<T>,
+        ctx: &ProcessContext<'_>,
+    ) -> Result<ProcessOutput> {
+        let status = self.process_mixed_impl(buffer, ctx)?;
+        Ok(self.process_output(status))
+    }
+
+    /// [`process_mixed`](Self::process_mixed) with output events handed to
+    /// `sink`, as in [`process_with_sink`](Self::process_with_sink).
+    pub fn process_mixed_with_sink<T: ClapSample, S: OutputEventSink + ?Sized>(
+        &mut self,
+        buffer: &mut PortBuffer<T>,
+        ctx: &ProcessContext<'_>,
+        sink: &mut S,
+    ) -> Result<ProcessStatus> {
+        let status = self.process_mixed_impl(buffer, ctx)?;
+        for event in self.buffers.output_events.events() {
+            sink.event(event);
         }
-        input_events.sort_by_time();
+        Ok(status)
+    }
 
-        let mut output_events = OutputEventList::new();
+    /// [`process_mixed`](Self::process_mixed) refilling a caller-owned
+    /// `output`, as in [`process_into`](Self::process_into).
+    pub fn process_mixed_into<T: ClapSample>(
+        &mut self,
+        buffer: &mut PortBuffer<T>,
+        ctx: &ProcessContext<'_>,
+        output: &mut ProcessOutput,
+    ) -> Result<()> {
+        let status = self.process_mixed_impl(buffer, ctx)?;
+        self.fill_output(status, output);
+        Ok(())
+    }
 
-        let mut input_ptrs: Vec<*mut T> =
-            buffer.inputs.iter().map(|s| s.as_ptr() as *mut T).collect();
-        let mut output_ptrs: Vec<*mut T> =
-            buffer.outputs.iter_mut().map(|s| s.as_mut_ptr()).collect();
+    fn process_output(&self, status: ProcessStatus) -> ProcessOutput {
+        let mut output = ProcessOutput::default();
+        self.fill_output(status, &mut output);
+        output
+    }
+
+    /// Overwrite `output` with the last process call's results, keeping the
+    /// capacity of its vectors.
+    fn fill_output(&self, status: ProcessStatus, output: &mut ProcessOutput) {
+        let output_events = &self.buffers.output_events;
+        output.status = status;
+        output.output_latencies.clear();
+        output.output_latencies.extend(self.output_latencies());
+        output.midi_events.clear();
+        output_events.read_midi_events(&mut output.midi_events);
+        output.param_changes.queues.clear();
+        output_events.read_param_changes(&mut output.param_changes);
+        output.note_expressions.clear();
+        output_events.read_note_expressions(&mut output.note_expressions);
+        output.note_ends.clear();
+        output_events.read_note_ends(&mut output.note_ends);
+    }
+
+    fn process_impl<T: ClapSample>(
+        &mut self,
+        buffer: &mut AudioBuffer<T>,
+        ctx: &ProcessContext<'_>,
+    ) -> Result<ProcessStatus> {
+        self.check_sample_type::<T>()?;
+        self.begin_process(ctx)?;
+        let mut output_mask = 0;
+        let status =
+            self.process_blocks(buffer.num_samples, ctx, |this, offset, n, transport| {
+                this.buffers.prepare_ports(
+                    buffer,
+                    offset,
+                    n,
+                    flat_port_channels(&this.input_port_channels),
+                    flat_port_channels(&this.output_port_channels),
+                );
+                let status = this.do_process(n as u32, transport)?;
+                let mask = this.buffers.output_constant_mask(buffer.outputs.len());
+                output_mask = merge_constant_mask(output_mask, mask, buffer.outputs, offset);
+                Ok(status)
+            })?;
+        buffer.output_constant_mask = output_mask;
+        Ok(status)
+    }
 
-        let n = buffer.num_samples;
-        let mut scratch_in = Vec::new();
-        let mut scratch_out = Vec::new();
-        let mut input_bufs = T::build_port_buffers(
+    fn process_ports_impl<T: ClapSample>(
+        &mut self,
+        buffer: &mut PortBuffer<T>,
+        ctx: &ProcessContext<'_>,
+    ) -> Result<ProcessStatus> {
+        validate_ports(
+            buffer,
             &self.input_port_channels,
-            &mut input_ptrs,
-            &mut scratch_in,
-            n,
-        );
-        let mut output_bufs = T::build_port_buffers(
             &self.output_port_channels,
-            &mut output_ptrs,
-            &mut scratch_out,
-            n,
+            &self.in_place_pairs,
+        )?;
+        self.check_sample_type::<T>()?;
+        self.begin_process(ctx)?;
+        self.process_blocks(buffer.num_samples, ctx, |this, offset, n, transport| {
+            this.buffers
+                .prepare_port_buffer(buffer, offset, &this.in_place_pairs);
+            let status = this.do_process(n as u32, transport)?;
+            this.buffers.read_back_ports(buffer, offset);
+            Ok(status)
+        })
+    }
+
+    fn process_mixed_impl<T: ClapSample>(
+        &mut self,
+        buffer: &mut PortBuffer<T>,
+        ctx: &ProcessContext<'_>,
+    ) -> Result<ProcessStatus> {
+        validate_ports(
+            buffer,
+            &self.input_port_channels,
+            &self.output_port_channels,
+            &self.in_place_pairs,
+        )?;
+        self.begin_process(ctx)?;
+        choose_precision(
+            &self.input_port_flags,
+            &self.output_port_flags,
+            T::requires_f64(),
+            &mut self.buffers.input_f64,
+            &mut self.buffers.output_f64,
         );
+        self.process_blocks(buffer.num_samples, ctx, |this, offset, n, transport| {
+            this.buffers
+                .prepare_mixed_ports(buffer, offset, n, &this.in_place_pairs);
+            let status = this.do_process(n as u32, transport)?;
+            this.buffers.read_back_mixed_ports(buffer, offset, n);
+            Ok(status)
+        })
+    }
 
-        self.do_process(
-            &mut input_bufs,
-            &mut output_bufs,
-            num_samples,
-            &input_events,
-            &mut output_events,
-            transport,
-        )
+    /// Run `block` over sub-blocks of at most `max_frames` samples, also
+    /// split at parameter changes if
+    /// [`set_split_at_param_changes`](Self::set_split_at_param_changes) is
+    /// on. `block` gets the sub-block's offset, length and transport.
+    ///
+    /// Each sub-block sees only its own input events, with times rebased to
+    /// its start; output events are rebased back to the whole block.
+    /// Sub-blocks after the first get the latest transport change before
+    /// them, or the block's transport, advanced to their start. Returns the
+    /// status of the last sub-block.
+    fn process_blocks(
+        &mut self,
+        num_samples: usize,
+        ctx: &ProcessContext<'_>,
+        mut block: impl FnMut(&mut Self, usize, usize, Option<&TransportInfo>) -> Result<ProcessStatus>,
+    ) -> Result<ProcessStatus> {
+        let max_frames = match self.max_frames as usize {
+            0 => num_samples.max(1),
+            max_frames => max_frames,
+        };
+        let mut offset = 0;
+        let mut first_event = 0;
+        loop {
+            let (n, last_event) = self.buffers.window_events(
+                offset,
+                max_frames.min(num_samples - offset),
+                num_samples,
+                first_event,
+                self.split_at_param_changes,
+            );
+            let transport = sub_block_transport(ctx, offset, self.sample_rate);
+
+            let output_start = self.buffers.output_events.events.len();
+            let status = block(self, offset, n, transport.as_ref())?;
+            for event in &mut self.buffers.output_events.events[output_start..] {
+                event.header_mut().time += offset as u32;
+            }
+
+            first_event = last_event;
+            offset += n;
+            if offset >= num_samples {
+                return Ok(status);
+            }
+        }
+    }
+
+    /// Fail if `T` is f64 and not every port supports 64-bit samples.
+    fn check_sample_type<T: ClapSample>(&self) -> Result<()> {
+        if T::requires_f64() && !self.supports_f64 {
+            return Err(ClapError::ProcessError(format!(
+                "Plugin '{}' does not support 64-bit audio processing \
+                 (CLAP_AUDIO_PORT_SUPPORTS_64BITS not set on every port)",
+                self.info.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Setup shared by every process variant: the processing state and the
+    /// input event list.
+    fn begin_process(&mut self, ctx: &ProcessContext<'_>) -> Result<()> {
+        self.start_processing()?;
+        self.buffers.prepare_events(ctx);
+        Ok(())
     }
 
     fn do_process(
         &mut self,
-        audio_inputs: &mut [clap_audio_buffer],
-        audio_outputs: &mut [clap_audio_buffer],
         num_samples: u32,
-        input_events: &InputEventList,
-        output_events: &mut OutputEventList,
         transport: Option<&TransportInfo>,
-    ) -> Result<ProcessOutput> {
+    ) -> Result<ProcessStatus> {
         // Record the audio thread ID so is_audio_thread checks work correctly.
         if let Ok(mut guard) = self.host_state.audio_thread_id.lock() {
             *guard = Some(std::thread::current().id());
         }
 
-        let clap_transport = transport.map(build_clap_transport);
+        let clap_transport = transport.map(|t| build_clap_transport(t, 0));
         let transport_ptr = clap_transport
             .as_ref()
             .map(|t| t as *const _)
             .unwrap_or(ptr::null());
 
-        let steady_time = transport
-            .map(|t| (t.song_pos_seconds * self.sample_rate) as i64)
-            .unwrap_or(0);
-
+        let buffers = &mut self.buffers;
         let process_data = clap_process {
-            steady_time,
+            steady_time: self.steady_time,
             frames_count: num_samples,
             transport: transport_ptr,
-            audio_inputs: audio_inputs.as_mut_ptr(),
-            audio_outputs: audio_outputs.as_mut_ptr(),
-            audio_inputs_count: audio_inputs.len() as u32,
-            audio_outputs_count: audio_outputs.len() as u32,
-            in_events: input_events.as_raw(),
-            out_events: output_events.as_raw_mut(),
+            audio_inputs: buffers.input_ports.as_mut_ptr(),
+            audio_outputs: buffers.output_ports.as_mut_ptr(),
+            audio_inputs_count: buffers.input_ports.len() as u32,
+            audio_outputs_count: buffers.output_ports.len() as u32,
+            in_events: buffers.input_events.as_raw(),
+            out_events: buffers.output_events.as_raw_mut(),
         };
 
         let plugin_ref = unsafe { &*self.plugin };
@@ -260,54 +1257,563 @@ impl ClapInstance {
             CLAP_PROCESS_CONTINUE
         };
 
+        self.steady_time += num_samples as i64;
+
         if status == CLAP_PROCESS_ERROR {
             return Err(ClapError::ProcessError("Plugin returned error".to_string()));
         }
-
-        Ok(ProcessOutput {
-            midi_events: output_events.to_midi_events(),
-            param_changes: output_events.to_param_changes(),
-            note_expressions: output_events.to_note_expressions(),
+        ProcessStatus::from_raw(status).ok_or_else(|| {
+            ClapError::ProcessError(format!("Plugin returned unknown status {}", status))
         })
     }
 }
 
-pub(super) fn build_clap_transport(transport: &TransportInfo) -> clap_event_transport {
-    let mut flags: u32 = CLAP_TRANSPORT_HAS_TEMPO
-        | CLAP_TRANSPORT_HAS_BEATS_TIMELINE
-        | CLAP_TRANSPORT_HAS_SECONDS_TIMELINE
-        | CLAP_TRANSPORT_HAS_TIME_SIGNATURE;
-
-    if transport.playing {
-        flags |= CLAP_TRANSPORT_IS_PLAYING;
-    }
-    if transport.recording {
-        flags |= CLAP_TRANSPORT_IS_RECORDING;
-    }
-    if transport.cycle_active {
-        flags |= CLAP_TRANSPORT_IS_LOOP_ACTIVE;
-    }
-
-    clap_event_transport {
-        header: clap_event_header {
-            size: std::mem::size_of::<clap_event_transport>() as u32,
-            time: 0,
-            space_id: CLAP_CORE_EVENT_SPACE_ID,
-            type_: CLAP_EVENT_TRANSPORT,
-            flags: 0,
-        },
-        flags,
-        song_pos_beats: (transport.song_pos_beats * CLAP_BEATTIME_FACTOR as f64) as i64,
-        song_pos_seconds: (transport.song_pos_seconds * CLAP_SECTIME_FACTOR as f64) as i64,
-        tempo: transport.tempo,
-        tempo_inc: 0.0,
-        loop_start_beats: (transport.loop_start_beats * CLAP_BEATTIME_FACTOR as f64) as i64,
-        loop_end_beats: (transport.loop_end_beats * CLAP_BEATTIME_FACTOR as f64) as i64,
-        loop_start_seconds: 0,
-        loop_end_seconds: 0,
-        bar_start: (transport.bar_start * CLAP_BEATTIME_FACTOR as f64) as i64,
-        bar_number: transport.bar_number,
-        tsig_num: transport.time_sig_numerator as u16,
-        tsig_denom: transport.time_sig_denominator as u16,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{MidiEvent, OutputPort, ParameterQueue};
+
+    #[test]
+    fn test_prepare_ports_pads_missing_channels() {
+        let mut buffers = ProcessBuffers::new(&[2, 1], &[2], 64, false);
+        let input = vec![1.0f32; 64];
+        let inputs: [&[f32]; 1] = [&input];
+        let mut left = vec![0.0f32; 64];
+        let mut right = vec![0.0f32; 64];
+        let mut outputs: [&mut [f32]; 2] = [&mut left, &mut right];
+        let mut audio = AudioBuffer::new(&inputs, &mut outputs, 64, 48000.0);
+
+        buffers.prepare_ports(&mut audio, 0, 64, &[2, 1], &[2]);
+
+        assert_eq!(buffers.input_ports.len(), 2);
+        assert_eq!(buffers.input_ports[0].channel_count, 2);
+        assert_eq!(buffers.input_ports[1].channel_count, 1);
+        assert!(buffers.input_ports[0].data64.is_null());
+        unsafe {
+            let port0 = buffers.input_ports[0].data32;
+            assert_eq!(*port0, input.as_ptr() as *mut f32);
+            // Missing channels point at zeroed scratch
+            let padded = std::slice::from_raw_parts(*port0.add(1), 64);
+            assert!(padded.iter().all(|&s| s == 0.0));
+            let port1 = buffers.input_ports[1].data32;
+            assert_eq!(*port1, buffers.f32_buffers.input_scratch[1].as_mut_ptr());
+        }
+        assert_eq!(buffers.output_ports.len(), 1);
+    }
+
+    #[test]
+    fn test_prepare_reuses_storage() {
+        let mut buffers = ProcessBuffers::new(&[2], &[2], 128, true);
+        let params = ParameterChanges::new();
+        let midi = [
+            MidiEvent::note_on(10, 0, 60, 100),
+            MidiEvent::note_off(5, 0, 62, 0),
+        ];
+
+        let mut left = vec![0.0f64; 128];
+        let mut right = vec![0.0f64; 128];
+        let mut outputs: [&mut [f64]; 2] = [&mut left, &mut right];
+        let mut audio = AudioBuffer::new(&[], &mut outputs, 128, 48000.0);
+
+        buffers.prepare_events(&ProcessContext {
+            midi: &midi,
+            params: Some(&params),
+            ..Default::default()
+        });
+        buffers.prepare_ports(&mut audio, 0, 128, &[2], &[2]);
+        let event_storage = buffers.input_events.events().as_ptr();
+        let ptr_storage = buffers.f64_buffers.input_ptrs.as_ptr();
+        let scratch = buffers.f64_buffers.input_scratch[0].as_ptr();
+
+        for _ in 0..4 {
+            buffers.prepare_events(&ProcessContext {
+                midi: &midi,
+                params: Some(&params),
+                ..Default::default()
+            });
+            buffers.prepare_ports(&mut audio, 0, 128, &[2], &[2]);
+        }
+
+        assert_eq!(buffers.input_events.events().as_ptr(), event_storage);
+        assert_eq!(buffers.input_events.events()[0].header().time, 5);
+        assert_eq!(buffers.f64_buffers.input_ptrs.as_ptr(), ptr_storage);
+        assert_eq!(buffers.f64_buffers.input_scratch[0].as_ptr(), scratch);
+        assert!(buffers.input_ports[0].data32.is_null());
+        assert!(!buffers.input_ports[0].data64.is_null());
+    }
+
+    #[test]
+    fn test_prepare_ports_grows_scratch_for_oversized_block() {
+        let mut buffers = ProcessBuffers::new(&[1], &[1], 16, false);
+        let mut audio = AudioBuffer::<f32>::new(&[], &mut [], 32, 48000.0);
+        buffers.prepare_ports(&mut audio, 0, 32, &[1], &[1]);
+        assert!(buffers.f32_buffers.input_scratch[0].len() >= 32);
+        assert!(buffers.f32_buffers.output_scratch[0].len() >= 32);
+    }
+
+    #[test]
+    fn test_constant_masks_follow_port_layout() {
+        let mut buffers = ProcessBuffers::new(&[2, 2], &[1, 2], 16, false);
+        let a = vec![0.0f32; 16];
+        let b = vec![0.5f32; 16];
+        let c = vec![0.0f32; 16];
+        let inputs: [&[f32]; 3] = [&a, &b, &c];
+        let mut out = [vec![0.0f32; 16], vec![0.0f32; 16]];
+        let [out0, out1] = &mut out;
+        let mut outputs: [&mut [f32]; 2] = [out0, out1];
+        let mut audio = AudioBuffer::new(&inputs, &mut outputs, 16, 48000.0);
+        audio.input_constant_mask = 0b010;
+
+        buffers.prepare_ports(&mut audio, 0, 16, &[2, 2], &[1, 2]);
+
+        // Channel 1 flagged by the caller, channel 3 padded with silence
+        assert_eq!(buffers.input_ports[0].constant_mask, 0b10);
+        assert_eq!(buffers.input_ports[1].constant_mask, 0b10);
+        assert_eq!(buffers.output_ports[0].constant_mask, 0);
+        assert_eq!(buffers.output_ports[1].constant_mask, 0);
+
+        // Plugin flags output port 1 channel 0 and the padded channel
+        buffers.output_ports[1].constant_mask = 0b11;
+        assert_eq!(buffers.output_constant_mask(2), 0b10);
+    }
+
+    #[test]
+    fn test_channel_bits() {
+        assert_eq!(channel_bits(0, 0), 0);
+        assert_eq!(channel_bits(0, 3), 0b111);
+        assert_eq!(channel_bits(2, 2), 0b1100);
+        assert_eq!(channel_bits(0, 64), u64::MAX);
+        assert_eq!(channel_bits(63, 4), 1 << 63);
+        assert_eq!(channel_bits(64, 1), 0);
+    }
+
+    #[test]
+    fn test_prepare_ports_sets_input_latencies() {
+        let mut buffers = ProcessBuffers::new(&[1, 1, 1], &[1, 1], 16, false);
+        let latencies = [64, 128];
+        let mut audio =
+            AudioBuffer::<f32>::new(&[], &mut [], 16, 48000.0).with_input_latencies(&latencies);
+
+        buffers.prepare_ports(&mut audio, 0, 16, &[1, 1, 1], &[1, 1]);
+
+        let input: Vec<u32> = buffers.input_ports.iter().map(|p| p.latency).collect();
+        assert_eq!(input, [64, 128, 0]);
+        assert!(buffers.output_ports.iter().all(|p| p.latency == 0));
+    }
+
+    #[test]
+    fn test_validate_ports() {
+        let main = vec![0.0f32; 32];
+        let short = vec![0.0f32; 16];
+        let stereo: [&[f32]; 2] = [&main, &main];
+        let mono: [&[f32]; 1] = [&main];
+        let short_mono: [&[f32]; 1] = [&short];
+
+        let inputs = [InputPort::new(&stereo[..]), InputPort::new(&mono[..])];
+        let buffer = PortBuffer::new(&inputs, &mut [], 32, 48000.0);
+        assert!(validate_ports(&buffer, &[2, 1], &[], &[]).is_ok());
+
+        let err = validate_ports(&buffer, &[2], &[], &[])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("declares 1 input ports, got 2"), "{}", err);
+
+        let err = validate_ports(&buffer, &[2, 2], &[], &[])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("input port 1: got 1, plugin declares 2"),
+            "{}",
+            err
+        );
+
+        let inputs = [InputPort::new(&short_mono[..])];
+        let buffer = PortBuffer::new(&inputs, &mut [], 32, 48000.0);
+        let err = validate_ports(&buffer, &[1], &[], &[])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("shorter than 32 samples"), "{}", err);
+
+        let buffer = PortBuffer::<f32>::new(&[], &mut [], 32, 48000.0);
+        let err = validate_ports(&buffer, &[], &[2], &[])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("output ports"), "{}", err);
+    }
+
+    #[test]
+    fn test_prepare_port_buffer_maps_ports_one_to_one() {
+        let mut buffers = ProcessBuffers::new(&[2, 1], &[2, 2], 16, false);
+        let main = [vec![0.0f32; 16], vec![0.0f32; 16]];
+        let key = vec![0.0f32; 16];
+        let main_channels: [&[f32]; 2] = [&main[0], &main[1]];
+        let key_channels: [&[f32]; 1] = [&key];
+        let inputs = [
+            InputPort::new(&main_channels[..]).with_latency(32),
+            InputPort::new(&key_channels[..]).with_constant_mask(0b11),
+        ];
+        let mut out = [
+            vec![0.0f32; 16],
+            vec![0.0f32; 16],
+            vec![0.0f32; 16],
+            vec![0.0f32; 16],
+        ];
+        let [a, b, c, d] = &mut out;
+        let mut main_out: [&mut [f32]; 2] = [a, b];
+        let mut aux_out: [&mut [f32]; 2] = [c, d];
+        let aux_ptr = aux_out[0].as_mut_ptr();
+        let mut outputs = [
+            OutputPort::new(&mut main_out),
+            OutputPort::new(&mut aux_out),
+        ];
+        let mut buffer = PortBuffer::new(&inputs, &mut outputs, 16, 48000.0);
+
+        buffers.prepare_port_buffer(&mut buffer, 0, &[]);
+
+        assert_eq!(buffers.input_ports.len(), 2);
+        assert_eq!(buffers.input_ports[0].latency, 32);
+        assert_eq!(buffers.input_ports[1].channel_count, 1);
+        // Bits beyond the port's channel count are dropped
+        assert_eq!(buffers.input_ports[1].constant_mask, 0b1);
+        unsafe {
+            assert_eq!(*buffers.input_ports[1].data32, key.as_ptr() as *mut f32);
+            assert_eq!(*buffers.output_ports[1].data32, aux_ptr);
+        }
+
+        buffers.output_ports[1].constant_mask = 0b10;
+        buffers.output_ports[1].latency = 64;
+        buffers.read_back_ports(&mut buffer, 0);
+        assert_eq!(buffer.outputs[0].constant_mask, 0);
+        assert_eq!(buffer.outputs[1].constant_mask, 0b10);
+        assert_eq!(buffer.outputs[1].latency, 64);
+    }
+
+    #[test]
+    fn test_choose_precision() {
+        let none = AudioPortFlags::empty();
+        let supports = AudioPortFlags::SUPPORTS_64BIT;
+        let prefers = AudioPortFlags::SUPPORTS_64BIT | AudioPortFlags::PREFERS_64BIT;
+        let (mut inputs, mut outputs) = (Vec::new(), Vec::new());
+
+        choose_precision(
+            &[none, supports],
+            &[prefers],
+            false,
+            &mut inputs,
+            &mut outputs,
+        );
+        assert_eq!(inputs, [false, false]);
+        assert_eq!(outputs, [true]);
+
+        choose_precision(
+            &[none, supports],
+            &[prefers],
+            true,
+            &mut inputs,
+            &mut outputs,
+        );
+        assert_eq!(inputs, [false, true]);
+        assert_eq!(outputs, [true]);
+
+        // Common sample size: widened only if every flagged port can be
+        let common = AudioPortFlags::REQUIRES_COMMON_SAMPLE_SIZE;
+        choose_precision(
+            &[supports | common],
+            &[prefers | common],
+            false,
+            &mut inputs,
+            &mut outputs,
+        );
+        assert_eq!(inputs, [true]);
+        assert_eq!(outputs, [true]);
+        choose_precision(
+            &[common],
+            &[prefers | common],
+            true,
+            &mut inputs,
+            &mut outputs,
+        );
+        assert_eq!(inputs, [false]);
+        assert_eq!(outputs, [false]);
+    }
+
+    #[test]
+    fn test_mixed_ports_convert_both_ways() {
+        let mut buffers = ProcessBuffers::new(&[1, 1], &[1, 1], 8, true);
+        buffers.input_f64 = vec![false, true];
+        buffers.output_f64 = vec![false, true];
+
+        let main = vec![0.25f64; 8];
+        let key = vec![0.5f64; 8];
+        let main_channels: [&[f64]; 1] = [&main];
+        let key_channels: [&[f64]; 1] = [&key];
+        let inputs = [
+            InputPort::new(&main_channels[..]),
+            InputPort::new(&key_channels[..]),
+        ];
+        let mut out = [vec![0.0f64; 8], vec![0.0f64; 8]];
+        let [a, b] = &mut out;
+        let wide_ptr = b.as_mut_ptr();
+        let mut narrow_out: [&mut [f64]; 1] = [a];
+        let mut wide_out: [&mut [f64]; 1] = [b];
+        let mut outputs = [
+            OutputPort::new(&mut narrow_out),
+            OutputPort::new(&mut wide_out),
+        ];
+        let mut buffer = PortBuffer::new(&inputs, &mut outputs, 8, 48000.0);
+
+        buffers.prepare_mixed_ports(&mut buffer, 0, 8, &[]);
+
+        // f32 port reads converted scratch, f64 port reads the host channel
+        assert!(buffers.input_ports[0].data64.is_null());
+        assert!(buffers.input_ports[1].data32.is_null());
+        unsafe {
+            let narrow = std::slice::from_raw_parts(*buffers.input_ports[0].data32, 8);
+            assert!(narrow.iter().all(|&s| s == 0.25f32));
+            assert_eq!(*buffers.input_ports[1].data64, key.as_ptr() as *mut f64);
+            assert_eq!(*buffers.output_ports[1].data64, wide_ptr);
+
+            // Plugin renders into the f32 scratch of output port 0
+            let rendered = std::slice::from_raw_parts_mut(*buffers.output_ports[0].data32, 8);
+            rendered.fill(0.75);
+        }
+
+        buffers.read_back_mixed_ports(&mut buffer, 0, 8);
+        assert!(buffer.outputs[0].channels[0].iter().all(|&s| s == 0.75));
+    }
+
+    #[test]
+    fn test_in_place_input_shares_output_pointers() {
+        let mut buffers = ProcessBuffers::new(&[2, 1], &[2], 16, true);
+        let key = vec![0.0f32; 16];
+        let key_channels: [&[f32]; 1] = [&key];
+        let inputs = [InputPort::in_place(), InputPort::new(&key_channels[..])];
+        let mut out = [vec![0.5f32; 16], vec![0.5f32; 16]];
+        let [l, r] = &mut out;
+        let (l_ptr, r_ptr) = (l.as_mut_ptr(), r.as_mut_ptr());
+        let mut main: [&mut [f32]; 2] = [l, r];
+        let mut outputs = [OutputPort::new(&mut main)];
+        let mut buffer = PortBuffer::new(&inputs, &mut outputs, 16, 48000.0);
+        let pairs = [Some(0), None];
+
+        assert!(validate_ports(&buffer, &[2, 1], &[2], &pairs).is_ok());
+        buffers.prepare_port_buffer(&mut buffer, 0, &pairs);
+
+        assert_eq!(buffers.input_ports[0].channel_count, 2);
+        assert_eq!(buffers.input_ports[1].channel_count, 1);
+        unsafe {
+            let input = buffers.input_ports[0].data32;
+            let output = buffers.output_ports[0].data32;
+            assert_eq!((*input, *input.add(1)), (l_ptr, r_ptr));
+            assert_eq!((*output, *output.add(1)), (l_ptr, r_ptr));
+        }
+
+        // Converted in-place inputs read the output channels
+        buffers.input_f64 = vec![true, false];
+        buffers.output_f64 = vec![false];
+        buffers.prepare_mixed_ports(&mut buffer, 0, 16, &pairs);
+        unsafe {
+            let input = std::slice::from_raw_parts(*buffers.input_ports[0].data64, 16);
+            assert!(input.iter().all(|&s| s == 0.5));
+            assert_eq!(*buffers.output_ports[0].data32, l_ptr);
+        }
+    }
+
+    #[test]
+    fn test_validate_unpaired_in_place_input() {
+        let inputs = [InputPort::<f32>::in_place()];
+        let buffer = PortBuffer::new(&inputs, &mut [], 16, 48000.0);
+        let err = validate_ports(&buffer, &[2], &[], &[None])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("pairs no output port"), "{}", err);
+        // A port declared with no channels needs no pair
+        assert!(validate_ports(&buffer, &[0], &[], &[None]).is_ok());
+    }
+
+    #[test]
+    fn test_empty_input_port_is_not_in_place() {
+        let inputs = [InputPort::<f32>::new(&[])];
+        assert!(!inputs[0].is_in_place());
+        // Checked as a port with no channels, not read from its pair
+        let mut out_l = [0.0f32; 16];
+        let mut out_r = [0.0f32; 16];
+        let mut out_channels: [&mut [f32]; 2] = [&mut out_l, &mut out_r];
+        let mut outputs = [OutputPort::new(&mut out_channels)];
+        let buffer = PortBuffer::new(&inputs, &mut outputs, 16, 48000.0);
+        assert!(validate_ports(&buffer, &[2], &[2], &[Some(0)]).is_err());
+        assert_eq!(input_channel_count(&buffer, &[Some(0)], 0), 0);
+    }
+
+    #[test]
+    fn test_window_events_rebases_each_sub_block() {
+        let mut buffers = ProcessBuffers::new(&[], &[2], 64, false);
+        let midi = [
+            MidiEvent::note_on(10, 0, 60, 100),
+            MidiEvent::note_on(64, 0, 62, 100),
+            MidiEvent::note_off(130, 0, 60, 0),
+            // Past the end of the block
+            MidiEvent::note_off(200, 0, 62, 0),
+        ];
+        buffers.prepare_events(&ProcessContext {
+            midi: &midi,
+            ..Default::default()
+        });
+
+        let times = |buffers: &ProcessBuffers, range: std::ops::Range<usize>| -> Vec<u32> {
+            buffers.input_events.events()[range]
+                .iter()
+                .map(|e| e.header().time)
+                .collect()
+        };
+        assert_eq!(buffers.window_events(0, 64, 150, 0, false), (64, 1));
+        assert_eq!(times(&buffers, 0..1), [10]);
+        assert_eq!(buffers.window_events(64, 64, 150, 1, false), (64, 2));
+        assert_eq!(times(&buffers, 1..2), [0]);
+        assert_eq!(buffers.window_events(128, 22, 150, 2, false), (22, 4));
+        assert_eq!(times(&buffers, 2..4), [2, 72]);
+    }
+
+    #[test]
+    fn test_window_events_splits_at_param_changes() {
+        let mut buffers = ProcessBuffers::new(&[], &[2], 64, false);
+        let mut params = ParameterChanges::new();
+        let mut queue = ParameterQueue::new(7);
+        queue.add_point(0, 0.0).add_point(20, 0.5);
+        params.add_queue(queue);
+        let midi = [MidiEvent::note_on(5, 0, 60, 100)];
+        buffers.prepare_events(&ProcessContext {
+            midi: &midi,
+            params: Some(&params),
+            ..Default::default()
+        });
+
+        // Stops short of the change at 20, which then starts a sub-block
+        assert_eq!(buffers.window_events(0, 64, 64, 0, true), (20, 2));
+        assert_eq!(buffers.window_events(20, 44, 64, 2, true), (44, 3));
+        assert_eq!(buffers.input_events.events()[2].header().time, 0);
+        // Without splitting, the whole block takes every event
+        buffers.prepare_events(&ProcessContext {
+            midi: &midi,
+            params: Some(&params),
+            ..Default::default()
+        });
+        assert_eq!(buffers.window_events(0, 64, 64, 0, false), (64, 3));
+    }
+
+    #[test]
+    fn test_prepare_ports_at_offset() {
+        let mut buffers = ProcessBuffers::new(&[1], &[1], 16, false);
+        let input: Vec<f32> = (0..32).map(|i| i as f32).collect();
+        let inputs: [&[f32]; 1] = [&input];
+        let mut output = vec![0.0f32; 32];
+        let mut outputs: [&mut [f32]; 1] = [&mut output];
+        let mut audio = AudioBuffer::new(&inputs, &mut outputs, 32, 48000.0);
+
+        buffers.prepare_ports(&mut audio, 16, 16, &[1], &[1]);
+        unsafe {
+            assert_eq!(**buffers.input_ports[0].data32, 16.0);
+            assert_eq!(
+                *buffers.output_ports[0].data32,
+                audio.outputs[0][16..].as_mut_ptr()
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_constant_mask() {
+        let a = vec![1.0f32; 8];
+        let b: Vec<f32> = [1.0, 1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 2.0].to_vec();
+        let channels: [&[f32]; 2] = [&a, &b];
+        assert_eq!(merge_constant_mask(0, 0b11, &channels, 0), 0b11);
+        // Channel 1 is constant in both halves but with different values
+        assert_eq!(merge_constant_mask(0b11, 0b11, &channels, 4), 0b01);
+        assert_eq!(merge_constant_mask(0b01, 0b00, &channels, 4), 0b00);
+    }
+
+    #[test]
+    fn test_sub_block_transport() {
+        let transport = TransportInfo::new()
+            .with_tempo(120.0)
+            .with_playing(true)
+            .with_position(3.0, 1.5)
+            .with_bar(0.0, 0);
+        let ctx = ProcessContext {
+            transport: Some(&transport),
+            ..Default::default()
+        };
+        assert_eq!(
+            sub_block_transport(&ctx, 0, 48000.0)
+                .unwrap()
+                .song_pos_beats,
+            3.0
+        );
+        // Half a second at 120 BPM is one beat, into the second bar
+        let advanced = sub_block_transport(&ctx, 24000, 48000.0).unwrap();
+        assert!((advanced.song_pos_beats - 4.0).abs() < 1e-9);
+        assert!((advanced.song_pos_seconds - 2.0).abs() < 1e-9);
+        assert_eq!(advanced.bar_number, 1);
+        assert_eq!(advanced.bar_start, 4.0);
+
+        let jump = TransportInfo::new()
+            .with_tempo(120.0)
+            .with_playing(true)
+            .with_position(16.0, 8.0);
+        let changes = [TransportChange {
+            sample_offset: 100,
+            transport: jump,
+        }];
+        let ctx = ProcessContext {
+            transport: Some(&transport),
+            transport_changes: &changes,
+            ..Default::default()
+        };
+        assert_eq!(
+            sub_block_transport(&ctx, 64, 48000.0)
+                .unwrap()
+                .song_pos_beats,
+            3.0 + 64.0 / 24000.0
+        );
+        // 28 samples past the jump
+        let after = sub_block_transport(&ctx, 128, 48000.0).unwrap();
+        assert!((after.song_pos_beats - (16.0 + 28.0 / 24000.0)).abs() < 1e-12);
+        assert!((after.song_pos_seconds - (8.0 + 28.0 / 48000.0)).abs() < 1e-12);
+        assert_eq!(
+            sub_block_transport(&ctx, 100, 48000.0)
+                .unwrap()
+                .song_pos_beats,
+            16.0
+        );
+
+        // The latest change by offset wins, whatever the slice order
+        let changes = [
+            TransportChange {
+                sample_offset: 100,
+                transport: jump,
+            },
+            TransportChange {
+                sample_offset: 50,
+                transport: jump.with_position(32.0, 16.0),
+            },
+        ];
+        let ctx = ProcessContext {
+            transport: Some(&transport),
+            transport_changes: &changes,
+            ..Default::default()
+        };
+        let after = sub_block_transport(&ctx, 128, 48000.0).unwrap();
+        assert!((after.song_pos_beats - (16.0 + 28.0 / 24000.0)).abs() < 1e-12);
+
+        let stopped = TransportInfo::new().with_position(3.0, 1.5);
+        let ctx = ProcessContext {
+            transport: Some(&stopped),
+            ..Default::default()
+        };
+        assert_eq!(
+            sub_block_transport(&ctx, 24000, 48000.0)
+                .unwrap()
+                .song_pos_beats,
+            3.0
+        );
     }
 }
@@ -0,0 +1,285 @@
+//! CLAP entry lifecycle and plugin factory access.
+
+use crate::cstr_to_string;
+use crate::error::{ClapError, LoadStage, Result};
+use crate::types::PluginInfo;
+use clap_sys::entry::clap_plugin_entry;
+use clap_sys::factory::draft::plugin_invalidation::{
+    clap_plugin_invalidation_factory, CLAP_PLUGIN_INVALIDATION_FACTORY_ID,
+};
+use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::plugin::clap_plugin_descriptor;
+use std::collections::HashMap;
+use std::ffi::{c_void, CStr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Global registry for CLAP entry init/deinit lifecycle.
+///
+/// The CLAP spec requires `clap_entry.init()` to be called once when a library
+/// is first loaded and `clap_entry.deinit()` once when it's finally unloaded.
+/// Many plugins do not tolerate repeated init/deinit cycles within the same
+/// process — their global state becomes corrupted. This registry ensures
+/// `init()` is called once per library path while it stays loaded.
+/// `deinit()` is only called by [`release`], when nothing but the registry
+/// holds the library, e.g. after a scan loaded a bundle just to read its
+/// descriptors.
+///
+/// The registry also owns the library handle, so an initialized entry is
+/// never unloaded and later reopened without a matching `deinit()`.
+static ENTRY_REGISTRY: Mutex<Option<HashMap<PathBuf, Arc<libloading::Library>>>> = Mutex::new(None);
+
+/// An opened CLAP library whose entry has been initialized.
+///
+/// Does not call deinit on drop — the entry stays initialized until the
+/// registry [releases](release) it.
+pub(crate) struct PluginEntry {
+    path: PathBuf,
+    entry: *const clap_plugin_entry,
+    _library: Arc<libloading::Library>,
+}
+
+// Safety: the entry is a static struct exported by the library, which
+// `_library` keeps loaded.
+unsafe impl Send for PluginEntry {}
+
+impl PluginEntry {
+    /// Open the library at `path` and initialize its entry.
+    /// `init()` runs only on the first open of a given library; subsequent
+    /// opens reuse the registered handle.
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        let mut registry = ENTRY_REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+        let map = registry.get_or_insert_with(HashMap::new);
+
+        if let Some(library) = map.get(path) {
+            let entry = Self::entry_symbol(path, library)?;
+            return Ok(Self {
+                path: path.to_path_buf(),
+                entry,
+                _library: library.clone(),
+            });
+        }
+
+        // On macOS, .clap plugins are bundles (directories). Resolve to the
+        // actual binary at Contents/MacOS/<stem> for dlopen, but keep the
+        // original bundle path for clap_plugin_entry.init() per CLAP spec.
+        let resolved = resolve_bundle_path(path);
+        let load_path = resolved.as_deref().unwrap_or(path);
+
+        let library = unsafe {
+            libloading::Library::new(load_path).map_err(|e| ClapError::LoadFailed {
+                path: path.to_path_buf(),
+                stage: LoadStage::Opening,
+                reason: format!("Failed to load library: {}", e),
+            })?
+        };
+        let entry = Self::entry_symbol(path, &library)?;
+
+        let init_fn = unsafe { (*entry).init }.ok_or_else(|| ClapError::LoadFailed {
+            path: path.to_path_buf(),
+            stage: LoadStage::Opening,
+            reason: "No init function".to_string(),
+        })?;
+
+        // Pass the original bundle path to init(), not the resolved binary path
+        let path_cstr = std::ffi::CString::new(path.to_string_lossy().as_ref()).map_err(|e| {
+            ClapError::LoadFailed {
+                path: path.to_path_buf(),
+                stage: LoadStage::Opening,
+                reason: format!("Invalid path: {}", e),
+            }
+        })?;
+
+        if !unsafe { init_fn(path_cstr.as_ptr()) } {
+            return Err(ClapError::LoadFailed {
+                path: path.to_path_buf(),
+                stage: LoadStage::Opening,
+                reason: "Entry init failed".to_string(),
+            });
+        }
+
+        let library = Arc::new(library);
+        map.insert(path.to_path_buf(), library.clone());
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            entry,
+            _library: library,
+        })
+    }
+
+    /// clap_entry is a static exported struct (not a function pointer).
+    /// get::<*const T> yields a Symbol whose Deref gives *const T; the
+    /// pointer stays valid as long as the library is loaded.
+    fn entry_symbol(
+        path: &Path,
+        library: &libloading::Library,
+    ) -> Result<*const clap_plugin_entry> {
+        let sym =
+            unsafe { library.get::<*const clap_plugin_entry>(b"clap_entry\0") }.map_err(|e| {
+                ClapError::LoadFailed {
+                    path: path.to_path_buf(),
+                    stage: LoadStage::Opening,
+                    reason: format!("No clap_entry symbol: {}", e),
+                }
+            })?;
+        let entry = *sym;
+        if entry.is_null() {
+            return Err(ClapError::LoadFailed {
+                path: path.to_path_buf(),
+                stage: LoadStage::Opening,
+                reason: "Null clap_entry symbol".to_string(),
+            });
+        }
+        Ok(entry)
+    }
+
+    pub(crate) fn load_failed(&self, stage: LoadStage, reason: impl Into<String>) -> ClapError {
+        ClapError::LoadFailed {
+            path: self.path.clone(),
+            stage,
+            reason: reason.into(),
+        }
+    }
+
+    /// Query a factory by ID. Returns null if the entry doesn't provide it.
+    pub(crate) fn get_factory(&self, factory_id: &CStr) -> *const c_void {
+        match unsafe { (*self.entry).get_factory } {
+            Some(f) => unsafe { f(factory_id.as_ptr()) },
+            None => std::ptr::null(),
+        }
+    }
+
+    pub(crate) fn plugin_factory(&self) -> Result<&clap_plugin_factory> {
+        if unsafe { (*self.entry).get_factory }.is_none() {
+            return Err(self.load_failed(LoadStage::Factory, "No get_factory function"));
+        }
+        let factory_ptr = self.get_factory(CLAP_PLUGIN_FACTORY_ID);
+        if factory_ptr.is_null() {
+            return Err(self.load_failed(LoadStage::Factory, "No plugin factory"));
+        }
+        Ok(unsafe { &*(factory_ptr as *const clap_plugin_factory) })
+    }
+
+    pub(crate) fn plugin_count(&self, factory: &clap_plugin_factory) -> Result<u32> {
+        let get_count_fn = factory
+            .get_plugin_count
+            .ok_or_else(|| self.load_failed(LoadStage::Factory, "No get_plugin_count function"))?;
+        Ok(unsafe { get_count_fn(factory) })
+    }
+
+    pub(crate) fn descriptor<'a>(
+        &self,
+        factory: &'a clap_plugin_factory,
+        index: u32,
+    ) -> Result<&'a clap_plugin_descriptor> {
+        let get_desc_fn = factory.get_plugin_descriptor.ok_or_else(|| {
+            self.load_failed(LoadStage::Factory, "No get_plugin_descriptor function")
+        })?;
+        let desc_ptr = unsafe { get_desc_fn(factory, index) };
+        if desc_ptr.is_null() || unsafe { (*desc_ptr).id }.is_null() {
+            return Err(self.load_failed(LoadStage::Factory, "No plugin descriptor"));
+        }
+        Ok(unsafe { &*desc_ptr })
+    }
+
+    pub(crate) fn invalidation_factory(&self) -> Option<&clap_plugin_invalidation_factory> {
+        let factory_ptr = self.get_factory(CLAP_PLUGIN_INVALIDATION_FACTORY_ID);
+        if factory_ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { &*(factory_ptr as *const clap_plugin_invalidation_factory) })
+        }
+    }
+
+    /// Walk every descriptor in the plugin factory without instantiating.
+    pub(crate) fn descriptors(&self) -> Result<Vec<PluginInfo>> {
+        let factory = self.plugin_factory()?;
+        let count = self.plugin_count(factory)?;
+        (0..count)
+            .map(|i| {
+                self.descriptor(factory, i)
+                    .map(|desc| unsafe { descriptor_info(desc) })
+            })
+            .collect()
+    }
+}
+
+/// Deinitialize and unload the library at `path` if nothing but the
+/// registry holds it. Returns `false` if an instance or other entry still
+/// uses the library, which is then left alone.
+pub(crate) fn release(path: &Path) -> bool {
+    let mut registry = ENTRY_REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(map) = registry.as_mut() else {
+        return true;
+    };
+    let Some(library) = map.get(path) else {
+        return true;
+    };
+    if Arc::strong_count(library) > 1 {
+        return false;
+    }
+    if let Ok(entry) = PluginEntry::entry_symbol(path, library) {
+        if let Some(deinit) = unsafe { (*entry).deinit } {
+            unsafe { deinit() };
+        }
+    }
+    map.remove(path);
+    true
+}
+
+/// Whether the library at `path` is currently held by the registry.
+pub(crate) fn is_loaded(path: &Path) -> bool {
+    let registry = ENTRY_REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    registry.as_ref().is_some_and(|map| map.contains_key(path))
+}
+
+/// Parse a plugin descriptor into a [`PluginInfo`]. Audio I/O counts are
+/// left at their defaults — they are only known after instantiation.
+///
+/// # Safety
+/// `desc` must point to a valid descriptor whose strings are null or
+/// nul-terminated.
+pub(crate) unsafe fn descriptor_info(desc: &clap_plugin_descriptor) -> PluginInfo {
+    let features = if desc.features.is_null() {
+        Vec::new()
+    } else {
+        let mut features = Vec::new();
+        let mut ptr = desc.features;
+        const MAX_FEATURES: usize = 256;
+        while !(*ptr).is_null() && features.len() < MAX_FEATURES {
+            features.push(CStr::from_ptr(*ptr).to_string_lossy().into_owned());
+            ptr = ptr.add(1);
+        }
+        features
+    };
+
+    PluginInfo::new(cstr_to_string(desc.id), cstr_to_string(desc.name))
+        .vendor(cstr_to_string(desc.vendor))
+        .version(cstr_to_string(desc.version))
+        .url(cstr_to_string(desc.url))
+        .description(cstr_to_string(desc.description))
+        .features(features)
+}
+
+/// On macOS, `.clap` plugins are bundles (directories). Resolve to the actual
+/// binary at `<bundle>/Contents/MacOS/<stem>`. Returns `None` if the path is
+/// already a file or we're not on macOS.
+pub(crate) fn resolve_bundle_path(path: &Path) -> Option<PathBuf> {
+    #[cfg(target_os = "macos")]
+    {
+        if path.is_dir() {
+            let stem = path.file_stem()?;
+            let binary = path.join("Contents").join("MacOS").join(stem);
+            if binary.is_file() {
+                return Some(binary);
+            }
+        }
+        None
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        let _ = path;
+        None
+    }
+}
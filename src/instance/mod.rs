@@ -1,77 +1,39 @@
 //! CLAP plugin instance.
 
 mod audio;
+pub(crate) mod entry;
 mod extensions;
 mod params;
 mod polling;
 mod ports;
+mod sleep;
 mod state;
+mod voices;
 
 pub use audio::{ClapSample, ProcessContext, ProcessOutput};
+
+use audio::{flat_port_channels, ProcessBuffers};
 pub use params::ParamMapping;
+pub use sleep::SleepTracker;
+pub use voices::{Voice, VoiceTracker};
 
-use crate::cstr_to_string;
 use crate::error::{ClapError, LoadStage, Result};
 use crate::host::{ClapHost, HostState};
-use crate::types::PluginInfo;
-use clap_sys::entry::clap_plugin_entry;
-use clap_sys::ext::audio_ports::{
-    clap_audio_port_info, clap_plugin_audio_ports, CLAP_AUDIO_PORT_SUPPORTS_64BITS,
-};
+use crate::types::{AudioPortFlags, PluginInfo};
+use clap_sys::ext::audio_ports::{clap_audio_port_info, clap_plugin_audio_ports};
+use clap_sys::id::CLAP_INVALID_ID;
 use clap_sys::plugin::clap_plugin;
+use entry::{descriptor_info, PluginEntry};
 use extensions::ExtensionCache;
-use std::collections::HashMap;
-use std::ffi::CStr;
-use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
-
-/// Global registry for CLAP entry init/deinit lifecycle.
-///
-/// The CLAP spec requires `clap_entry.init()` to be called once when a library
-/// is first loaded and `clap_entry.deinit()` once when it's finally unloaded.
-/// Many plugins do not tolerate repeated init/deinit cycles within the same
-/// process — their global state becomes corrupted. This registry ensures
-/// `init()` is called exactly once per library path for the lifetime of the
-/// process. `deinit()` is intentionally NOT called, matching real-world DAW
-/// behavior where plugins run in subprocesses that exit cleanly.
-static ENTRY_REGISTRY: Mutex<Option<HashMap<PathBuf, bool>>> = Mutex::new(None);
-
-/// RAII guard for CLAP entry lifetime. Does not call deinit on drop — the
-/// entry stays initialized for the lifetime of the process.
-struct EntryGuard {
-    _path: PathBuf,
-}
-
-/// Register a CLAP entry for the given path.
-/// Calls `init_fn` only on the first load of a given library. Subsequent
-/// loads of the same library skip init (the entry is already initialized).
-fn entry_registry_acquire(
-    path: &Path,
-    init_fn: unsafe extern "C" fn(*const i8) -> bool,
-    path_cstr: &std::ffi::CString,
-) -> std::result::Result<EntryGuard, String> {
-    let mut registry = ENTRY_REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
-    let map = registry.get_or_insert_with(HashMap::new);
-
-    if !map.contains_key(path) {
-        if !unsafe { init_fn(path_cstr.as_ptr()) } {
-            return Err("Entry init failed".to_string());
-        }
-        map.insert(path.to_path_buf(), true);
-    }
-
-    Ok(EntryGuard {
-        _path: path.to_path_buf(),
-    })
-}
+use std::path::Path;
+use std::sync::Arc;
 
 pub struct ClapInstance {
     plugin: *const clap_plugin,
     // IMPORTANT: Drop order matters! Fields are dropped top-to-bottom.
-    // _entry_guard must be dropped BEFORE _library so that deinit() is
-    // called while the library is still loaded in memory.
-    _entry_guard: EntryGuard,
-    _library: libloading::Library,
+    // The plugin is destroyed in Drop::drop, before _entry releases its
+    // handle on the library.
+    _entry: PluginEntry,
     _host: Box<ClapHost>,
     host_state: Arc<HostState>,
     extensions: ExtensionCache,
@@ -81,250 +43,164 @@ pub struct ClapInstance {
     max_frames: u32,
     is_active: bool,
     is_processing: bool,
-    /// Per-port channel counts for input ports (e.g. [2] for stereo, [2, 2] for two stereo ports).
+    /// Sample counter passed as `clap_process.steady_time`. Advances by the
+    /// block length after every process call and restarts at activation.
+    steady_time: i64,
+    /// Whether process calls start a new sub-block at every parameter
+    /// change.
+    split_at_param_changes: bool,
+    /// Per-port channel counts for input ports as declared by the plugin
+    /// (e.g. [2] for stereo, [2, 2] for two stereo ports). Empty if it
+    /// declares none.
     input_port_channels: Vec<u32>,
     /// Per-port channel counts for output ports.
     output_port_channels: Vec<u32>,
+    /// Per-port flags, parallel to `input_port_channels`.
+    input_port_flags: Vec<AudioPortFlags>,
+    /// Per-port flags, parallel to `output_port_channels`.
+    output_port_flags: Vec<AudioPortFlags>,
+    /// For each input port, the output port it is paired with for in-place
+    /// processing.
+    in_place_pairs: Vec<Option<usize>>,
+    /// Reused by every process call; sized in `activate()`.
+    buffers: ProcessBuffers,
 }
 
 // Safety: CLAP plugins are designed to be called from a single thread
 unsafe impl Send for ClapInstance {}
 
 impl ClapInstance {
+    /// Load the first plugin in the bundle's factory.
     pub fn load(path: impl AsRef<Path>, sample_rate: f64, max_frames: u32) -> Result<Self> {
-        let bundle_path = path.as_ref();
-        // On macOS, .clap plugins are bundles (directories). Resolve to the
-        // actual binary at Contents/MacOS/<stem> for dlopen, but keep the
-        // original bundle path for clap_plugin_entry.init() per CLAP spec.
-        let resolved = resolve_bundle_path(bundle_path);
-        let load_path = resolved.as_deref().unwrap_or(bundle_path);
-
-        let library = unsafe {
-            libloading::Library::new(load_path).map_err(|e| ClapError::LoadFailed {
-                path: bundle_path.to_path_buf(),
-                stage: LoadStage::Opening,
-                reason: format!("Failed to load library: {}", e),
-            })?
-        };
-
-        // clap_entry is a static exported struct (not a function pointer).
-        // get::<*const T> yields a Symbol whose Deref gives *const T.
-        // We copy the pointer value out so the Symbol borrow can end,
-        // then convert to a reference that lives as long as _library.
-        let entry_struct: &clap_plugin_entry = unsafe {
-            let sym = library
-                .get::<*const clap_plugin_entry>(b"clap_entry\0")
-                .map_err(|e| ClapError::LoadFailed {
-                    path: bundle_path.to_path_buf(),
-                    stage: LoadStage::Opening,
-                    reason: format!("No clap_entry symbol: {}", e),
-                })?;
-            &*(*sym)
-        };
+        Self::load_with_index(path, 0, sample_rate, max_frames)
+    }
 
-        let init_fn = entry_struct.init.ok_or_else(|| ClapError::LoadFailed {
-            path: bundle_path.to_path_buf(),
-            stage: LoadStage::Opening,
-            reason: "No init function".to_string(),
-        })?;
+    /// Load the plugin at descriptor `index` in the bundle's factory.
+    pub fn load_with_index(
+        path: impl AsRef<Path>,
+        index: u32,
+        sample_rate: f64,
+        max_frames: u32,
+    ) -> Result<Self> {
+        let entry = PluginEntry::open(path.as_ref())?;
+        let factory = entry.plugin_factory()?;
+        let plugin_count = entry.plugin_count(factory)?;
+        if plugin_count == 0 {
+            return Err(entry.load_failed(LoadStage::Factory, "No plugins in factory"));
+        }
+        if index >= plugin_count {
+            return Err(entry.load_failed(
+                LoadStage::Factory,
+                format!(
+                    "Plugin index {} out of range ({} plugins in factory)",
+                    index, plugin_count
+                ),
+            ));
+        }
+        let descriptor = entry.descriptor(factory, index)?;
+        let info = unsafe { descriptor_info(descriptor) };
+        Self::instantiate(entry, info, sample_rate, max_frames)
+    }
 
-        // Pass the original bundle path to init(), not the resolved binary path
-        let path_cstr =
-            std::ffi::CString::new(bundle_path.to_string_lossy().as_ref()).map_err(|e| {
-                ClapError::LoadFailed {
-                    path: bundle_path.to_path_buf(),
-                    stage: LoadStage::Opening,
-                    reason: format!("Invalid path: {}", e),
-                }
+    /// Load the plugin whose descriptor ID matches `plugin_id`, for bundles
+    /// that ship several plugins.
+    pub fn load_with_id(
+        path: impl AsRef<Path>,
+        plugin_id: &str,
+        sample_rate: f64,
+        max_frames: u32,
+    ) -> Result<Self> {
+        let entry = PluginEntry::open(path.as_ref())?;
+        let info = entry
+            .descriptors()?
+            .into_iter()
+            .find(|info| info.id == plugin_id)
+            .ok_or_else(|| {
+                entry.load_failed(
+                    LoadStage::Factory,
+                    format!("No plugin with ID '{}' in factory", plugin_id),
+                )
             })?;
+        Self::instantiate(entry, info, sample_rate, max_frames)
+    }
 
-        // Use the entry registry to ensure init is called exactly once per
-        // library. deinit is intentionally skipped — many plugins don't
-        // tolerate repeated init/deinit cycles in the same process.
-        let entry_guard =
-            entry_registry_acquire(bundle_path, init_fn, &path_cstr).map_err(|reason| {
-                ClapError::LoadFailed {
-                    path: bundle_path.to_path_buf(),
-                    stage: LoadStage::Opening,
-                    reason,
-                }
-            })?;
+    /// List every plugin descriptor in a bundle without instantiating any
+    /// of them. Audio I/O counts are left at their defaults.
+    pub fn descriptors(path: impl AsRef<Path>) -> Result<Vec<PluginInfo>> {
+        PluginEntry::open(path.as_ref())?.descriptors()
+    }
 
+    fn instantiate(
+        entry: PluginEntry,
+        descriptor_info: PluginInfo,
+        sample_rate: f64,
+        max_frames: u32,
+    ) -> Result<Self> {
         let host_state = Arc::new(HostState::new());
         let host = Box::new(ClapHost::new(host_state.clone()));
 
-        let get_factory_fn = entry_struct
-            .get_factory
-            .ok_or_else(|| ClapError::LoadFailed {
-                path: bundle_path.to_path_buf(),
-                stage: LoadStage::Factory,
-                reason: "No get_factory function".to_string(),
-            })?;
-
-        let factory_ptr = unsafe {
-            get_factory_fn(clap_sys::factory::plugin_factory::CLAP_PLUGIN_FACTORY_ID.as_ptr())
-        };
+        let factory = entry.plugin_factory()?;
 
-        if factory_ptr.is_null() {
-            return Err(ClapError::LoadFailed {
-                path: bundle_path.to_path_buf(),
-                stage: LoadStage::Factory,
-                reason: "No plugin factory".to_string(),
-            });
-        }
-
-        let factory = unsafe {
-            &*(factory_ptr as *const clap_sys::factory::plugin_factory::clap_plugin_factory)
-        };
-
-        let get_count_fn = factory
-            .get_plugin_count
-            .ok_or_else(|| ClapError::LoadFailed {
-                path: bundle_path.to_path_buf(),
-                stage: LoadStage::Factory,
-                reason: "No get_plugin_count function".to_string(),
-            })?;
-
-        let plugin_count = unsafe { get_count_fn(factory_ptr as *const _) };
-        if plugin_count == 0 {
-            return Err(ClapError::LoadFailed {
-                path: bundle_path.to_path_buf(),
-                stage: LoadStage::Factory,
-                reason: "No plugins in factory".to_string(),
-            });
-        }
-
-        let get_desc_fn = factory
-            .get_plugin_descriptor
-            .ok_or_else(|| ClapError::LoadFailed {
-                path: bundle_path.to_path_buf(),
-                stage: LoadStage::Factory,
-                reason: "No get_plugin_descriptor function".to_string(),
-            })?;
-
-        let desc_ptr = unsafe { get_desc_fn(factory_ptr as *const _, 0) };
-        if desc_ptr.is_null() {
-            return Err(ClapError::LoadFailed {
-                path: bundle_path.to_path_buf(),
-                stage: LoadStage::Factory,
-                reason: "No plugin descriptor".to_string(),
-            });
-        }
-
-        let descriptor = unsafe { &*desc_ptr };
-
-        let plugin_id = unsafe { CStr::from_ptr(descriptor.id) }
-            .to_string_lossy()
-            .into_owned();
-        let name = unsafe { CStr::from_ptr(descriptor.name) }
-            .to_string_lossy()
-            .into_owned();
-        let vendor = unsafe { CStr::from_ptr(descriptor.vendor) }
-            .to_string_lossy()
-            .into_owned();
-        let version = unsafe { CStr::from_ptr(descriptor.version) }
-            .to_string_lossy()
-            .into_owned();
-        let url = unsafe { cstr_to_string(descriptor.url) };
-        let description = unsafe { cstr_to_string(descriptor.description) };
-
-        let features = if descriptor.features.is_null() {
-            Vec::new()
-        } else {
-            let mut features = Vec::new();
-            let mut ptr = descriptor.features;
-            const MAX_FEATURES: usize = 256;
-            unsafe {
-                while !(*ptr).is_null() && features.len() < MAX_FEATURES {
-                    features.push(CStr::from_ptr(*ptr).to_string_lossy().into_owned());
-                    ptr = ptr.add(1);
-                }
-            }
-            features
-        };
-
-        let plugin_id_cstr =
-            std::ffi::CString::new(plugin_id.as_str()).map_err(|e| ClapError::LoadFailed {
-                path: bundle_path.to_path_buf(),
-                stage: LoadStage::Instantiation,
-                reason: format!("Invalid plugin ID: {}", e),
-            })?;
+        let plugin_id_cstr = std::ffi::CString::new(descriptor_info.id.as_str()).map_err(|e| {
+            entry.load_failed(
+                LoadStage::Instantiation,
+                format!("Invalid plugin ID: {}", e),
+            )
+        })?;
 
-        let create_fn = factory.create_plugin.ok_or_else(|| ClapError::LoadFailed {
-            path: bundle_path.to_path_buf(),
-            stage: LoadStage::Instantiation,
-            reason: "No create_plugin function".to_string(),
+        let create_fn = factory.create_plugin.ok_or_else(|| {
+            entry.load_failed(LoadStage::Instantiation, "No create_plugin function")
         })?;
 
-        let plugin = unsafe {
-            create_fn(
-                factory_ptr as *const _,
-                host.as_raw(),
-                plugin_id_cstr.as_ptr(),
-            )
-        };
+        let plugin = unsafe { create_fn(factory, host.as_raw(), plugin_id_cstr.as_ptr()) };
 
         if plugin.is_null() {
-            return Err(ClapError::LoadFailed {
-                path: bundle_path.to_path_buf(),
-                stage: LoadStage::Instantiation,
-                reason: "Failed to create plugin instance".to_string(),
-            });
+            return Err(
+                entry.load_failed(LoadStage::Instantiation, "Failed to create plugin instance")
+            );
         }
 
         let plugin_ref = unsafe { &*plugin };
-        let plugin_init_fn = plugin_ref.init.ok_or_else(|| ClapError::LoadFailed {
-            path: bundle_path.to_path_buf(),
-            stage: LoadStage::Initialization,
-            reason: "No plugin init function".to_string(),
+        let plugin_init_fn = plugin_ref.init.ok_or_else(|| {
+            entry.load_failed(LoadStage::Initialization, "No plugin init function")
         })?;
 
         if !unsafe { plugin_init_fn(plugin) } {
-            return Err(ClapError::LoadFailed {
-                path: bundle_path.to_path_buf(),
-                stage: LoadStage::Initialization,
-                reason: "Plugin init failed".to_string(),
-            });
+            return Err(entry.load_failed(LoadStage::Initialization, "Plugin init failed"));
         }
 
         let extensions = ExtensionCache::query(plugin);
 
-        let input_port_channels = Self::port_channels_static(plugin, extensions.audio.ports, true);
-        let output_port_channels =
-            Self::port_channels_static(plugin, extensions.audio.ports, false);
+        let input_ports = Self::port_infos_static(plugin, extensions.audio.ports, true);
+        let output_ports = Self::port_infos_static(plugin, extensions.audio.ports, false);
+        let input_port_channels: Vec<u32> = input_ports.iter().map(|p| p.channel_count).collect();
+        let output_port_channels: Vec<u32> = output_ports.iter().map(|p| p.channel_count).collect();
+        let input_port_flags: Vec<AudioPortFlags> = input_ports
+            .iter()
+            .map(|p| AudioPortFlags::from_bits_truncate(p.flags))
+            .collect();
+        let output_port_flags: Vec<AudioPortFlags> = output_ports
+            .iter()
+            .map(|p| AudioPortFlags::from_bits_truncate(p.flags))
+            .collect();
+        let in_place_pairs = Self::in_place_pairs_static(&input_ports, &output_ports);
 
         let audio_inputs: usize = input_port_channels.iter().map(|&c| c as usize).sum();
         let audio_outputs: usize = output_port_channels.iter().map(|&c| c as usize).sum();
 
-        let supports_f64 = Self::check_f64_support(plugin, extensions.audio.ports);
-
-        let info = PluginInfo {
-            id: plugin_id,
-            name,
-            vendor,
-            version,
-            url,
-            description,
-            features,
-            audio_inputs: if audio_inputs > 0 { audio_inputs } else { 2 },
-            audio_outputs: if audio_outputs > 0 { audio_outputs } else { 2 },
-        };
+        // The single-precision process API passes data64 on every port
+        let mut all_flags = input_port_flags.iter().chain(&output_port_flags).peekable();
+        let supports_f64 = all_flags.peek().is_some()
+            && all_flags.all(|flags| flags.contains(AudioPortFlags::SUPPORTS_64BIT));
 
-        let input_port_channels = if input_port_channels.is_empty() {
-            vec![2]
-        } else {
-            input_port_channels
-        };
-        let output_port_channels = if output_port_channels.is_empty() {
-            vec![2]
-        } else {
-            output_port_channels
-        };
+        let info = descriptor_info.audio_io(
+            if audio_inputs > 0 { audio_inputs } else { 2 },
+            if audio_outputs > 0 { audio_outputs } else { 2 },
+        );
 
         Ok(Self {
             plugin,
-            _entry_guard: entry_guard,
-            _library: library,
+            _entry: entry,
             _host: host,
             host_state,
             extensions,
@@ -334,67 +210,73 @@ impl ClapInstance {
             max_frames,
             is_active: false,
             is_processing: false,
+            steady_time: 0,
+            split_at_param_changes: false,
             input_port_channels,
             output_port_channels,
+            input_port_flags,
+            output_port_flags,
+            in_place_pairs,
+            buffers: ProcessBuffers::default(),
         })
     }
 
-    fn port_channels_static(
+    /// Every declared port in one direction.
+    fn port_infos_static(
         plugin: *const clap_plugin,
         audio_ports: *const clap_plugin_audio_ports,
         is_input: bool,
-    ) -> Vec<u32> {
+    ) -> Vec<clap_audio_port_info> {
         if audio_ports.is_null() {
             return Vec::new();
         }
         let ext = unsafe { &*audio_ports };
-        let count_fn = match ext.count {
-            Some(f) => f,
-            None => return Vec::new(),
-        };
-        let get_fn = match ext.get {
-            Some(f) => f,
-            None => return Vec::new(),
+        let (Some(count_fn), Some(get_fn)) = (ext.count, ext.get) else {
+            return Vec::new();
         };
         let count = unsafe { count_fn(plugin, is_input) };
         let mut ports = Vec::with_capacity(count as usize);
         for i in 0..count {
             let mut info: clap_audio_port_info = unsafe { std::mem::zeroed() };
             if unsafe { get_fn(plugin, i, is_input, &mut info) } {
-                ports.push(info.channel_count);
+                ports.push(info);
             }
         }
         ports
     }
 
-    fn check_f64_support(
-        plugin: *const clap_plugin,
-        audio_ports: *const clap_plugin_audio_ports,
-    ) -> bool {
-        if audio_ports.is_null() {
-            return false;
-        }
-        let ext = unsafe { &*audio_ports };
-        let count_fn = match ext.count {
-            Some(f) => f,
-            None => return false,
-        };
-        let get_fn = match ext.get {
-            Some(f) => f,
-            None => return false,
-        };
-        let count = unsafe { count_fn(plugin, false) };
-        for i in 0..count {
-            let mut info: clap_audio_port_info = unsafe { std::mem::zeroed() };
-            if unsafe { get_fn(plugin, i, false, &mut info) }
-                && (info.flags & CLAP_AUDIO_PORT_SUPPORTS_64BITS) != 0
-            {
-                return true;
-            }
-        }
-        false
+    /// Match each input port's `in_place_pair` to the output port with that
+    /// ID. Pairs whose channel counts differ are ignored.
+    fn in_place_pairs_static(
+        inputs: &[clap_audio_port_info],
+        outputs: &[clap_audio_port_info],
+    ) -> Vec<Option<usize>> {
+        inputs
+            .iter()
+            .map(|input| {
+                if input.in_place_pair == CLAP_INVALID_ID {
+                    return None;
+                }
+                outputs.iter().position(|output| {
+                    output.id == input.in_place_pair && output.channel_count == input.channel_count
+                })
+            })
+            .collect()
+    }
+
+    /// `(input, output)` port indices the plugin declares as in-place pairs.
+    /// Pass [`InputPort::in_place`](crate::InputPort::in_place) for such an
+    /// input to have it share the output port's channels.
+    pub fn in_place_pairs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.in_place_pairs
+            .iter()
+            .enumerate()
+            .filter_map(|(input, output)| output.map(|output| (input, output)))
     }
 
+    /// Whether every declared port, input and output, supports 64-bit
+    /// samples, as required by `process` with an `AudioBuffer64`. Use
+    /// [`process_mixed`](Self::process_mixed) when only some ports do.
     pub fn supports_f64(&self) -> bool {
         self.supports_f64
     }
@@ -419,6 +301,30 @@ impl ClapInstance {
         self.is_processing
     }
 
+    /// The `steady_time` the next process call will pass: the number of
+    /// samples processed since activation, unless overridden.
+    pub fn steady_time(&self) -> i64 {
+        self.steady_time
+    }
+
+    /// Override the steady-time counter, e.g. to start an offline render at
+    /// a given sample or to account for blocks skipped while the plugin
+    /// slept. It must not go backwards while the plugin is active.
+    pub fn set_steady_time(&mut self, steady_time: i64) {
+        self.steady_time = steady_time;
+    }
+
+    /// Split process calls at every parameter change, so each change lands
+    /// on the first sample of a sub-block. For plugins that only apply
+    /// parameter values at block boundaries. Off by default.
+    pub fn set_split_at_param_changes(&mut self, split: bool) {
+        self.split_at_param_changes = split;
+    }
+
+    pub fn split_at_param_changes(&self) -> bool {
+        self.split_at_param_changes
+    }
+
     pub fn activate(&mut self) -> Result<()> {
         if self.is_active {
             return Ok(());
@@ -435,6 +341,16 @@ impl ClapInstance {
             });
         }
 
+        self.steady_time = 0;
+        self.buffers = ProcessBuffers::new(
+            flat_port_channels(&self.input_port_channels),
+            flat_port_channels(&self.output_port_channels),
+            self.max_frames,
+            self.input_port_flags
+                .iter()
+                .chain(&self.output_port_flags)
+                .any(|flags| flags.contains(AudioPortFlags::SUPPORTS_64BIT)),
+        );
         self.is_active = true;
         Ok(())
     }
@@ -528,30 +444,9 @@ impl Drop for ClapInstance {
         }
 
         // entry.deinit() is intentionally NOT called. The ENTRY_REGISTRY
-        // keeps entries initialized for the process lifetime. Many plugins
-        // corrupt global state when init/deinit are called repeatedly.
-    }
-}
-
-/// On macOS, `.clap` plugins are bundles (directories). Resolve to the actual
-/// binary at `<bundle>/Contents/MacOS/<stem>`. Returns `None` if the path is
-/// already a file or we're not on macOS.
-fn resolve_bundle_path(path: &Path) -> Option<PathBuf> {
-    #[cfg(target_os = "macos")]
-    {
-        if path.is_dir() {
-            let stem = path.file_stem()?;
-            let binary = path.join("Contents").join("MacOS").join(stem);
-            if binary.is_file() {
-                return Some(binary);
-            }
-        }
-        None
-    }
-    #[cfg(not(target_os = "macos"))]
-    {
-        let _ = path;
-        None
+        // keeps entries initialized until nothing else holds the library.
+        // Many plugins corrupt global state when init/deinit are called
+        // repeatedly.
     }
 }
 
@@ -702,4 +597,25 @@ mod tests {
             assert!(!context_menu_builder_supports(std::ptr::null(), 9999));
         }
     }
+
+    #[test]
+    fn test_in_place_pairs_match_ids_and_channels() {
+        use super::ClapInstance;
+        use clap_sys::ext::audio_ports::clap_audio_port_info;
+        use clap_sys::id::CLAP_INVALID_ID;
+
+        let port = |id: u32, channel_count: u32, in_place_pair: u32| {
+            let mut info: clap_audio_port_info = unsafe { std::mem::zeroed() };
+            info.id = id;
+            info.channel_count = channel_count;
+            info.in_place_pair = in_place_pair;
+            info
+        };
+        let inputs = [port(0, 2, 10), port(1, 1, CLAP_INVALID_ID), port(2, 2, 11)];
+        let outputs = [port(11, 1, CLAP_INVALID_ID), port(10, 2, 0)];
+
+        let pairs = ClapInstance::in_place_pairs_static(&inputs, &outputs);
+        // Input 2 names output 11, but their channel counts differ
+        assert_eq!(pairs, [Some(1), None, None]);
+    }
 }
@@ -266,6 +266,17 @@ impl ClapInstance {
             .poll(&self.host_state.processing.preset_loaded)
     }
 
+    /// Take the error reported by the plugin for an asynchronous preset load,
+    /// if any.
+    pub fn poll_preset_error(&self) -> Option<String> {
+        self.host_state
+            .processing
+            .preset_error
+            .lock()
+            .ok()
+            .and_then(|mut error| error.take())
+    }
+
     /// Call `plugin.on_main_thread()` when the plugin has requested a main-thread callback.
     pub fn on_main_thread(&mut self) -> &mut Self {
         let plugin_ref = unsafe { &*self.plugin };
@@ -0,0 +1,111 @@
+//! Tracking when a plugin can be put to sleep.
+
+use super::ClapSample;
+use super::ProcessContext;
+use crate::types::{AudioBuffer, ProcessStatus};
+
+/// Follows a plugin's [`ProcessStatus`] together with input silence and tail
+/// length to decide when process calls can be skipped.
+///
+/// ```ignore
+/// let mut sleep = SleepTracker::new();
+/// if sleep.should_process(&buffer, &ctx) {
+///     let output = plugin.process(&mut buffer, &ctx)?;
+///     sleep.update(output.status, &buffer, plugin.get_tail());
+/// } else {
+///     // Plugin is asleep: outputs are silent
+///     plugin.set_steady_time(plugin.steady_time() + buffer.num_samples as i64);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SleepTracker {
+    sleeping: bool,
+    /// Samples of tail left to render since input went quiet. `None` while
+    /// input is not quiet.
+    tail_remaining: Option<u64>,
+}
+
+impl SleepTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_sleeping(&self) -> bool {
+        self.sleeping
+    }
+
+    /// Force the plugin awake, e.g. after a parameter change made outside
+    /// the process call or a transport jump.
+    pub fn wake(&mut self) {
+        self.sleeping = false;
+        self.tail_remaining = None;
+    }
+
+    /// Whether the plugin must process this block. Wakes a sleeping plugin
+    /// if the context carries events or any input channel is not silent.
+    pub fn should_process<T: ClapSample>(
+        &mut self,
+        buffer: &AudioBuffer<T>,
+        ctx: &ProcessContext<'_>,
+    ) -> bool {
+        let has_events = !ctx.midi.is_empty()
+            || !ctx.expressions.is_empty()
+            || ctx.params.is_some_and(|p| !p.is_empty())
+            || ctx.modulations.is_some_and(|m| !m.is_empty());
+        if self.sleeping && (has_events || !inputs_silent(buffer)) {
+            self.wake();
+        }
+        !self.sleeping
+    }
+
+    /// Record the status returned by the block just processed. `tail` is the
+    /// plugin's current tail length in samples, as returned by
+    /// [`ClapInstance::get_tail`](crate::ClapInstance::get_tail); `u32::MAX`
+    /// means infinite.
+    pub fn update<T: ClapSample>(
+        &mut self,
+        status: ProcessStatus,
+        buffer: &AudioBuffer<T>,
+        tail: u32,
+    ) {
+        let input_silent = inputs_silent(buffer);
+        if !input_silent {
+            self.tail_remaining = None;
+        }
+        self.sleeping = match status {
+            ProcessStatus::Continue => false,
+            ProcessStatus::Sleep => true,
+            ProcessStatus::ContinueIfNotQuiet => outputs_silent(buffer),
+            ProcessStatus::Tail => {
+                if !input_silent || tail == u32::MAX {
+                    false
+                } else {
+                    let remaining = self.tail_remaining.get_or_insert(tail as u64);
+                    *remaining = remaining.saturating_sub(buffer.num_samples as u64);
+                    *remaining == 0
+                }
+            }
+        };
+        if self.sleeping {
+            self.tail_remaining = None;
+        }
+    }
+}
+
+fn is_silent<T: ClapSample>(channel: &[T], num_samples: usize) -> bool {
+    channel.iter().take(num_samples).all(|&s| s == T::default())
+}
+
+fn inputs_silent<T: ClapSample>(buffer: &AudioBuffer<T>) -> bool {
+    buffer
+        .inputs
+        .iter()
+        .all(|c| is_silent(c, buffer.num_samples))
+}
+
+fn outputs_silent<T: ClapSample>(buffer: &AudioBuffer<T>) -> bool {
+    buffer
+        .outputs
+        .iter()
+        .all(|c| is_silent(c, buffer.num_samples))
+}
@@ -3,8 +3,8 @@
 use super::ClapInstance;
 use crate::error::{ClapError, Result};
 use crate::host::{InputStream, OutputStream};
-use crate::types::StateContext;
-use clap_sys::factory::preset_discovery::CLAP_PRESET_DISCOVERY_LOCATION_FILE;
+use crate::types::{PresetInfo, PresetLocationKind, StateContext};
+use std::ffi::CString;
 use std::path::Path;
 use std::ptr;
 
@@ -84,7 +84,37 @@ impl ClapInstance {
         !self.extensions.state.context.is_null()
     }
 
+    /// Load a preset file through CLAP_EXT_PRESET_LOAD.
     pub fn load_preset(&mut self, path: &Path) -> Result<()> {
+        self.load_preset_from_location(
+            PresetLocationKind::File,
+            Some(&path.to_string_lossy()),
+            None,
+        )
+    }
+
+    /// Load a preset found by [`PresetProvider::metadata`](crate::PresetProvider::metadata).
+    pub fn load_preset_info(&mut self, preset: &PresetInfo) -> Result<()> {
+        self.load_preset_from_location(
+            preset.location_kind,
+            preset.location.as_deref(),
+            preset.load_key.as_deref(),
+        )
+    }
+
+    /// Load a preset by location. `location` is the file path for
+    /// [`PresetLocationKind::File`] and `None` for presets bundled inside
+    /// the plugin. `load_key` selects a preset within a container file or
+    /// the plugin.
+    ///
+    /// If the plugin reports an error through `clap_host_preset_load.on_error`
+    /// during the call, its message is returned as a [`ClapError::StateError`].
+    pub fn load_preset_from_location(
+        &mut self,
+        kind: PresetLocationKind,
+        location: Option<&str>,
+        load_key: Option<&str>,
+    ) -> Result<()> {
         if self.extensions.state.preset_load.is_null() {
             return Err(ClapError::StateError(
                 "No preset-load extension".to_string(),
@@ -94,16 +124,30 @@ impl ClapInstance {
         let from_location_fn = ext
             .from_location
             .ok_or_else(|| ClapError::StateError("No from_location function".to_string()))?;
-        let location = std::ffi::CString::new(path.to_string_lossy().as_ref())
-            .map_err(|e| ClapError::StateError(format!("Invalid path: {}", e)))?;
-        if unsafe {
+        let location = location
+            .map(CString::new)
+            .transpose()
+            .map_err(|e| ClapError::StateError(format!("Invalid location: {}", e)))?;
+        let load_key = load_key
+            .map(CString::new)
+            .transpose()
+            .map_err(|e| ClapError::StateError(format!("Invalid load key: {}", e)))?;
+
+        // Discard errors left over from an earlier asynchronous load
+        self.poll_preset_error();
+
+        let ok = unsafe {
             from_location_fn(
                 self.plugin,
-                CLAP_PRESET_DISCOVERY_LOCATION_FILE,
-                location.as_ptr(),
-                ptr::null(),
+                kind.into(),
+                location.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
+                load_key.as_ref().map_or(ptr::null(), |s| s.as_ptr()),
             )
-        } {
+        };
+        if let Some(message) = self.poll_preset_error() {
+            return Err(ClapError::StateError(message));
+        }
+        if ok {
             Ok(())
         } else {
             Err(ClapError::StateError("Preset load failed".to_string()))
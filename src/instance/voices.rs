@@ -0,0 +1,214 @@
+//! Tracking which notes are still sounding.
+
+use super::ProcessOutput;
+use crate::events::{ClapEvent, OutputEventSink};
+use crate::types::{MidiData, MidiEvent, NoteEnd, VoiceInfo};
+
+/// A note the host started that the plugin hasn't ended yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Voice {
+    /// -1 if the note was sent without an ID.
+    pub note_id: i32,
+    pub port_index: i16,
+    pub channel: u8,
+    pub key: u8,
+    /// A note-off was sent; the voice may still be in its release.
+    pub released: bool,
+}
+
+/// Follows the note-ons a host sends and the `CLAP_EVENT_NOTE_END` and
+/// `CLAP_EVENT_NOTE_CHOKE` events the plugin returns, to know which voices
+/// are still sounding.
+///
+/// Released voices stay tracked until the plugin ends them. For a plugin
+/// that never sends `CLAP_EVENT_NOTE_END`, turn that off with
+/// [`set_note_ends_reported`](Self::set_note_ends_reported) so they don't
+/// pile up.
+///
+/// Tracking input can grow the voice list and
+/// [`release_all`](Self::release_all) returns a new `Vec`, so keep those
+/// off the audio thread. Ending voices as a sink doesn't allocate.
+///
+/// ```ignore
+/// let mut voices = VoiceTracker::new();
+/// if let Some(info) = plugin.get_voice_info() {
+///     voices.set_voice_info(info);
+/// }
+/// voices.track_input(&midi);
+/// plugin.process_with_sink(&mut buffer, &ctx, &mut voices)?;
+///
+/// // On transport stop
+/// let note_offs = voices.release_all(0);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct VoiceTracker {
+    voices: Vec<Voice>,
+    voice_info: Option<VoiceInfo>,
+    forget_released: bool,
+}
+
+impl VoiceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the plugin ends voices with `CLAP_EVENT_NOTE_END`. On by
+    /// default; when off, a voice is forgotten as soon as it is released.
+    pub fn set_note_ends_reported(&mut self, reported: bool) {
+        self.forget_released = !reported;
+        if self.forget_released {
+            self.voices.retain(|v| !v.released);
+        }
+    }
+
+    pub fn note_ends_reported(&self) -> bool {
+        !self.forget_released
+    }
+
+    /// Use the plugin's voice info, as returned by
+    /// [`ClapInstance::get_voice_info`](crate::ClapInstance::get_voice_info).
+    /// Without overlapping-note support, a new note on a key that is still
+    /// sounding replaces the old voice.
+    pub fn set_voice_info(&mut self, info: VoiceInfo) {
+        self.voice_info = Some(info);
+    }
+
+    pub fn voice_info(&self) -> Option<&VoiceInfo> {
+        self.voice_info.as_ref()
+    }
+
+    /// Voices started and not yet ended, oldest first.
+    pub fn voices(&self) -> &[Voice] {
+        &self.voices
+    }
+
+    pub fn len(&self) -> usize {
+        self.voices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.voices.is_empty()
+    }
+
+    /// Whether the plugin's voice capacity is used up, so a new note will
+    /// steal a voice. Always false without voice info.
+    pub fn is_full(&self) -> bool {
+        self.voice_info
+            .is_some_and(|info| self.voices.len() >= info.voice_capacity as usize)
+    }
+
+    /// Whether any voice on `key` and `channel` is still sounding.
+    pub fn is_sounding(&self, channel: u8, key: u8) -> bool {
+        self.voices
+            .iter()
+            .any(|v| v.channel == channel && v.key == key)
+    }
+
+    /// Record the notes in events about to be sent to the plugin.
+    pub fn track_input(&mut self, events: &[MidiEvent]) {
+        for event in events {
+            self.track_event(event);
+        }
+    }
+
+    pub fn track_event(&mut self, event: &MidiEvent) {
+        match event.data {
+            MidiData::NoteOn { key, .. } => {
+                let overlapping = self
+                    .voice_info
+                    .is_none_or(|info| info.supports_overlapping_notes);
+                if !overlapping {
+                    self.voices.retain(|v| {
+                        !(v.port_index == event.port_index
+                            && v.channel == event.channel
+                            && v.key == key)
+                    });
+                }
+                self.voices.push(Voice {
+                    note_id: event.note_id,
+                    port_index: event.port_index,
+                    channel: event.channel,
+                    key,
+                    released: false,
+                });
+            }
+            MidiData::NoteOff { key, .. } => {
+                for voice in &mut self.voices {
+                    let matches = if event.note_id >= 0 {
+                        voice.note_id == event.note_id
+                    } else {
+                        voice.port_index == event.port_index
+                            && voice.channel == event.channel
+                            && voice.key == key
+                    };
+                    if matches {
+                        voice.released = true;
+                    }
+                }
+                if self.forget_released {
+                    self.voices.retain(|v| !v.released);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Remove the voices the plugin ended during a block.
+    pub fn track_output(&mut self, output: &ProcessOutput) {
+        for end in &output.note_ends {
+            self.end(end);
+        }
+    }
+
+    /// Remove the voices matching a note end. Fields set to -1 match any
+    /// value, as in CLAP.
+    pub fn end(&mut self, end: &NoteEnd) {
+        let matches = |wanted: i32, value: i32| wanted < 0 || wanted == value;
+        self.voices.retain(|v| {
+            !(matches(end.note_id, v.note_id)
+                && matches(end.port_index as i32, v.port_index as i32)
+                && matches(end.channel as i32, v.channel as i32)
+                && matches(end.key as i32, v.key as i32))
+        });
+    }
+
+    /// Note-offs for every voice not yet released, e.g. to send when the
+    /// transport stops. The voices stay tracked until the plugin ends them,
+    /// unless note ends aren't reported.
+    pub fn release_all(&mut self, sample_offset: i32) -> Vec<MidiEvent> {
+        let note_offs = self
+            .voices
+            .iter_mut()
+            .filter(|v| !v.released)
+            .map(|v| {
+                v.released = true;
+                MidiEvent::note_off(sample_offset, v.channel, v.key, 0)
+                    .with_note_id(v.note_id)
+                    .with_port_index(v.port_index)
+            })
+            .collect();
+        if self.forget_released {
+            self.voices.clear();
+        }
+        note_offs
+    }
+
+    /// Forget every voice, e.g. after deactivating the plugin.
+    pub fn clear(&mut self) {
+        self.voices.clear();
+    }
+}
+
+impl OutputEventSink for VoiceTracker {
+    fn event(&mut self, event: &ClapEvent) {
+        if let ClapEvent::NoteEnd(e) | ClapEvent::NoteChoke(e) = event {
+            self.end(&NoteEnd {
+                sample_offset: e.header.time as i32,
+                port_index: e.port_index,
+                channel: e.channel,
+                key: e.key,
+                note_id: e.note_id,
+            });
+        }
+    }
+}
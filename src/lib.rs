@@ -37,6 +37,9 @@ pub mod error;
 pub mod events;
 pub mod host;
 pub mod instance;
+pub mod preset_discovery;
+pub mod scan;
+pub mod transport;
 pub mod types;
 
 /// # Safety
@@ -50,18 +53,27 @@ pub(crate) unsafe fn cstr_to_string(ptr: *const std::ffi::c_char) -> String {
 }
 
 pub use error::{ClapError, LoadStage, Result};
-pub use events::{ClapEvent, EventList, InputEventList, OutputEventList};
+pub use events::{ClapEvent, EventList, InputEventList, OutputEventList, OutputEventSink};
 pub use host::{ClapHost, HostState, InputStream, OutputStream};
-pub use instance::{ClapInstance, ClapSample, ParamMapping, ProcessContext};
+pub use instance::{
+    ClapInstance, ClapSample, ParamMapping, ProcessContext, ProcessOutput, SleepTracker, Voice,
+    VoiceTracker,
+};
+pub use preset_discovery::{PresetDiscovery, PresetProvider};
+pub use scan::{BundleScan, ScanFailure, ScanReport, ScannedPlugin};
+pub use transport::{TempoMap, TempoSegment, Transport};
 #[cfg(unix)]
 pub use types::PosixFdFlags;
 pub use types::{
     AmbisonicConfig, AmbisonicNormalization, AmbisonicOrdering, AudioBuffer, AudioBuffer32,
     AudioBuffer64, AudioPortConfig, AudioPortConfigRequest, AudioPortFlags, AudioPortInfo,
-    AudioPortType, ClapMidiEvent, Color, ContextMenuItem, ContextMenuTarget, EditorSize, MidiData,
-    MidiEvent, NoteDialect, NoteDialects, NoteExpressionType, NoteExpressionValue, NoteName,
-    NotePortInfo, ParamAutomationState, ParameterChanges, ParameterFlags, ParameterInfo,
-    ParameterPoint, ParameterQueue, PluginInfo, RemoteControlsPage, StateContext, SurroundChannel,
-    TrackInfo, TransportInfo, TransportRequest, TriggerInfo, TuningInfo, UndoChange,
-    UndoDeltaProperties, VoiceInfo, WindowHandle,
+    AudioPortType, ClapMidiEvent, Color, ContextMenuItem, ContextMenuTarget, EditorSize, InputPort,
+    MidiData, MidiEvent, ModulationPoint, ModulationQueue, NoteDialect, NoteDialects, NoteEnd,
+    NoteExpressionType, NoteExpressionValue, NoteName, NotePortInfo, OutputPort,
+    ParamAutomationState, ParameterChanges, ParameterFlags, ParameterInfo, ParameterModulations,
+    ParameterPoint, ParameterQueue, PluginInfo, PortBuffer, PresetFiletype, PresetFlags,
+    PresetInfo, PresetLocation, PresetLocationKind, PresetPluginId, PresetProviderInfo,
+    ProcessStatus, RemoteControlsPage, Soundpack, StateContext, SurroundChannel, TrackInfo,
+    TransportChange, TransportFields, TransportInfo, TransportRequest, TriggerInfo, TuningInfo,
+    UndoChange, UndoDeltaProperties, VoiceInfo, WindowHandle,
 };
@@ -0,0 +1,443 @@
+//! Host-side indexer and metadata-receiver callbacks.
+
+use crate::cstr_to_string;
+use crate::types::{
+    PresetFiletype, PresetFlags, PresetInfo, PresetLocation, PresetLocationKind, PresetPluginId,
+    Soundpack,
+};
+use clap_sys::factory::preset_discovery::{
+    clap_preset_discovery_filetype, clap_preset_discovery_indexer, clap_preset_discovery_location,
+    clap_preset_discovery_metadata_receiver, clap_preset_discovery_soundpack,
+    CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+};
+use clap_sys::timestamp::{clap_timestamp, CLAP_TIMESTAMP_UNKNOWN};
+use clap_sys::universal_plugin_id::clap_universal_plugin_id;
+use clap_sys::version::CLAP_VERSION;
+use std::ffi::{c_char, c_void};
+use std::ptr;
+
+fn optional_string(ptr: *const c_char) -> Option<String> {
+    if ptr.is_null() {
+        None
+    } else {
+        Some(unsafe { cstr_to_string(ptr) })
+    }
+}
+
+fn optional_timestamp(timestamp: clap_timestamp) -> Option<u64> {
+    (timestamp != CLAP_TIMESTAMP_UNKNOWN).then_some(timestamp)
+}
+
+/// Collects what a provider declares during `init()`.
+///
+/// `indexer_data` points back at the full struct. Must not be moved while a
+/// provider holds the pointer.
+pub(crate) struct Indexer {
+    raw: clap_preset_discovery_indexer,
+    pub(crate) filetypes: Vec<PresetFiletype>,
+    pub(crate) locations: Vec<PresetLocation>,
+    pub(crate) soundpacks: Vec<Soundpack>,
+}
+
+impl Indexer {
+    pub(crate) fn new() -> Box<Self> {
+        Box::new(Self {
+            raw: clap_preset_discovery_indexer {
+                clap_version: CLAP_VERSION,
+                name: c"clap-host".as_ptr(),
+                vendor: c"Rust".as_ptr(),
+                url: c"".as_ptr(),
+                version: c"0.1.0".as_ptr(),
+                indexer_data: ptr::null_mut(),
+                declare_filetype: Some(indexer_declare_filetype),
+                declare_location: Some(indexer_declare_location),
+                declare_soundpack: Some(indexer_declare_soundpack),
+                get_extension: Some(indexer_get_extension),
+            },
+            filetypes: Vec::new(),
+            locations: Vec::new(),
+            soundpacks: Vec::new(),
+        })
+    }
+
+    pub(crate) fn as_raw_mut(&mut self) -> *const clap_preset_discovery_indexer {
+        self.raw.indexer_data = self as *mut Self as *mut c_void;
+        &self.raw
+    }
+}
+
+unsafe fn indexer<'a>(indexer: *const clap_preset_discovery_indexer) -> Option<&'a mut Indexer> {
+    if indexer.is_null() || (*indexer).indexer_data.is_null() {
+        None
+    } else {
+        Some(&mut *((*indexer).indexer_data as *mut Indexer))
+    }
+}
+
+pub(crate) unsafe extern "C" fn indexer_declare_filetype(
+    indexer_ptr: *const clap_preset_discovery_indexer,
+    filetype: *const clap_preset_discovery_filetype,
+) -> bool {
+    let Some(indexer) = indexer(indexer_ptr).filter(|_| !filetype.is_null()) else {
+        return false;
+    };
+    let filetype = &*filetype;
+    indexer.filetypes.push(PresetFiletype {
+        name: cstr_to_string(filetype.name),
+        description: cstr_to_string(filetype.description),
+        file_extension: cstr_to_string(filetype.file_extension),
+    });
+    true
+}
+
+pub(crate) unsafe extern "C" fn indexer_declare_location(
+    indexer_ptr: *const clap_preset_discovery_indexer,
+    location: *const clap_preset_discovery_location,
+) -> bool {
+    let Some(indexer) = indexer(indexer_ptr).filter(|_| !location.is_null()) else {
+        return false;
+    };
+    let location = &*location;
+    let kind = if location.kind == CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN {
+        PresetLocationKind::Plugin
+    } else {
+        PresetLocationKind::File
+    };
+    if kind == PresetLocationKind::File && location.location.is_null() {
+        return false;
+    }
+    indexer.locations.push(PresetLocation {
+        flags: PresetFlags::from_bits_truncate(location.flags),
+        name: cstr_to_string(location.name),
+        kind,
+        location: match kind {
+            PresetLocationKind::File => optional_string(location.location),
+            PresetLocationKind::Plugin => None,
+        },
+    });
+    true
+}
+
+pub(crate) unsafe extern "C" fn indexer_declare_soundpack(
+    indexer_ptr: *const clap_preset_discovery_indexer,
+    soundpack: *const clap_preset_discovery_soundpack,
+) -> bool {
+    let Some(indexer) = indexer(indexer_ptr).filter(|_| !soundpack.is_null()) else {
+        return false;
+    };
+    let soundpack = &*soundpack;
+    indexer.soundpacks.push(Soundpack {
+        flags: PresetFlags::from_bits_truncate(soundpack.flags),
+        id: cstr_to_string(soundpack.id),
+        name: cstr_to_string(soundpack.name),
+        description: cstr_to_string(soundpack.description),
+        homepage_url: cstr_to_string(soundpack.homepage_url),
+        vendor: cstr_to_string(soundpack.vendor),
+        image_path: cstr_to_string(soundpack.image_path),
+        release_timestamp: optional_timestamp(soundpack.release_timestamp),
+    });
+    true
+}
+
+unsafe extern "C" fn indexer_get_extension(
+    _indexer: *const clap_preset_discovery_indexer,
+    _extension_id: *const c_char,
+) -> *const c_void {
+    ptr::null()
+}
+
+/// Collects presets reported by a provider's `get_metadata()` for a single
+/// location. Setters apply to the preset most recently started with
+/// `begin_preset`.
+pub(crate) struct MetadataReceiver {
+    raw: clap_preset_discovery_metadata_receiver,
+    location_kind: PresetLocationKind,
+    location: Option<String>,
+    pub(crate) presets: Vec<PresetInfo>,
+    pub(crate) error: Option<String>,
+}
+
+impl MetadataReceiver {
+    pub(crate) fn new(location_kind: PresetLocationKind, location: Option<String>) -> Self {
+        Self {
+            raw: clap_preset_discovery_metadata_receiver {
+                receiver_data: ptr::null_mut(),
+                on_error: Some(receiver_on_error),
+                begin_preset: Some(receiver_begin_preset),
+                add_plugin_id: Some(receiver_add_plugin_id),
+                set_soundpack_id: Some(receiver_set_soundpack_id),
+                set_flags: Some(receiver_set_flags),
+                add_creator: Some(receiver_add_creator),
+                set_description: Some(receiver_set_description),
+                set_timestamps: Some(receiver_set_timestamps),
+                add_feature: Some(receiver_add_feature),
+                add_extra_info: Some(receiver_add_extra_info),
+            },
+            location_kind,
+            location,
+            presets: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// The returned pointer is only valid while this receiver isn't moved.
+    pub(crate) fn as_raw_mut(&mut self) -> *const clap_preset_discovery_metadata_receiver {
+        self.raw.receiver_data = self as *mut Self as *mut c_void;
+        &self.raw
+    }
+}
+
+unsafe fn receiver<'a>(
+    receiver: *const clap_preset_discovery_metadata_receiver,
+) -> Option<&'a mut MetadataReceiver> {
+    if receiver.is_null() || (*receiver).receiver_data.is_null() {
+        None
+    } else {
+        Some(&mut *((*receiver).receiver_data as *mut MetadataReceiver))
+    }
+}
+
+unsafe fn current_preset<'a>(
+    receiver_ptr: *const clap_preset_discovery_metadata_receiver,
+) -> Option<&'a mut PresetInfo> {
+    receiver(receiver_ptr)?.presets.last_mut()
+}
+
+pub(crate) unsafe extern "C" fn receiver_on_error(
+    receiver_ptr: *const clap_preset_discovery_metadata_receiver,
+    os_error: i32,
+    error_message: *const c_char,
+) {
+    if let Some(receiver) = receiver(receiver_ptr) {
+        let message = cstr_to_string(error_message);
+        receiver.error = Some(if os_error != 0 {
+            format!("{} (os error {})", message, os_error)
+        } else {
+            message
+        });
+    }
+}
+
+pub(crate) unsafe extern "C" fn receiver_begin_preset(
+    receiver_ptr: *const clap_preset_discovery_metadata_receiver,
+    name: *const c_char,
+    load_key: *const c_char,
+) -> bool {
+    let Some(receiver) = receiver(receiver_ptr) else {
+        return false;
+    };
+    let mut preset = PresetInfo::new(cstr_to_string(name), receiver.location_kind);
+    preset.location = receiver.location.clone();
+    preset.load_key = optional_string(load_key);
+    receiver.presets.push(preset);
+    true
+}
+
+pub(crate) unsafe extern "C" fn receiver_add_plugin_id(
+    receiver_ptr: *const clap_preset_discovery_metadata_receiver,
+    plugin_id: *const clap_universal_plugin_id,
+) {
+    if plugin_id.is_null() {
+        return;
+    }
+    if let Some(preset) = current_preset(receiver_ptr) {
+        let plugin_id = &*plugin_id;
+        preset.plugin_ids.push(PresetPluginId {
+            abi: cstr_to_string(plugin_id.abi),
+            id: cstr_to_string(plugin_id.id),
+        });
+    }
+}
+
+unsafe extern "C" fn receiver_set_soundpack_id(
+    receiver_ptr: *const clap_preset_discovery_metadata_receiver,
+    soundpack_id: *const c_char,
+) {
+    if let Some(preset) = current_preset(receiver_ptr) {
+        preset.soundpack_id = optional_string(soundpack_id);
+    }
+}
+
+pub(crate) unsafe extern "C" fn receiver_set_flags(
+    receiver_ptr: *const clap_preset_discovery_metadata_receiver,
+    flags: u32,
+) {
+    if let Some(preset) = current_preset(receiver_ptr) {
+        preset.flags = PresetFlags::from_bits_truncate(flags);
+    }
+}
+
+pub(crate) unsafe extern "C" fn receiver_add_creator(
+    receiver_ptr: *const clap_preset_discovery_metadata_receiver,
+    creator: *const c_char,
+) {
+    if let Some(preset) = current_preset(receiver_ptr) {
+        preset.creators.push(cstr_to_string(creator));
+    }
+}
+
+unsafe extern "C" fn receiver_set_description(
+    receiver_ptr: *const clap_preset_discovery_metadata_receiver,
+    description: *const c_char,
+) {
+    if let Some(preset) = current_preset(receiver_ptr) {
+        preset.description = optional_string(description);
+    }
+}
+
+unsafe extern "C" fn receiver_set_timestamps(
+    receiver_ptr: *const clap_preset_discovery_metadata_receiver,
+    creation_time: clap_timestamp,
+    modification_time: clap_timestamp,
+) {
+    if let Some(preset) = current_preset(receiver_ptr) {
+        preset.creation_time = optional_timestamp(creation_time);
+        preset.modification_time = optional_timestamp(modification_time);
+    }
+}
+
+unsafe extern "C" fn receiver_add_feature(
+    receiver_ptr: *const clap_preset_discovery_metadata_receiver,
+    feature: *const c_char,
+) {
+    if let Some(preset) = current_preset(receiver_ptr) {
+        preset.features.push(cstr_to_string(feature));
+    }
+}
+
+pub(crate) unsafe extern "C" fn receiver_add_extra_info(
+    receiver_ptr: *const clap_preset_discovery_metadata_receiver,
+    key: *const c_char,
+    value: *const c_char,
+) {
+    if let Some(preset) = current_preset(receiver_ptr) {
+        preset
+            .extra_info
+            .push((cstr_to_string(key), cstr_to_string(value)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap_sys::factory::preset_discovery::{
+        CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT, CLAP_PRESET_DISCOVERY_IS_FAVORITE,
+        CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+    };
+
+    #[test]
+    fn test_indexer_collects_declarations() {
+        let mut indexer = Indexer::new();
+        let raw = indexer.as_raw_mut();
+
+        let filetype = clap_preset_discovery_filetype {
+            name: c"Bank".as_ptr(),
+            description: ptr::null(),
+            file_extension: c"bank".as_ptr(),
+        };
+        let file_location = clap_preset_discovery_location {
+            flags: CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT,
+            name: c"Factory".as_ptr(),
+            kind: CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+            location: c"/presets".as_ptr(),
+        };
+        let plugin_location = clap_preset_discovery_location {
+            flags: 0,
+            name: c"Built-in".as_ptr(),
+            kind: CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN,
+            location: ptr::null(),
+        };
+        let soundpack = clap_preset_discovery_soundpack {
+            flags: 0,
+            id: c"pack-1".as_ptr(),
+            name: c"Pack".as_ptr(),
+            description: ptr::null(),
+            homepage_url: ptr::null(),
+            vendor: c"Example".as_ptr(),
+            image_path: ptr::null(),
+            release_timestamp: CLAP_TIMESTAMP_UNKNOWN,
+        };
+
+        unsafe {
+            assert!(indexer_declare_filetype(raw, &filetype));
+            assert!(indexer_declare_location(raw, &file_location));
+            assert!(indexer_declare_location(raw, &plugin_location));
+            assert!(indexer_declare_soundpack(raw, &soundpack));
+            assert!(!indexer_declare_filetype(raw, ptr::null()));
+        }
+
+        assert_eq!(indexer.filetypes.len(), 1);
+        assert_eq!(indexer.filetypes[0].file_extension, "bank");
+        assert_eq!(indexer.locations.len(), 2);
+        assert_eq!(indexer.locations[0].location.as_deref(), Some("/presets"));
+        assert_eq!(indexer.locations[0].flags, PresetFlags::FACTORY_CONTENT);
+        assert_eq!(indexer.locations[1].kind, PresetLocationKind::Plugin);
+        assert_eq!(indexer.locations[1].location, None);
+        assert_eq!(indexer.soundpacks[0].id, "pack-1");
+        assert_eq!(indexer.soundpacks[0].release_timestamp, None);
+    }
+
+    #[test]
+    fn test_indexer_rejects_file_location_without_path() {
+        let mut indexer = Indexer::new();
+        let location = clap_preset_discovery_location {
+            flags: 0,
+            name: c"Broken".as_ptr(),
+            kind: CLAP_PRESET_DISCOVERY_LOCATION_FILE,
+            location: ptr::null(),
+        };
+        unsafe {
+            assert!(!indexer_declare_location(indexer.as_raw_mut(), &location));
+        }
+        assert!(indexer.locations.is_empty());
+    }
+
+    #[test]
+    fn test_receiver_builds_presets() {
+        let mut receiver = MetadataReceiver::new(
+            PresetLocationKind::File,
+            Some("/presets/bank.bank".to_string()),
+        );
+        let raw = receiver.as_raw_mut();
+        let plugin_id = clap_universal_plugin_id {
+            abi: c"clap".as_ptr(),
+            id: c"com.example.synth".as_ptr(),
+        };
+
+        unsafe {
+            // Setters before begin_preset are ignored
+            receiver_add_creator(raw, c"Nobody".as_ptr());
+
+            assert!(receiver_begin_preset(raw, c"Lead".as_ptr(), c"0".as_ptr()));
+            receiver_add_plugin_id(raw, &plugin_id);
+            receiver_set_flags(raw, CLAP_PRESET_DISCOVERY_IS_FAVORITE);
+            receiver_add_creator(raw, c"Alice".as_ptr());
+            receiver_add_extra_info(raw, c"genre".as_ptr(), c"trance".as_ptr());
+
+            assert!(receiver_begin_preset(raw, c"Pad".as_ptr(), c"1".as_ptr()));
+        }
+
+        assert_eq!(receiver.presets.len(), 2);
+        let lead = &receiver.presets[0];
+        assert_eq!(lead.name, "Lead");
+        assert_eq!(lead.location.as_deref(), Some("/presets/bank.bank"));
+        assert_eq!(lead.load_key.as_deref(), Some("0"));
+        assert_eq!(lead.plugin_ids[0].id, "com.example.synth");
+        assert_eq!(lead.flags, PresetFlags::FAVORITE);
+        assert_eq!(lead.creators, vec!["Alice".to_string()]);
+        assert_eq!(
+            lead.extra_info,
+            vec![("genre".to_string(), "trance".to_string())]
+        );
+        assert!(receiver.presets[1].creators.is_empty());
+    }
+
+    #[test]
+    fn test_receiver_records_error() {
+        let mut receiver = MetadataReceiver::new(PresetLocationKind::Plugin, None);
+        unsafe {
+            receiver_on_error(receiver.as_raw_mut(), 2, c"No such file".as_ptr());
+        }
+        assert_eq!(receiver.error.as_deref(), Some("No such file (os error 2)"));
+    }
+}
@@ -0,0 +1,313 @@
+//! Host side of the CLAP preset-discovery factory.
+//!
+//! A bundle may export a preset-discovery factory listing one or more
+//! providers. Each provider declares, through the host's indexer, the file
+//! types, locations and soundpacks it knows about; the host then asks it for
+//! the metadata of each location to build its preset browser.
+//!
+//! ```ignore
+//! let discovery = PresetDiscovery::open("/path/to/plugin.clap")?;
+//! for provider_info in discovery.providers()? {
+//!     let provider = discovery.create_provider(&provider_info.id)?;
+//!     for preset in provider.crawl() {
+//!         println!("{} ({:?})", preset.name, preset.location);
+//!     }
+//! }
+//! ```
+
+mod callbacks;
+
+use crate::cstr_to_string;
+use crate::error::{ClapError, LoadStage, Result};
+use crate::instance::entry::PluginEntry;
+use crate::types::{
+    PresetFiletype, PresetInfo, PresetLocation, PresetLocationKind, PresetProviderInfo, Soundpack,
+};
+use callbacks::{Indexer, MetadataReceiver};
+use clap_sys::factory::preset_discovery::{
+    clap_preset_discovery_factory, clap_preset_discovery_provider,
+    CLAP_PRESET_DISCOVERY_FACTORY_ID, CLAP_PRESET_DISCOVERY_FACTORY_ID_COMPAT,
+};
+use std::collections::HashSet;
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+
+/// A bundle's preset-discovery factory.
+pub struct PresetDiscovery {
+    entry: PluginEntry,
+    factory: *const clap_preset_discovery_factory,
+}
+
+// Safety: the factory is a static struct exported by the library, which the
+// held `PluginEntry` keeps loaded.
+unsafe impl Send for PresetDiscovery {}
+
+impl PresetDiscovery {
+    /// Open the bundle at `path` and query its preset-discovery factory.
+    /// Fails at [`LoadStage::Factory`] if the bundle doesn't provide one.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let entry = PluginEntry::open(path.as_ref())?;
+        let mut factory = entry.get_factory(CLAP_PRESET_DISCOVERY_FACTORY_ID);
+        if factory.is_null() {
+            factory = entry.get_factory(CLAP_PRESET_DISCOVERY_FACTORY_ID_COMPAT);
+        }
+        if factory.is_null() {
+            return Err(entry.load_failed(LoadStage::Factory, "No preset discovery factory"));
+        }
+        Ok(Self {
+            entry,
+            factory: factory as *const clap_preset_discovery_factory,
+        })
+    }
+
+    fn factory(&self) -> &clap_preset_discovery_factory {
+        unsafe { &*self.factory }
+    }
+
+    /// Descriptors of every provider in the factory.
+    pub fn providers(&self) -> Result<Vec<PresetProviderInfo>> {
+        let factory = self.factory();
+        let count_fn = factory.count.ok_or_else(|| {
+            self.entry
+                .load_failed(LoadStage::Factory, "No count function")
+        })?;
+        let get_desc_fn = factory.get_descriptor.ok_or_else(|| {
+            self.entry
+                .load_failed(LoadStage::Factory, "No get_descriptor function")
+        })?;
+
+        let count = unsafe { count_fn(factory) };
+        (0..count)
+            .map(|i| {
+                let desc = unsafe { get_desc_fn(factory, i) };
+                if desc.is_null() || unsafe { (*desc).id }.is_null() {
+                    return Err(self
+                        .entry
+                        .load_failed(LoadStage::Factory, "No provider descriptor"));
+                }
+                let desc = unsafe { &*desc };
+                Ok(unsafe {
+                    PresetProviderInfo {
+                        id: cstr_to_string(desc.id),
+                        name: cstr_to_string(desc.name),
+                        vendor: cstr_to_string(desc.vendor),
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Create and initialize the provider with the given ID. Its
+    /// declarations are available as soon as this returns.
+    pub fn create_provider(&self, provider_id: &str) -> Result<PresetProvider<'_>> {
+        let info = self
+            .providers()?
+            .into_iter()
+            .find(|p| p.id == provider_id)
+            .ok_or_else(|| {
+                self.entry.load_failed(
+                    LoadStage::Factory,
+                    format!("No provider with ID '{}'", provider_id),
+                )
+            })?;
+
+        let factory = self.factory();
+        let create_fn = factory.create.ok_or_else(|| {
+            self.entry
+                .load_failed(LoadStage::Factory, "No create function")
+        })?;
+        let id_cstr = CString::new(provider_id)
+            .map_err(|e| self.entry.load_failed(LoadStage::Factory, e.to_string()))?;
+
+        let mut indexer = Indexer::new();
+        let raw = unsafe { create_fn(factory, indexer.as_raw_mut(), id_cstr.as_ptr()) };
+        if raw.is_null() {
+            return Err(self
+                .entry
+                .load_failed(LoadStage::Instantiation, "Provider creation failed"));
+        }
+
+        // From here on, Drop destroys the provider on every error path
+        let provider = PresetProvider {
+            _discovery: self,
+            raw,
+            indexer,
+            info,
+        };
+
+        let init_fn = provider.raw().init.ok_or_else(|| {
+            self.entry
+                .load_failed(LoadStage::Initialization, "No init function")
+        })?;
+        if !unsafe { init_fn(provider.raw) } {
+            return Err(self
+                .entry
+                .load_failed(LoadStage::Initialization, "Provider init failed"));
+        }
+        Ok(provider)
+    }
+}
+
+/// An initialized preset-discovery provider. Destroyed on drop.
+pub struct PresetProvider<'a> {
+    _discovery: &'a PresetDiscovery,
+    raw: *const clap_preset_discovery_provider,
+    /// Boxed so the pointer handed to the provider stays valid.
+    indexer: Box<Indexer>,
+    info: PresetProviderInfo,
+}
+
+impl PresetProvider<'_> {
+    fn raw(&self) -> &clap_preset_discovery_provider {
+        unsafe { &*self.raw }
+    }
+
+    pub fn info(&self) -> &PresetProviderInfo {
+        &self.info
+    }
+
+    pub fn filetypes(&self) -> &[PresetFiletype] {
+        &self.indexer.filetypes
+    }
+
+    pub fn locations(&self) -> &[PresetLocation] {
+        &self.indexer.locations
+    }
+
+    pub fn soundpacks(&self) -> &[Soundpack] {
+        &self.indexer.soundpacks
+    }
+
+    /// Ask the provider for the presets at a single location: a preset file
+    /// for [`PresetLocationKind::File`], or `None` for
+    /// [`PresetLocationKind::Plugin`]. If the provider reports failure, the
+    /// message it passed to `on_error` is returned as a
+    /// [`ClapError::StateError`].
+    pub fn metadata(
+        &self,
+        kind: PresetLocationKind,
+        location: Option<&str>,
+    ) -> Result<Vec<PresetInfo>> {
+        let get_metadata_fn = self
+            .raw()
+            .get_metadata
+            .ok_or_else(|| ClapError::StateError("No get_metadata function".to_string()))?;
+        let location_cstr = location
+            .map(CString::new)
+            .transpose()
+            .map_err(|e| ClapError::StateError(e.to_string()))?;
+        let location_ptr = location_cstr
+            .as_ref()
+            .map_or(std::ptr::null(), |s| s.as_ptr());
+
+        let mut receiver = MetadataReceiver::new(kind, location.map(str::to_string));
+        let ok =
+            unsafe { get_metadata_fn(self.raw, kind.into(), location_ptr, receiver.as_raw_mut()) };
+        if !ok {
+            return Err(ClapError::StateError(receiver.error.unwrap_or_else(|| {
+                format!(
+                    "Provider failed to read metadata for {}",
+                    location.unwrap_or("plugin")
+                )
+            })));
+        }
+        Ok(receiver.presets)
+    }
+
+    /// Walk every declared location and collect the presets found.
+    ///
+    /// File locations are searched recursively for files matching a declared
+    /// file type; files the provider fails to read are skipped. Call
+    /// [`metadata`](Self::metadata) directly to see the errors.
+    pub fn crawl(&self) -> Vec<PresetInfo> {
+        let mut presets = Vec::new();
+        for location in self.locations() {
+            match (location.kind, location.location.as_deref()) {
+                (PresetLocationKind::Plugin, _) => {
+                    if let Ok(found) = self.metadata(PresetLocationKind::Plugin, None) {
+                        presets.extend(found);
+                    }
+                }
+                (PresetLocationKind::File, Some(path)) => {
+                    let files = preset_files(Path::new(path), |f| self.matches_filetype(f));
+                    for file in files {
+                        let file = file.to_string_lossy();
+                        if let Ok(found) = self.metadata(PresetLocationKind::File, Some(&file)) {
+                            presets.extend(found);
+                        }
+                    }
+                }
+                (PresetLocationKind::File, None) => {}
+            }
+        }
+        presets
+    }
+
+    fn matches_filetype(&self, path: &Path) -> bool {
+        let filetypes = self.filetypes();
+        if filetypes.is_empty() {
+            return true;
+        }
+        let extension = path.extension().map(|e| e.to_string_lossy());
+        filetypes.iter().any(|ft| {
+            ft.file_extension.is_empty()
+                || extension
+                    .as_deref()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case(&ft.file_extension))
+        })
+    }
+}
+
+impl Drop for PresetProvider<'_> {
+    fn drop(&mut self) {
+        if let Some(destroy_fn) = self.raw().destroy {
+            unsafe { destroy_fn(self.raw) };
+        }
+    }
+}
+
+/// Files under `path` (or `path` itself) accepted by `matches`. Symlinked
+/// directories are followed once, so a link cycle doesn't recurse forever.
+fn preset_files(path: &Path, matches: impl Fn(&Path) -> bool) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut visited = HashSet::new();
+    let mut pending = vec![path.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        if path.is_dir() {
+            let Ok(canonical) = path.canonicalize() else {
+                continue;
+            };
+            if !visited.insert(canonical) {
+                continue;
+            }
+            if let Ok(entries) = std::fs::read_dir(&path) {
+                pending.extend(entries.flatten().map(|e| e.path()));
+            }
+        } else if path.is_file() && matches(&path) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_preset_files_stops_at_symlink_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let bank = dir.path().join("bank");
+        std::fs::create_dir(&bank).unwrap();
+        std::fs::write(bank.join("lead.preset"), b"").unwrap();
+        std::fs::write(bank.join("notes.txt"), b"").unwrap();
+        std::os::unix::fs::symlink(dir.path(), bank.join("loop")).unwrap();
+
+        let files = preset_files(dir.path(), |f| {
+            f.extension().is_some_and(|ext| ext == "preset")
+        });
+        assert_eq!(files, vec![bank.join("lead.preset")]);
+    }
+}
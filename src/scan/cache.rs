@@ -0,0 +1,403 @@
+//! Persistent scan cache.
+//!
+//! Remembers the scan result for each bundle, keyed on the bundle's
+//! modification time and size, so unchanged bundles are not reopened on the
+//! next launch. Invalidation sources are cached along with the plugins.
+//! Bundles that failed or crashed are kept as blacklist entries until the
+//! file changes; [transient](ScanFailure::transient) failures are retried
+//! on the next scan.
+//!
+//! Audio I/O counts are only cached once known, since scanning doesn't
+//! instantiate plugins; see [`ScanCache::set_audio_io`].
+//!
+//! ```ignore
+//! let mut cache = ScanCache::load(&cache_path)?;
+//! let report = cache.scan_paths(&default_search_paths(), |path| {
+//!     scanner.scan_bundle_with_sources(path)
+//! });
+//! cache.save(&cache_path)?;
+//! ```
+
+use super::wire::{
+    apply_plugin_field, apply_source_field, parse_audio_io, parse_stage, split_field,
+    write_audio_io, write_field, write_plugin_info, write_source,
+};
+use super::{find_bundles, BundleScan, InvalidationSource, ScanFailure, ScanReport, ScannedPlugin};
+use crate::error::{LoadStage, Result};
+use crate::instance::entry::resolve_bundle_path;
+use crate::types::PluginInfo;
+use std::collections::HashMap;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const HEADER: &str = "clap-host-scan-cache";
+const FORMAT_VERSION: &str = "3";
+
+/// Modification time and size of a bundle when it was scanned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct FileStamp {
+    modified_secs: u64,
+    modified_nanos: u32,
+    size: u64,
+}
+
+impl FileStamp {
+    /// Stamp the file that actually changes on rebuild — the inner binary
+    /// for macOS bundle directories.
+    pub(super) fn of(bundle: &Path) -> Option<Self> {
+        let resolved = resolve_bundle_path(bundle);
+        let metadata = std::fs::metadata(resolved.as_deref().unwrap_or(bundle)).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self {
+            modified_secs: modified.as_secs(),
+            modified_nanos: modified.subsec_nanos(),
+            size: metadata.len(),
+        })
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.split(' ');
+        Some(Self {
+            modified_secs: parts.next()?.parse().ok()?,
+            modified_nanos: parts.next()?.parse().ok()?,
+            size: parts.next()?.parse().ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedBundle {
+    stamp: FileStamp,
+    result: std::result::Result<Vec<ScannedPlugin>, ScanFailure>,
+    sources: Vec<InvalidationSource>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanCache {
+    bundles: HashMap<PathBuf, CachedBundle>,
+}
+
+impl ScanCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a cache file. A missing file yields an empty cache; malformed
+    /// entries are dropped and will simply be rescanned.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::new()),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self::parse(&text))
+    }
+
+    /// Write the cache to `path`. The file is written next to `path` under a
+    /// temporary name and renamed over it, so an interrupted save leaves the
+    /// previous cache intact.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+        temp_name.push(format!(".{}.tmp", std::process::id()));
+        let temp = path.with_file_name(temp_name);
+
+        let result = self
+            .write(&temp)
+            .and_then(|()| std::fs::rename(&temp, path).map_err(Into::into));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+        result
+    }
+
+    fn write(&self, path: &Path) -> Result<()> {
+        let mut out = BufWriter::new(std::fs::File::create(path)?);
+        write_field(&mut out, HEADER, FORMAT_VERSION)?;
+
+        let mut paths: Vec<_> = self.bundles.keys().collect();
+        paths.sort();
+        for path in paths {
+            let bundle = &self.bundles[path];
+            write_field(&mut out, "bundle", &path.to_string_lossy())?;
+            let stamp = bundle.stamp;
+            write_field(
+                &mut out,
+                "stamp",
+                &format!(
+                    "{} {} {}",
+                    stamp.modified_secs, stamp.modified_nanos, stamp.size
+                ),
+            )?;
+            match &bundle.result {
+                Ok(plugins) => {
+                    for plugin in plugins {
+                        write_field(&mut out, "plugin", &plugin.index.to_string())?;
+                        write_plugin_info(&mut out, &plugin.info)?;
+                        if let Some(audio_io) = plugin.audio_io {
+                            write_audio_io(&mut out, audio_io)?;
+                        }
+                        write_field(&mut out, "end", "")?;
+                    }
+                    for source in &bundle.sources {
+                        write_source(&mut out, source)?;
+                    }
+                }
+                Err(failure) => {
+                    write_field(&mut out, "blacklist", &failure.stage.to_string())?;
+                    write_field(&mut out, "reason", &failure.reason)?;
+                }
+            }
+        }
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        Ok(())
+    }
+
+    fn parse(text: &str) -> Self {
+        let mut cache = Self::new();
+        let mut lines = text.lines();
+        match lines.next().map(split_field) {
+            Some((HEADER, version)) if version == FORMAT_VERSION => {}
+            _ => return cache,
+        }
+
+        let mut current: Option<(PathBuf, Option<CachedBundle>)> = None;
+        let mut plugin: Option<ScannedPlugin> = None;
+
+        let mut finish = |current: &mut Option<(PathBuf, Option<CachedBundle>)>| {
+            if let Some((path, Some(bundle))) = current.take() {
+                cache.bundles.insert(path, bundle);
+            }
+        };
+
+        for line in lines {
+            let (key, value) = split_field(line);
+            match key {
+                "bundle" => {
+                    finish(&mut current);
+                    current = Some((PathBuf::from(value), None));
+                }
+                "stamp" => {
+                    if let (Some((_, bundle)), Some(stamp)) =
+                        (current.as_mut(), FileStamp::parse(&value))
+                    {
+                        *bundle = Some(CachedBundle {
+                            stamp,
+                            result: Ok(Vec::new()),
+                            sources: Vec::new(),
+                        });
+                    }
+                }
+                "plugin" => {
+                    plugin = current.as_ref().map(|(path, _)| ScannedPlugin {
+                        path: path.clone(),
+                        index: value.parse().unwrap_or(0),
+                        info: PluginInfo::new("", ""),
+                        audio_io: None,
+                    });
+                }
+                "audio_io" => {
+                    if let (Some(plugin), Some((inputs, outputs))) =
+                        (plugin.as_mut(), parse_audio_io(&value))
+                    {
+                        plugin.audio_io = Some((inputs, outputs));
+                        plugin.info.audio_inputs = inputs;
+                        plugin.info.audio_outputs = outputs;
+                    }
+                }
+                "end" => {
+                    if let (Some((_, Some(bundle))), Some(plugin)) =
+                        (current.as_mut(), plugin.take())
+                    {
+                        if let Ok(plugins) = &mut bundle.result {
+                            plugins.push(plugin);
+                        }
+                    }
+                }
+                "blacklist" => {
+                    if let Some((path, Some(bundle))) = current.as_mut() {
+                        bundle.result = Err(ScanFailure {
+                            path: path.clone(),
+                            stage: parse_stage(&value).unwrap_or(LoadStage::Opening),
+                            reason: String::new(),
+                            transient: false,
+                        });
+                    }
+                }
+                "reason" => {
+                    if let Some((
+                        _,
+                        Some(CachedBundle {
+                            result: Err(failure),
+                            ..
+                        }),
+                    )) = current.as_mut()
+                    {
+                        failure.reason = value;
+                    }
+                }
+                "source" | "glob" | "recursive" => {
+                    if let Some((path, Some(bundle))) = current.as_mut() {
+                        apply_source_field(&mut bundle.sources, path, key, value);
+                    }
+                }
+                key => {
+                    if let Some(plugin) = plugin.as_mut() {
+                        apply_plugin_field(&mut plugin.info, key, value);
+                    }
+                }
+            }
+        }
+        finish(&mut current);
+        cache
+    }
+
+    /// The cached result for `bundle`, if the file hasn't changed since it
+    /// was recorded.
+    pub fn get(
+        &self,
+        bundle: impl AsRef<Path>,
+    ) -> Option<&std::result::Result<Vec<ScannedPlugin>, ScanFailure>> {
+        let bundle = bundle.as_ref();
+        let cached = self.bundles.get(bundle)?;
+        (FileStamp::of(bundle) == Some(cached.stamp)).then_some(&cached.result)
+    }
+
+    /// The cached invalidation sources for `bundle`, if the file hasn't
+    /// changed since they were recorded.
+    pub fn invalidation_sources(&self, bundle: impl AsRef<Path>) -> Option<&[InvalidationSource]> {
+        let bundle = bundle.as_ref();
+        let cached = self.bundles.get(bundle)?;
+        (FileStamp::of(bundle) == Some(cached.stamp)).then_some(&cached.sources[..])
+    }
+
+    /// Record a scan result, either a [`BundleScan`] or just the plugins,
+    /// against the bundle's current mtime and size. Does nothing if the
+    /// bundle can't be stat'ed. A [transient](ScanFailure::transient)
+    /// failure drops any cached entry instead, so the bundle is retried.
+    pub fn insert<T: Into<BundleScan>>(
+        &mut self,
+        bundle: impl AsRef<Path>,
+        result: std::result::Result<T, ScanFailure>,
+    ) {
+        let bundle = bundle.as_ref();
+        if matches!(&result, Err(failure) if failure.transient) {
+            self.bundles.remove(bundle);
+            return;
+        }
+        let Some(stamp) = FileStamp::of(bundle) else {
+            return;
+        };
+        let (result, sources) = match result.map(Into::into) {
+            Ok(scan) => (Ok(scan.plugins), scan.invalidation_sources),
+            Err(failure) => (Err(failure), Vec::new()),
+        };
+        self.bundles.insert(
+            bundle.to_path_buf(),
+            CachedBundle {
+                stamp,
+                result,
+                sources,
+            },
+        );
+    }
+
+    /// Record the channel counts of the plugin at descriptor `index` in
+    /// `bundle`, e.g. from its audio ports once it has been loaded. Returns
+    /// `false` if the bundle has no cached plugin at that index.
+    pub fn set_audio_io(
+        &mut self,
+        bundle: impl AsRef<Path>,
+        index: u32,
+        inputs: usize,
+        outputs: usize,
+    ) -> bool {
+        let Some(CachedBundle {
+            result: Ok(plugins),
+            ..
+        }) = self.bundles.get_mut(bundle.as_ref())
+        else {
+            return false;
+        };
+        let Some(plugin) = plugins.iter_mut().find(|p| p.index == index) else {
+            return false;
+        };
+        plugin.audio_io = Some((inputs, outputs));
+        plugin.info.audio_inputs = inputs;
+        plugin.info.audio_outputs = outputs;
+        true
+    }
+
+    pub fn remove(&mut self, bundle: impl AsRef<Path>) {
+        self.bundles.remove(bundle.as_ref());
+    }
+
+    /// Whether `bundle` failed or crashed on a previous scan and hasn't
+    /// changed since.
+    pub fn is_blacklisted(&self, bundle: impl AsRef<Path>) -> bool {
+        matches!(self.get(bundle), Some(Err(_)))
+    }
+
+    /// All blacklisted bundles, whether or not they have changed since.
+    pub fn blacklist(&self) -> impl Iterator<Item = &ScanFailure> {
+        self.bundles
+            .values()
+            .filter_map(|bundle| bundle.result.as_ref().err())
+    }
+
+    /// Forget every blacklist entry so those bundles are retried.
+    pub fn clear_blacklist(&mut self) {
+        self.bundles.retain(|_, bundle| bundle.result.is_ok());
+    }
+
+    pub fn len(&self) -> usize {
+        self.bundles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bundles.is_empty()
+    }
+
+    /// Scan every bundle under `dirs`, calling `scan_bundle` only for
+    /// bundles that are new or changed since they were cached. Blacklisted
+    /// bundles are reported as failures without being reopened; transient
+    /// failures are reported but not cached. Entries for
+    /// bundles that no longer exist are dropped. `scan_bundle` may return a
+    /// [`BundleScan`], e.g. from
+    /// [`IsolatedScanner::scan_bundle_with_sources`](super::IsolatedScanner::scan_bundle_with_sources),
+    /// to have invalidation sources cached and reported too.
+    pub fn scan_paths<P, F, T>(&mut self, dirs: &[P], mut scan_bundle: F) -> ScanReport
+    where
+        P: AsRef<Path>,
+        F: FnMut(&Path) -> std::result::Result<T, ScanFailure>,
+        T: Into<BundleScan>,
+    {
+        self.bundles.retain(|path, _| path.exists());
+
+        let mut report = ScanReport::default();
+        for bundle in find_bundles(dirs) {
+            let result = match self.get(&bundle) {
+                Some(cached) => cached.clone().map(|plugins| BundleScan {
+                    plugins,
+                    invalidation_sources: self.bundles[&bundle].sources.clone(),
+                }),
+                None => {
+                    let result = scan_bundle(&bundle).map(Into::into);
+                    self.insert(&bundle, result.clone());
+                    result
+                }
+            };
+            match result {
+                Ok(scan) => {
+                    report.plugins.extend(scan.plugins);
+                    report
+                        .invalidation_sources
+                        .extend(scan.invalidation_sources);
+                }
+                Err(failure) => report.failures.push(failure),
+            }
+        }
+        report
+    }
+}
@@ -0,0 +1,298 @@
+//! Plugin-invalidation factory support and bundle change watching.
+//!
+//! A bundle whose plugin list depends on files outside the bundle (scripts,
+//! samples, generated plugins) exports `CLAP_PLUGIN_INVALIDATION_FACTORY_ID`,
+//! listing the directories and file name patterns to watch. [`BundleWatcher`]
+//! watches those sources along with the bundle binaries themselves and
+//! reports which bundles need rescanning, e.g. after a rebuild during
+//! plugin development.
+//!
+//! [`BundleWatcher::poll`] may call the factory's `refresh()`, which CLAP
+//! only allows on the main thread, so poll from the main thread.
+//!
+//! ```ignore
+//! let report = clap_host::scan::scan();
+//! let mut watcher = BundleWatcher::from_report(&report);
+//! loop {
+//!     for bundle in watcher.poll() {
+//!         let plugins = clap_host::scan::scan_bundle(&bundle);
+//!     }
+//!     std::thread::sleep(Duration::from_secs(1));
+//! }
+//! ```
+
+use super::cache::FileStamp;
+use super::{ScanFailure, ScanReport};
+use crate::cstr_to_string;
+use crate::instance::entry::{self, PluginEntry};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// A location declared by a bundle's plugin-invalidation factory. Changes to
+/// matching files mean the bundle's plugin list must be refreshed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidationSource {
+    /// The `.clap` bundle that declared this source.
+    pub bundle: PathBuf,
+    pub directory: PathBuf,
+    /// Pattern for file names in `directory`, supporting `*` and `?`.
+    pub filename_glob: String,
+    /// Whether subdirectories of `directory` are watched too.
+    pub recursive: bool,
+}
+
+impl InvalidationSource {
+    /// Whether `file_name` matches [`filename_glob`](Self::filename_glob).
+    pub fn matches(&self, file_name: &str) -> bool {
+        glob_match(self.filename_glob.as_bytes(), file_name.as_bytes())
+    }
+
+    /// Every matching file under the source directory. Each directory is
+    /// visited once, so symlink cycles terminate.
+    fn files(&self) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![self.directory.clone()];
+        while let Some(dir) = pending.pop() {
+            if !dir.canonicalize().is_ok_and(|dir| visited.insert(dir)) {
+                continue;
+            }
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    if self.recursive {
+                        pending.push(path);
+                    }
+                } else if self.matches(&entry.file_name().to_string_lossy()) {
+                    files.push(path);
+                }
+            }
+        }
+        files
+    }
+}
+
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_match(&pattern[1..], name) || (!name.is_empty() && glob_match(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob_match(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => glob_match(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+impl PluginEntry {
+    /// Sources declared by the plugin-invalidation factory. Empty if the
+    /// bundle doesn't export one.
+    pub(crate) fn invalidation_sources(&self, bundle: &Path) -> Vec<InvalidationSource> {
+        let Some(factory) = self.invalidation_factory() else {
+            return Vec::new();
+        };
+        let (Some(count_fn), Some(get_fn)) = (factory.count, factory.get) else {
+            return Vec::new();
+        };
+        let count = unsafe { count_fn(factory) };
+        (0..count)
+            .filter_map(|i| {
+                let source = unsafe { get_fn(factory, i) };
+                if source.is_null() || unsafe { (*source).directory }.is_null() {
+                    return None;
+                }
+                let source = unsafe { &*source };
+                let glob = unsafe { cstr_to_string(source.filename_glob) };
+                Some(InvalidationSource {
+                    bundle: bundle.to_path_buf(),
+                    directory: PathBuf::from(unsafe { cstr_to_string(source.directory) }),
+                    filename_glob: if glob.is_empty() {
+                        "*".to_string()
+                    } else {
+                        glob
+                    },
+                    recursive: source.recursive_scan,
+                })
+            })
+            .collect()
+    }
+
+    /// Ask the invalidation factory to refresh its plugin list. Returns
+    /// `false` if the library must be reloaded instead. Main thread only.
+    pub(crate) fn refresh(&self) -> bool {
+        self.invalidation_factory()
+            .and_then(|factory| factory.refresh.map(|f| unsafe { f(factory) }))
+            .unwrap_or(false)
+    }
+}
+
+/// Query the invalidation sources of a single bundle, in process.
+pub fn invalidation_sources(
+    path: impl AsRef<Path>,
+) -> Result<Vec<InvalidationSource>, ScanFailure> {
+    let path = path.as_ref();
+    let entry = PluginEntry::open(path).map_err(|e| ScanFailure::from_error(path, e))?;
+    Ok(entry.invalidation_sources(path))
+}
+
+type Snapshot = BTreeMap<PathBuf, FileStamp>;
+
+#[derive(Debug)]
+struct WatchedBundle {
+    stamp: Option<FileStamp>,
+    sources: Vec<(InvalidationSource, Snapshot)>,
+}
+
+/// Polls bundles and their invalidation sources for changes.
+#[derive(Debug, Default)]
+pub struct BundleWatcher {
+    bundles: HashMap<PathBuf, WatchedBundle>,
+    /// Changed bundles whose old library is still held by live instances.
+    reload_required: BTreeSet<PathBuf>,
+}
+
+impl BundleWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Watch every bundle in `report`, including failed ones, along with the
+    /// invalidation sources it lists.
+    pub fn from_report(report: &ScanReport) -> Self {
+        let mut watcher = Self::new();
+        for plugin in &report.plugins {
+            watcher.watch_bundle(&plugin.path);
+        }
+        for failure in &report.failures {
+            watcher.watch_bundle(&failure.path);
+        }
+        for source in &report.invalidation_sources {
+            watcher.watch_source(source.clone());
+        }
+        watcher
+    }
+
+    /// Watch the bundle binary for changes to its modification time or size.
+    pub fn watch_bundle(&mut self, bundle: impl AsRef<Path>) {
+        let bundle = bundle.as_ref();
+        self.bundles
+            .entry(bundle.to_path_buf())
+            .or_insert_with(|| WatchedBundle {
+                stamp: FileStamp::of(bundle),
+                sources: Vec::new(),
+            });
+    }
+
+    /// Watch an invalidation source; changes are reported against
+    /// [`InvalidationSource::bundle`].
+    pub fn watch_source(&mut self, source: InvalidationSource) {
+        self.watch_bundle(&source.bundle);
+        let watched = self.bundles.get_mut(&source.bundle).expect("just inserted");
+        if watched.sources.iter().all(|(s, _)| *s != source) {
+            let snapshot = snapshot(&source);
+            watched.sources.push((source, snapshot));
+        }
+    }
+
+    pub fn unwatch(&mut self, bundle: impl AsRef<Path>) {
+        self.bundles.remove(bundle.as_ref());
+        self.reload_required.remove(bundle.as_ref());
+    }
+
+    pub fn watched(&self) -> impl Iterator<Item = &Path> {
+        self.bundles.keys().map(PathBuf::as_path)
+    }
+
+    /// Changed bundles that can't be reloaded in this process yet, because
+    /// instances still hold the old library. A rescan in this process sees
+    /// the old code until every such instance is dropped; [`poll`](Self::poll)
+    /// then reports the bundle again.
+    pub fn reload_required(&self) -> impl Iterator<Item = &Path> {
+        self.reload_required.iter().map(PathBuf::as_path)
+    }
+
+    /// Bundles that changed since the last poll, sorted by path. Call from
+    /// the main thread.
+    ///
+    /// Libraries already loaded in this process are prepared for a rescan:
+    /// a change in an invalidation source calls the factory's `refresh()`;
+    /// otherwise, or if it returns false, the library is deinitialized and
+    /// unloaded so the next open loads the new code. A library that
+    /// instances still hold is left loaded and listed in
+    /// [`reload_required`](Self::reload_required) until they are dropped.
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        self.reload_required.retain(|path| {
+            let released = entry::release(path);
+            if released {
+                changed.push(path.clone());
+            }
+            !released
+        });
+
+        for (path, watched) in &mut self.bundles {
+            let stamp = FileStamp::of(path);
+            let binary_changed = stamp != watched.stamp;
+            watched.stamp = stamp;
+
+            let mut sources_changed = false;
+            for (source, previous) in &mut watched.sources {
+                let current = snapshot(source);
+                if current != *previous {
+                    *previous = current;
+                    sources_changed = true;
+                }
+            }
+
+            if binary_changed || sources_changed {
+                if !invalidate(path, binary_changed) {
+                    self.reload_required.insert(path.clone());
+                }
+                changed.push(path.clone());
+            }
+        }
+        changed.sort();
+        changed.dedup();
+        changed
+    }
+}
+
+fn snapshot(source: &InvalidationSource) -> Snapshot {
+    source
+        .files()
+        .into_iter()
+        .filter_map(|file| FileStamp::of(&file).map(|stamp| (file, stamp)))
+        .collect()
+}
+
+/// Prepare a loaded library for a rescan. Returns `false` if it must be
+/// reloaded but instances still hold it.
+fn invalidate(bundle: &Path, binary_changed: bool) -> bool {
+    if !entry::is_loaded(bundle) {
+        return true;
+    }
+    let refreshed = !binary_changed
+        && PluginEntry::open(bundle)
+            .map(|entry| entry.refresh())
+            .unwrap_or(false);
+    refreshed || entry::release(bundle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*", b"anything.lua"));
+        assert!(glob_match(b"*.lua", b"synth.lua"));
+        assert!(!glob_match(b"*.lua", b"synth.luac"));
+        assert!(glob_match(b"patch-??.txt", b"patch-01.txt"));
+        assert!(!glob_match(b"patch-??.txt", b"patch-1.txt"));
+        assert!(glob_match(b"a*b*c", b"aXXbYYc"));
+        assert!(!glob_match(b"", b"x"));
+    }
+}
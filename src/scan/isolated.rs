@@ -0,0 +1,343 @@
+//! Out-of-process scanning with crash isolation.
+//!
+//! Each bundle is opened in a child process running the `clap-scan-helper`
+//! binary, which streams descriptor metadata back over its stdout. A bundle
+//! that segfaults in `clap_entry.init` or `get_factory`, or hangs, only takes
+//! down the child; the host receives a [`ScanFailure`] tagged with the
+//! [`LoadStage`] the child had reached.
+
+use super::wire::{
+    apply_plugin_field, apply_source_field, parse_stage, split_field, write_field,
+    write_plugin_info, write_source,
+};
+use super::{
+    default_search_paths, find_bundles, BundleScan, InvalidationSource, ScanFailure, ScanReport,
+    ScannedPlugin,
+};
+use crate::error::{ClapError, LoadStage};
+use crate::instance::entry::PluginEntry;
+use crate::types::PluginInfo;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// File name of the helper binary built from `src/bin/clap-scan-helper.rs`.
+pub const HELPER_NAME: &str = if cfg!(windows) {
+    "clap-scan-helper.exe"
+} else {
+    "clap-scan-helper"
+};
+
+/// Scans bundles in child processes.
+///
+/// ```ignore
+/// let scanner = IsolatedScanner::new(IsolatedScanner::default_helper_path()?)
+///     .timeout(Duration::from_secs(5));
+/// let report = scanner.scan();
+/// ```
+#[derive(Debug, Clone)]
+pub struct IsolatedScanner {
+    helper: PathBuf,
+    timeout: Duration,
+}
+
+impl IsolatedScanner {
+    pub fn new(helper: impl Into<PathBuf>) -> Self {
+        Self {
+            helper: helper.into(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// The helper binary installed next to the current executable.
+    pub fn default_helper_path() -> Option<PathBuf> {
+        let exe = std::env::current_exe().ok()?;
+        let helper = exe.parent()?.join(HELPER_NAME);
+        helper.is_file().then_some(helper)
+    }
+
+    /// Maximum time a single bundle may take before the child is killed.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn helper(&self) -> &Path {
+        &self.helper
+    }
+
+    /// List the plugin descriptors in a single bundle, in a child process.
+    pub fn scan_bundle(&self, path: impl AsRef<Path>) -> Result<Vec<ScannedPlugin>, ScanFailure> {
+        self.scan_bundle_with_sources(path).map(|scan| scan.plugins)
+    }
+
+    /// List the plugin descriptors and invalidation sources of a single
+    /// bundle, in a child process.
+    pub fn scan_bundle_with_sources(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<BundleScan, ScanFailure> {
+        let path = path.as_ref();
+        let failure = |stage, reason: String| ScanFailure {
+            path: path.to_path_buf(),
+            stage,
+            reason,
+            transient: false,
+        };
+        let transient = |stage, reason: String| ScanFailure {
+            transient: true,
+            ..failure(stage, reason)
+        };
+
+        let mut child = Command::new(&self.helper)
+            .arg(path)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| {
+                transient(
+                    LoadStage::Opening,
+                    format!("Failed to spawn {}: {}", self.helper.display(), e),
+                )
+            })?;
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let deadline = Instant::now() + self.timeout;
+        let mut stage = LoadStage::Opening;
+        let mut plugins = Vec::new();
+        let mut current: Option<ScannedPlugin> = None;
+        let mut sources: Vec<InvalidationSource> = Vec::new();
+        let mut error = None;
+        let mut done = false;
+
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match rx.recv_timeout(remaining) {
+                Ok(line) => {
+                    let (key, value) = split_field(&line);
+                    match key {
+                        "stage" => stage = parse_stage(&value).unwrap_or(stage),
+                        "plugin" => {
+                            current = Some(ScannedPlugin {
+                                path: path.to_path_buf(),
+                                index: value.parse().unwrap_or(0),
+                                info: PluginInfo::new("", ""),
+                                audio_io: None,
+                            })
+                        }
+                        "end" => plugins.extend(current.take()),
+                        "error" => error = Some(value),
+                        "done" => done = true,
+                        "source" | "glob" | "recursive" => {
+                            apply_source_field(&mut sources, path, key, value);
+                        }
+                        key => {
+                            if let Some(plugin) = current.as_mut() {
+                                apply_plugin_field(&mut plugin.info, key, value);
+                            }
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(transient(
+                        stage,
+                        format!("Scanner timed out after {:?}", self.timeout),
+                    ));
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        // Closing stdout doesn't mean the child exits: it may still hang in
+        // deinit or at exit, so keep to the deadline
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                Ok(None) => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(failure(
+                        stage,
+                        format!(
+                            "Scanner finished but did not exit within {:?}",
+                            self.timeout
+                        ),
+                    ));
+                }
+                Err(e) => {
+                    return Err(transient(
+                        stage,
+                        format!("Failed to wait for scanner: {}", e),
+                    ))
+                }
+            }
+        };
+
+        if let Some(reason) = error {
+            return Err(failure(stage, reason));
+        }
+        if !done || !status.success() {
+            return Err(failure(
+                stage,
+                format!("Scanner process crashed ({})", status),
+            ));
+        }
+        Ok(BundleScan {
+            plugins,
+            invalidation_sources: sources,
+        })
+    }
+
+    /// Scan every bundle found under `dirs`, one child process per bundle.
+    pub fn scan_paths<P: AsRef<Path>>(&self, dirs: &[P]) -> ScanReport {
+        let mut report = ScanReport::default();
+        for bundle in find_bundles(dirs) {
+            match self.scan_bundle_with_sources(&bundle) {
+                Ok(scan) => {
+                    report.plugins.extend(scan.plugins);
+                    report
+                        .invalidation_sources
+                        .extend(scan.invalidation_sources);
+                }
+                Err(failure) => report.failures.push(failure),
+            }
+        }
+        report
+    }
+
+    /// Scan the [default search paths](default_search_paths).
+    pub fn scan(&self) -> ScanReport {
+        self.scan_paths(&default_search_paths())
+    }
+}
+
+/// Entry point of the `clap-scan-helper` binary. Scans the bundle given as
+/// the first argument and writes the results to stdout. Returns the process
+/// exit code.
+#[doc(hidden)]
+pub fn helper_main() -> i32 {
+    let Some(path) = std::env::args_os().nth(1) else {
+        eprintln!("usage: {} <bundle.clap>", HELPER_NAME);
+        return 2;
+    };
+    let Ok(out) = protocol_stream() else {
+        return 1;
+    };
+    match write_bundle(&mut io::BufWriter::new(out), Path::new(&path)) {
+        Ok(()) => 0,
+        Err(_) => 1,
+    }
+}
+
+/// Take over stdout for the protocol and point fd 1 at stderr, so anything
+/// the plugin prints can't be mistaken for a record.
+#[cfg(unix)]
+fn protocol_stream() -> io::Result<std::fs::File> {
+    use std::os::raw::c_int;
+    use std::os::unix::io::FromRawFd;
+
+    extern "C" {
+        fn dup(fd: c_int) -> c_int;
+        fn dup2(src: c_int, dst: c_int) -> c_int;
+    }
+
+    let fd = unsafe { dup(1) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if unsafe { dup2(2, 1) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // Safety: `fd` is a fresh descriptor owned by nothing else.
+    Ok(unsafe { std::fs::File::from_raw_fd(fd) })
+}
+
+/// Take over stdout for the protocol and point both the C runtime's fd 1
+/// and the standard output handle at stderr, so anything the plugin prints
+/// can't be mistaken for a record.
+#[cfg(windows)]
+fn protocol_stream() -> io::Result<std::fs::File> {
+    use std::os::raw::c_int;
+    use std::os::windows::io::{AsRawHandle, FromRawHandle, RawHandle};
+
+    extern "C" {
+        fn _dup(fd: c_int) -> c_int;
+        fn _dup2(src: c_int, dst: c_int) -> c_int;
+        fn _get_osfhandle(fd: c_int) -> isize;
+    }
+    extern "system" {
+        fn SetStdHandle(std_handle: u32, handle: RawHandle) -> i32;
+    }
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+
+    let fd = unsafe { _dup(1) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let handle = unsafe { _get_osfhandle(fd) };
+    if handle == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    if unsafe { _dup2(2, 1) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if unsafe { SetStdHandle(STD_OUTPUT_HANDLE, io::stderr().as_raw_handle()) } == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // Safety: the handle belongs to the duplicated descriptor `fd`, which is
+    // never closed through the C runtime.
+    Ok(unsafe { std::fs::File::from_raw_handle(handle as RawHandle) })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn protocol_stream() -> io::Result<io::Stdout> {
+    Ok(io::stdout())
+}
+
+fn write_bundle(out: &mut impl Write, path: &Path) -> io::Result<()> {
+    write_field(out, "stage", &LoadStage::Opening.to_string())?;
+    out.flush()?;
+    let result = PluginEntry::open(path).and_then(|entry| {
+        write_field(out, "stage", &LoadStage::Factory.to_string())?;
+        out.flush()?;
+        Ok((entry.descriptors()?, entry.invalidation_sources(path)))
+    });
+
+    match result {
+        Ok((descriptors, sources)) => {
+            for (index, info) in descriptors.iter().enumerate() {
+                write_field(out, "plugin", &index.to_string())?;
+                write_plugin_info(out, info)?;
+                write_field(out, "end", "")?;
+            }
+            for source in &sources {
+                write_source(out, source)?;
+            }
+        }
+        Err(ClapError::LoadFailed { stage, reason, .. }) => {
+            write_field(out, "stage", &stage.to_string())?;
+            write_field(out, "error", &reason)?;
+        }
+        Err(e) => write_field(out, "error", &e.to_string())?,
+    }
+    write_field(out, "done", "")?;
+    out.flush()
+}
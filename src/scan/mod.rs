@@ -0,0 +1,261 @@
+//! Plugin bundle scanning.
+//!
+//! Walks the standard CLAP search paths and lists every plugin descriptor in
+//! every `.clap` bundle without instantiating anything. Bundles that fail to
+//! open are reported alongside the results instead of aborting the scan.
+//!
+//! For crash isolation, [`IsolatedScanner`] runs the same scan in a child
+//! process per bundle. [`ScanCache`] persists results between launches.
+//! [`BundleWatcher`] reports bundles that need rescanning.
+//!
+//! ```ignore
+//! let report = clap_host::scan::scan();
+//! for plugin in &report.plugins {
+//!     println!("{} — {}", plugin.info, plugin.path.display());
+//! }
+//! for failure in &report.failures {
+//!     eprintln!("{}", failure);
+//! }
+//! ```
+
+mod cache;
+mod invalidation;
+pub mod isolated;
+mod wire;
+
+pub use cache::ScanCache;
+pub use invalidation::{invalidation_sources, BundleWatcher, InvalidationSource};
+pub use isolated::IsolatedScanner;
+
+use crate::error::{ClapError, LoadStage};
+use crate::instance::entry::{self, PluginEntry};
+use crate::instance::ClapInstance;
+use crate::types::PluginInfo;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// A plugin descriptor found in a bundle.
+#[derive(Debug, Clone)]
+pub struct ScannedPlugin {
+    /// Path of the `.clap` bundle containing the plugin.
+    pub path: PathBuf,
+    /// Descriptor index in the bundle's factory, for [`ClapInstance::load_with_index`].
+    pub index: u32,
+    pub info: PluginInfo,
+    /// Input and output channel counts, if known. Scanning doesn't
+    /// instantiate the plugin, so they are `None` and `info` holds
+    /// `PluginInfo`'s defaults until recorded with
+    /// [`ScanCache::set_audio_io`].
+    pub audio_io: Option<(usize, usize)>,
+}
+
+/// A bundle that could not be scanned.
+#[derive(Debug, Clone)]
+pub struct ScanFailure {
+    pub path: PathBuf,
+    pub stage: LoadStage,
+    pub reason: String,
+    /// The failure came from the host side rather than the plugin, e.g. the
+    /// scanner couldn't be spawned or timed out. [`ScanCache`] retries these
+    /// instead of blacklisting the bundle.
+    pub transient: bool,
+}
+
+impl fmt::Display for ScanFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Failed to scan {}: {} - {}",
+            self.path.display(),
+            self.stage,
+            self.reason
+        )
+    }
+}
+
+impl ScanFailure {
+    fn from_error(path: &Path, error: ClapError) -> Self {
+        match error {
+            ClapError::LoadFailed { stage, reason, .. } => Self {
+                path: path.to_path_buf(),
+                stage,
+                reason,
+                transient: false,
+            },
+            other => Self {
+                path: path.to_path_buf(),
+                stage: LoadStage::Opening,
+                reason: other.to_string(),
+                transient: false,
+            },
+        }
+    }
+}
+
+/// Everything scanning a single bundle finds.
+#[derive(Debug, Clone, Default)]
+pub struct BundleScan {
+    pub plugins: Vec<ScannedPlugin>,
+    /// Sources declared by the bundle's plugin-invalidation factory, if it
+    /// exports one.
+    pub invalidation_sources: Vec<InvalidationSource>,
+}
+
+impl From<Vec<ScannedPlugin>> for BundleScan {
+    fn from(plugins: Vec<ScannedPlugin>) -> Self {
+        Self {
+            plugins,
+            invalidation_sources: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScanReport {
+    pub plugins: Vec<ScannedPlugin>,
+    pub failures: Vec<ScanFailure>,
+    /// Sources declared by bundles exporting a plugin-invalidation factory.
+    pub invalidation_sources: Vec<InvalidationSource>,
+}
+
+/// Standard CLAP search paths for the current platform, followed by the
+/// entries of `CLAP_PATH`.
+pub fn default_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(home) = std::env::var_os("HOME") {
+            paths.push(PathBuf::from(home).join(".clap"));
+        }
+        paths.push(PathBuf::from("/usr/lib/clap"));
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        if let Some(home) = std::env::var_os("HOME") {
+            paths.push(PathBuf::from(home).join("Library/Audio/Plug-Ins/CLAP"));
+        }
+        paths.push(PathBuf::from("/Library/Audio/Plug-Ins/CLAP"));
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        if let Some(common) = std::env::var_os("COMMONPROGRAMFILES") {
+            paths.push(PathBuf::from(common).join("CLAP"));
+        }
+        if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+            paths.push(PathBuf::from(local).join("Programs\\Common\\CLAP"));
+        }
+    }
+
+    if let Some(clap_path) = std::env::var_os("CLAP_PATH") {
+        paths.extend(std::env::split_paths(&clap_path));
+    }
+
+    paths
+}
+
+/// Recursively collect `.clap` bundles under `dirs`. Missing directories are
+/// skipped. Bundles are not descended into, so macOS bundle directories are
+/// returned as a single path. Each directory is visited once, so symlink
+/// cycles terminate.
+pub fn find_bundles<P: AsRef<Path>>(dirs: &[P]) -> Vec<PathBuf> {
+    let mut bundles = Vec::new();
+    let mut visited = HashSet::new();
+    for dir in dirs {
+        collect_bundles(dir.as_ref(), &mut bundles, &mut visited);
+    }
+    bundles.sort();
+    bundles.dedup();
+    bundles
+}
+
+fn collect_bundles(dir: &Path, bundles: &mut Vec<PathBuf>, visited: &mut HashSet<PathBuf>) {
+    let Ok(canonical) = dir.canonicalize() else {
+        return;
+    };
+    if !visited.insert(canonical) {
+        return;
+    }
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if is_bundle(&path) {
+            bundles.push(path);
+        } else if path.is_dir() {
+            collect_bundles(&path, bundles, visited);
+        }
+    }
+}
+
+fn is_bundle(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("clap"))
+}
+
+/// List the plugin descriptors in a single bundle.
+pub fn scan_bundle(path: impl AsRef<Path>) -> Result<Vec<ScannedPlugin>, ScanFailure> {
+    let path = path.as_ref();
+    let descriptors =
+        ClapInstance::descriptors(path).map_err(|e| ScanFailure::from_error(path, e))?;
+    Ok(scanned_plugins(path, descriptors))
+}
+
+/// List the plugin descriptors and invalidation sources of a single bundle.
+pub fn scan_bundle_with_sources(path: impl AsRef<Path>) -> Result<BundleScan, ScanFailure> {
+    let path = path.as_ref();
+    let entry = PluginEntry::open(path).map_err(|e| ScanFailure::from_error(path, e))?;
+    let descriptors = entry
+        .descriptors()
+        .map_err(|e| ScanFailure::from_error(path, e))?;
+    Ok(BundleScan {
+        plugins: scanned_plugins(path, descriptors),
+        invalidation_sources: entry.invalidation_sources(path),
+    })
+}
+
+fn scanned_plugins(path: &Path, descriptors: Vec<PluginInfo>) -> Vec<ScannedPlugin> {
+    descriptors
+        .into_iter()
+        .enumerate()
+        .map(|(index, info)| ScannedPlugin {
+            path: path.to_path_buf(),
+            index: index as u32,
+            info,
+            audio_io: None,
+        })
+        .collect()
+}
+
+/// Scan every bundle found under `dirs`.
+///
+/// Bundles loaded only for the scan are deinitialized and unloaded
+/// afterwards; bundles already in use by this process stay loaded.
+pub fn scan_paths<P: AsRef<Path>>(dirs: &[P]) -> ScanReport {
+    let mut report = ScanReport::default();
+    for bundle in find_bundles(dirs) {
+        let was_loaded = entry::is_loaded(&bundle);
+        match scan_bundle_with_sources(&bundle) {
+            Ok(scan) => {
+                report.plugins.extend(scan.plugins);
+                report
+                    .invalidation_sources
+                    .extend(scan.invalidation_sources);
+            }
+            Err(failure) => report.failures.push(failure),
+        }
+        if !was_loaded {
+            entry::release(&bundle);
+        }
+    }
+    report
+}
+
+/// Scan the [default search paths](default_search_paths).
+pub fn scan() -> ScanReport {
+    scan_paths(&default_search_paths())
+}
@@ -0,0 +1,232 @@
+//! Line-oriented record format for scan results.
+//!
+//! Each line is `key<TAB>value`, with backslash, tab and newline escaped in
+//! the value. Shared by the out-of-process scan helper protocol and the scan
+//! cache file.
+
+use super::InvalidationSource;
+use crate::error::LoadStage;
+use crate::types::PluginInfo;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub(crate) fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+pub(crate) fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+pub(crate) fn write_field(out: &mut impl Write, key: &str, value: &str) -> io::Result<()> {
+    writeln!(out, "{}\t{}", key, escape(value))
+}
+
+/// Split a line into its key and unescaped value.
+pub(crate) fn split_field(line: &str) -> (&str, String) {
+    let line = line.trim_end_matches(['\r', '\n']);
+    match line.split_once('\t') {
+        Some((key, value)) => (key, unescape(value)),
+        None => (line, String::new()),
+    }
+}
+
+pub(crate) fn write_plugin_info(out: &mut impl Write, info: &PluginInfo) -> io::Result<()> {
+    write_field(out, "id", &info.id)?;
+    write_field(out, "name", &info.name)?;
+    write_field(out, "vendor", &info.vendor)?;
+    write_field(out, "version", &info.version)?;
+    write_field(out, "url", &info.url)?;
+    write_field(out, "description", &info.description)?;
+    for feature in &info.features {
+        write_field(out, "feature", feature)?;
+    }
+    Ok(())
+}
+
+/// Write known input and output channel counts. Unknown counts are left
+/// out rather than written as defaults.
+pub(crate) fn write_audio_io(
+    out: &mut impl Write,
+    (inputs, outputs): (usize, usize),
+) -> io::Result<()> {
+    write_field(out, "audio_io", &format!("{} {}", inputs, outputs))
+}
+
+pub(crate) fn parse_audio_io(value: &str) -> Option<(usize, usize)> {
+    let (inputs, outputs) = value.split_once(' ')?;
+    Some((inputs.parse().ok()?, outputs.parse().ok()?))
+}
+
+/// Apply a field written by [`write_plugin_info`]. Returns `false` for keys
+/// that don't belong to a `PluginInfo`.
+pub(crate) fn apply_plugin_field(info: &mut PluginInfo, key: &str, value: String) -> bool {
+    match key {
+        "id" => info.id = value,
+        "name" => info.name = value,
+        "vendor" => info.vendor = value,
+        "version" => info.version = value,
+        "url" => info.url = value,
+        "description" => info.description = value,
+        "feature" => info.features.push(value),
+        _ => return false,
+    }
+    true
+}
+
+pub(crate) fn write_source(out: &mut impl Write, source: &InvalidationSource) -> io::Result<()> {
+    write_field(out, "source", &source.directory.to_string_lossy())?;
+    write_field(out, "glob", &source.filename_glob)?;
+    write_field(out, "recursive", if source.recursive { "1" } else { "0" })
+}
+
+/// Apply a field written by [`write_source`]: `source` starts a new source
+/// declared by `bundle`, the others update the last one. Returns `false`
+/// for keys that don't belong to a source.
+pub(crate) fn apply_source_field(
+    sources: &mut Vec<InvalidationSource>,
+    bundle: &Path,
+    key: &str,
+    value: String,
+) -> bool {
+    match key {
+        "source" => sources.push(InvalidationSource {
+            bundle: bundle.to_path_buf(),
+            directory: PathBuf::from(value),
+            filename_glob: "*".to_string(),
+            recursive: false,
+        }),
+        "glob" => {
+            if let Some(source) = sources.last_mut() {
+                source.filename_glob = value;
+            }
+        }
+        "recursive" => {
+            if let Some(source) = sources.last_mut() {
+                source.recursive = value == "1";
+            }
+        }
+        _ => return false,
+    }
+    true
+}
+
+pub(crate) fn parse_stage(value: &str) -> Option<LoadStage> {
+    match value {
+        "opening" => Some(LoadStage::Opening),
+        "factory" => Some(LoadStage::Factory),
+        "instantiation" => Some(LoadStage::Instantiation),
+        "initialization" => Some(LoadStage::Initialization),
+        "activation" => Some(LoadStage::Activation),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_roundtrip() {
+        let value = "tab\there\nnewline \\ backslash\r";
+        let escaped = escape(value);
+        assert!(!escaped.contains('\t'));
+        assert!(!escaped.contains('\n'));
+        assert_eq!(unescape(&escaped), value);
+    }
+
+    #[test]
+    fn test_plugin_info_roundtrip() {
+        let info = PluginInfo::new("com.example.synth", "Synth\tPro")
+            .vendor("Example")
+            .version("1.2.3")
+            .description("Line one\nLine two")
+            .features(vec!["instrument".to_string(), "synthesizer".to_string()]);
+
+        let mut buf = Vec::new();
+        write_plugin_info(&mut buf, &info).unwrap();
+        // Descriptor fields only; audio I/O isn't part of a descriptor
+        assert!(!String::from_utf8_lossy(&buf).contains("audio_io"));
+
+        let mut parsed = PluginInfo::new("", "");
+        for line in String::from_utf8(buf).unwrap().lines() {
+            let (key, value) = split_field(line);
+            assert!(apply_plugin_field(&mut parsed, key, value));
+        }
+        assert_eq!(parsed.id, info.id);
+        assert_eq!(parsed.name, info.name);
+        assert_eq!(parsed.description, info.description);
+        assert_eq!(parsed.features, info.features);
+    }
+
+    #[test]
+    fn test_audio_io_roundtrip() {
+        let mut buf = Vec::new();
+        write_audio_io(&mut buf, (0, 6)).unwrap();
+        let line = String::from_utf8(buf).unwrap();
+        let (key, value) = split_field(line.trim_end());
+        assert_eq!(key, "audio_io");
+        assert_eq!(parse_audio_io(&value), Some((0, 6)));
+        assert_eq!(parse_audio_io("2"), None);
+    }
+
+    #[test]
+    fn test_source_roundtrip() {
+        let bundle = Path::new("/plugins/scripts.clap");
+        let source = InvalidationSource {
+            bundle: bundle.to_path_buf(),
+            directory: PathBuf::from("/home/user/scripts"),
+            filename_glob: "*.lua".to_string(),
+            recursive: true,
+        };
+
+        let mut buf = Vec::new();
+        write_source(&mut buf, &source).unwrap();
+
+        let mut parsed = Vec::new();
+        for line in String::from_utf8(buf).unwrap().lines() {
+            let (key, value) = split_field(line);
+            assert!(apply_source_field(&mut parsed, bundle, key, value));
+        }
+        assert_eq!(parsed, vec![source]);
+    }
+
+    #[test]
+    fn test_parse_stage_matches_display() {
+        for stage in [
+            LoadStage::Opening,
+            LoadStage::Factory,
+            LoadStage::Instantiation,
+            LoadStage::Initialization,
+            LoadStage::Activation,
+        ] {
+            assert_eq!(parse_stage(&stage.to_string()), Some(stage));
+        }
+        assert_eq!(parse_stage("bogus"), None);
+    }
+}
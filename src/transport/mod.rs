@@ -0,0 +1,279 @@
+//! Host transport driver.
+//!
+//! [`Transport`] owns the play state, position and loop region, and turns
+//! them into the [`TransportInfo`] each process call needs, so callers don't
+//! have to compute beat positions and bar numbers by hand.
+//!
+//! ```ignore
+//! let mut transport = Transport::new(48000.0).with_tempo(128.0);
+//! transport.play();
+//! loop {
+//!     transport.apply_requests(plugin.drain_transport_requests());
+//!     transport.next_block(512);
+//!     plugin.process(&mut buffer, &ProcessContext {
+//!         midi: &midi,
+//!         ..transport.context()
+//!     })?;
+//! }
+//! ```
+
+mod tempo_map;
+
+pub use tempo_map::{TempoMap, TempoSegment};
+
+use tempo_map::valid_sample_rate;
+
+use crate::instance::ProcessContext;
+use crate::types::{TransportChange, TransportInfo, TransportRequest};
+
+/// Advances a [`TransportInfo`] across successive blocks.
+///
+/// Positions are in quarter-note beats, as in CLAP. While playing, each
+/// [`next_block`](Self::next_block) moves the position forward by the block
+/// length; with the loop active, a block that crosses the loop end wraps
+/// back to the loop start and reports the jump as a [`TransportChange`].
+/// Tempo and time signature follow a [`TempoMap`]; a change inside a block
+/// is reported the same way.
+#[derive(Debug, Clone)]
+pub struct Transport {
+    sample_rate: f64,
+    tempo_map: TempoMap,
+    playing: bool,
+    recording: bool,
+    loop_active: bool,
+    loop_start_beats: f64,
+    loop_end_beats: f64,
+    /// Position at the start of the next block.
+    position_beats: f64,
+    block_info: TransportInfo,
+    block_changes: Vec<TransportChange>,
+}
+
+impl Transport {
+    /// A stopped transport at beat 0, 120 BPM in 4/4. A `sample_rate`
+    /// below 1 Hz is taken as 1 Hz.
+    pub fn new(sample_rate: f64) -> Self {
+        let mut transport = Self {
+            sample_rate: valid_sample_rate(sample_rate),
+            tempo_map: TempoMap::default(),
+            playing: false,
+            recording: false,
+            loop_active: false,
+            loop_start_beats: 0.0,
+            loop_end_beats: 0.0,
+            position_beats: 0.0,
+            block_info: TransportInfo::new(),
+            block_changes: Vec::with_capacity(4),
+        };
+        transport.block_info = transport.info_at(0.0);
+        transport
+    }
+
+    pub fn with_tempo(mut self, tempo: f64) -> Self {
+        self.set_tempo(tempo);
+        self
+    }
+
+    pub fn with_time_signature(mut self, numerator: i32, denominator: i32) -> Self {
+        self.set_time_signature(numerator, denominator);
+        self
+    }
+
+    pub fn with_tempo_map(mut self, tempo_map: TempoMap) -> Self {
+        self.tempo_map = tempo_map;
+        self
+    }
+
+    /// Set the loop region in beats and enable it.
+    pub fn with_loop(mut self, start_beats: f64, end_beats: f64) -> Self {
+        self.set_loop(true, start_beats, end_beats);
+        self
+    }
+
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+
+    /// Tempo at the current position.
+    pub fn tempo(&self) -> f64 {
+        self.tempo_map.tempo_at(self.position_beats)
+    }
+
+    /// Set the tempo at beat 0. Later changes in the tempo map are kept.
+    pub fn set_tempo(&mut self, tempo: f64) {
+        self.tempo_map.set_tempo(0.0, tempo);
+    }
+
+    /// Set the time signature at beat 0. Later changes in the tempo map
+    /// are kept.
+    pub fn set_time_signature(&mut self, numerator: i32, denominator: i32) {
+        self.tempo_map
+            .set_time_signature(0.0, numerator, denominator);
+    }
+
+    pub fn tempo_map(&self) -> &TempoMap {
+        &self.tempo_map
+    }
+
+    pub fn tempo_map_mut(&mut self) -> &mut TempoMap {
+        &mut self.tempo_map
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    pub fn is_loop_active(&self) -> bool {
+        self.loop_active
+    }
+
+    /// Position at the start of the next block, in beats.
+    pub fn position_beats(&self) -> f64 {
+        self.position_beats
+    }
+
+    pub fn play(&mut self) {
+        self.playing = true;
+    }
+
+    /// Stop playback and recording. The position is kept.
+    pub fn stop(&mut self) {
+        self.playing = false;
+        self.recording = false;
+    }
+
+    pub fn seek(&mut self, position_beats: f64) {
+        self.position_beats = position_beats.max(0.0);
+    }
+
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    pub fn set_loop(&mut self, active: bool, start_beats: f64, end_beats: f64) {
+        self.loop_active = active;
+        self.loop_start_beats = start_beats;
+        self.loop_end_beats = end_beats;
+    }
+
+    /// Apply a request a plugin made through the host transport-control
+    /// extension.
+    pub fn apply(&mut self, request: TransportRequest) {
+        match request {
+            TransportRequest::Start | TransportRequest::Continue => self.play(),
+            TransportRequest::Stop => self.stop(),
+            TransportRequest::Pause => self.playing = false,
+            TransportRequest::TogglePlay => self.playing = !self.playing,
+            TransportRequest::Jump { position_beats } => self.seek(position_beats),
+            TransportRequest::LoopRegion {
+                start_beats,
+                duration_beats,
+            } => {
+                self.loop_start_beats = start_beats;
+                self.loop_end_beats = start_beats + duration_beats;
+            }
+            TransportRequest::ToggleLoop => self.loop_active = !self.loop_active,
+            TransportRequest::EnableLoop(active) => self.loop_active = active,
+            TransportRequest::Record(recording) => self.recording = recording,
+            TransportRequest::ToggleRecord => self.recording = !self.recording,
+        }
+    }
+
+    /// Apply requests in order, e.g. those returned by
+    /// [`ClapInstance::drain_transport_requests`](crate::ClapInstance::drain_transport_requests).
+    pub fn apply_requests(&mut self, requests: impl IntoIterator<Item = TransportRequest>) {
+        for request in requests {
+            self.apply(request);
+        }
+    }
+
+    /// Prepare the transport for a block of `num_samples` and advance the
+    /// position past it. The block's state is then available from
+    /// [`block_info`](Self::block_info), [`block_changes`](Self::block_changes)
+    /// and [`context`](Self::context).
+    pub fn next_block(&mut self, num_samples: u32) {
+        self.block_changes.clear();
+        self.block_info = self.info_at(self.position_beats);
+        if !self.playing {
+            return;
+        }
+
+        let mut position = self.position_beats;
+        let mut offset = 0u32;
+        while let Some((samples, target)) = self.next_boundary(position, num_samples - offset) {
+            offset += samples;
+            position = target;
+            // A boundary on the last sample is where the next block starts
+            if offset < num_samples {
+                self.block_changes.push(TransportChange {
+                    sample_offset: offset,
+                    transport: self.info_at(position),
+                });
+            }
+        }
+        self.position_beats =
+            self.tempo_map
+                .advance(position, (num_samples - offset) as f64, self.sample_rate);
+    }
+
+    /// Transport state at the start of the last prepared block.
+    pub fn block_info(&self) -> &TransportInfo {
+        &self.block_info
+    }
+
+    /// Changes inside the last prepared block, such as a loop wraparound or
+    /// a tempo change.
+    pub fn block_changes(&self) -> &[TransportChange] {
+        &self.block_changes
+    }
+
+    /// A process context carrying the last prepared block's transport, to
+    /// fill the remaining fields with struct update syntax.
+    pub fn context(&self) -> ProcessContext<'_> {
+        ProcessContext {
+            transport: Some(&self.block_info),
+            transport_changes: &self.block_changes,
+            ..Default::default()
+        }
+    }
+
+    /// The first loop wrap or tempo map change that playback starting at
+    /// `position` reaches within `remaining` samples, as the samples until
+    /// it and the position at that sample: the wrap or change target plus
+    /// however far past the boundary the whole sample lands. Playback that
+    /// starts past the loop end is not wrapped.
+    fn next_boundary(&self, position: f64, remaining: u32) -> Option<(u32, f64)> {
+        let loop_length = self.loop_end_beats - self.loop_start_beats;
+        let loop_end = (self.loop_active && loop_length > 0.0 && position < self.loop_end_beats)
+            .then_some(self.loop_end_beats);
+        let (target, next) = match (loop_end, self.tempo_map.next_change(position)) {
+            (Some(end), Some(change)) if change < end => (change, change),
+            (Some(end), _) => (end, self.loop_start_beats),
+            (None, Some(change)) => (change, change),
+            (None, None) => return None,
+        };
+        let (samples, past) = self
+            .tempo_map
+            .samples_until(position, target, self.sample_rate);
+        (samples <= remaining).then(|| {
+            let next = self.tempo_map.advance(next, past, self.sample_rate);
+            (samples, next)
+        })
+    }
+
+    fn info_at(&self, position_beats: f64) -> TransportInfo {
+        self.tempo_map
+            .transport_at(position_beats)
+            .with_playing(self.playing)
+            .with_recording(self.recording)
+            .with_loop(self.loop_active, self.loop_start_beats, self.loop_end_beats)
+            .with_loop_seconds(
+                self.tempo_map.beats_to_seconds(self.loop_start_beats),
+                self.tempo_map.beats_to_seconds(self.loop_end_beats),
+            )
+    }
+}
@@ -0,0 +1,312 @@
+//! Tempo and time-signature map.
+
+use crate::types::{TransportChange, TransportInfo};
+
+const MIN_TEMPO: f64 = 1.0;
+const MAX_TEMPO: f64 = 10_000.0;
+
+/// `tempo` clamped to 1-10000 BPM, so beat and time conversions stay finite.
+fn valid_tempo(tempo: f64) -> f64 {
+    if tempo.is_nan() {
+        MIN_TEMPO
+    } else {
+        tempo.clamp(MIN_TEMPO, MAX_TEMPO)
+    }
+}
+
+/// `sample_rate`, or 1 Hz if it isn't a positive number.
+pub(super) fn valid_sample_rate(sample_rate: f64) -> f64 {
+    if sample_rate >= 1.0 && sample_rate.is_finite() {
+        sample_rate
+    } else {
+        1.0
+    }
+}
+
+/// A tempo in effect from `beat` on. `seconds` caches the time at `beat`.
+#[derive(Debug, Clone, Copy)]
+struct TempoPoint {
+    beat: f64,
+    tempo: f64,
+    seconds: f64,
+}
+
+/// A time signature in effect from `beat` on. `bar` caches the bar number
+/// starting at `beat`.
+#[derive(Debug, Clone, Copy)]
+struct MeterPoint {
+    beat: f64,
+    numerator: i32,
+    denominator: i32,
+    bar: i32,
+}
+
+impl MeterPoint {
+    fn bar_length(&self) -> f64 {
+        if self.denominator <= 0 {
+            return 4.0;
+        }
+        self.numerator as f64 * 4.0 / self.denominator as f64
+    }
+}
+
+/// One piece of a block split at tempo and meter changes.
+#[derive(Debug, Clone, Copy)]
+pub struct TempoSegment {
+    pub sample_offset: u32,
+    pub num_samples: u32,
+    /// Transport state at the start of the segment, reported as playing.
+    pub transport: TransportInfo,
+}
+
+/// Tempo and time-signature changes along the beat timeline.
+///
+/// Positions are in quarter-note beats. Tempo changes are steps: a tempo
+/// holds until the next change. Tempos are clamped to 1-10000 BPM. A
+/// time-signature change that falls inside a bar starts a new bar.
+///
+/// ```ignore
+/// let map = TempoMap::new(120.0)
+///     .with_tempo(16.0, 90.0)
+///     .with_time_signature(16.0, 6, 8);
+/// let beats = map.samples_to_beats(block_start, 48000.0);
+/// for segment in map.split_block(beats, 512, 48000.0) {
+///     // Process `segment.num_samples` samples with `segment.transport`
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TempoMap {
+    tempos: Vec<TempoPoint>,
+    meters: Vec<MeterPoint>,
+}
+
+impl TempoMap {
+    /// A map with a constant `tempo` in 4/4.
+    pub fn new(tempo: f64) -> Self {
+        Self {
+            tempos: vec![TempoPoint {
+                beat: 0.0,
+                tempo: valid_tempo(tempo),
+                seconds: 0.0,
+            }],
+            meters: vec![MeterPoint {
+                beat: 0.0,
+                numerator: 4,
+                denominator: 4,
+                bar: 0,
+            }],
+        }
+    }
+
+    pub fn with_tempo(mut self, beat: f64, tempo: f64) -> Self {
+        self.set_tempo(beat, tempo);
+        self
+    }
+
+    pub fn with_time_signature(mut self, beat: f64, numerator: i32, denominator: i32) -> Self {
+        self.set_time_signature(beat, numerator, denominator);
+        self
+    }
+
+    /// Change the tempo from `beat` on, replacing any change at that beat.
+    pub fn set_tempo(&mut self, beat: f64, tempo: f64) {
+        let beat = beat.max(0.0);
+        let tempo = valid_tempo(tempo);
+        match self.tempos.iter().position(|p| p.beat >= beat) {
+            Some(i) if self.tempos[i].beat == beat => self.tempos[i].tempo = tempo,
+            Some(i) => self.tempos.insert(
+                i,
+                TempoPoint {
+                    beat,
+                    tempo,
+                    seconds: 0.0,
+                },
+            ),
+            None => self.tempos.push(TempoPoint {
+                beat,
+                tempo,
+                seconds: 0.0,
+            }),
+        }
+        for i in 1..self.tempos.len() {
+            let prev = self.tempos[i - 1];
+            self.tempos[i].seconds =
+                prev.seconds + (self.tempos[i].beat - prev.beat) * 60.0 / prev.tempo;
+        }
+    }
+
+    /// Change the time signature from `beat` on, replacing any change at
+    /// that beat.
+    pub fn set_time_signature(&mut self, beat: f64, numerator: i32, denominator: i32) {
+        let beat = beat.max(0.0);
+        let point = MeterPoint {
+            beat,
+            numerator,
+            denominator,
+            bar: 0,
+        };
+        match self.meters.iter().position(|p| p.beat >= beat) {
+            Some(i) if self.meters[i].beat == beat => self.meters[i] = point,
+            Some(i) => self.meters.insert(i, point),
+            None => self.meters.push(point),
+        }
+        for i in 1..self.meters.len() {
+            let prev = self.meters[i - 1];
+            let bars = ((self.meters[i].beat - prev.beat) / prev.bar_length()).ceil();
+            self.meters[i].bar = prev.bar + bars as i32;
+        }
+    }
+
+    fn tempo_point(&self, beat: f64) -> &TempoPoint {
+        let i = self.tempos.partition_point(|p| p.beat <= beat);
+        &self.tempos[i.saturating_sub(1)]
+    }
+
+    fn meter_point(&self, beat: f64) -> &MeterPoint {
+        let i = self.meters.partition_point(|p| p.beat <= beat);
+        &self.meters[i.saturating_sub(1)]
+    }
+
+    pub fn tempo_at(&self, beat: f64) -> f64 {
+        self.tempo_point(beat).tempo
+    }
+
+    pub fn time_signature_at(&self, beat: f64) -> (i32, i32) {
+        let meter = self.meter_point(beat);
+        (meter.numerator, meter.denominator)
+    }
+
+    /// Bar number and the beat the bar starts on.
+    pub fn bar_at(&self, beat: f64) -> (i32, f64) {
+        let meter = self.meter_point(beat);
+        let bars = ((beat - meter.beat) / meter.bar_length()).floor();
+        (
+            meter.bar + bars as i32,
+            meter.beat + bars * meter.bar_length(),
+        )
+    }
+
+    pub fn beats_to_seconds(&self, beats: f64) -> f64 {
+        let point = self.tempo_point(beats);
+        point.seconds + (beats - point.beat) * 60.0 / point.tempo
+    }
+
+    pub fn seconds_to_beats(&self, seconds: f64) -> f64 {
+        let i = self.tempos.partition_point(|p| p.seconds <= seconds);
+        let point = &self.tempos[i.saturating_sub(1)];
+        point.beat + (seconds - point.seconds) * point.tempo / 60.0
+    }
+
+    pub fn beats_to_samples(&self, beats: f64, sample_rate: f64) -> f64 {
+        self.beats_to_seconds(beats) * sample_rate
+    }
+
+    pub fn samples_to_beats(&self, samples: f64, sample_rate: f64) -> f64 {
+        self.seconds_to_beats(samples / sample_rate)
+    }
+
+    /// The first tempo or time-signature change strictly after `beat`.
+    pub fn next_change(&self, beat: f64) -> Option<f64> {
+        let tempo = self.tempos.iter().map(|p| p.beat).find(|&b| b > beat);
+        let meter = self.meters.iter().map(|p| p.beat).find(|&b| b > beat);
+        match (tempo, meter) {
+            (Some(t), Some(m)) => Some(t.min(m)),
+            (t, m) => t.or(m),
+        }
+    }
+
+    /// Whole samples from `from` until playback reaches `to`, at least 1,
+    /// and how many samples past `to` playback is after them.
+    pub(super) fn samples_until(&self, from: f64, to: f64, sample_rate: f64) -> (u32, f64) {
+        let exact =
+            self.beats_to_samples(to, sample_rate) - self.beats_to_samples(from, sample_rate);
+        let samples = exact.ceil().max(1.0);
+        (samples as u32, samples - exact)
+    }
+
+    /// The position `samples` after `beat`.
+    pub(super) fn advance(&self, beat: f64, samples: f64, sample_rate: f64) -> f64 {
+        if samples <= 0.0 {
+            return beat;
+        }
+        let position = self.samples_to_beats(
+            self.beats_to_samples(beat, sample_rate) + samples,
+            sample_rate,
+        );
+        position.max(beat)
+    }
+
+    /// Transport state at `beat`, with every field known and playback
+    /// stopped.
+    pub fn transport_at(&self, beat: f64) -> TransportInfo {
+        let (numerator, denominator) = self.time_signature_at(beat);
+        let (bar_number, bar_start) = self.bar_at(beat);
+        TransportInfo::new()
+            .with_tempo(self.tempo_at(beat))
+            .with_time_signature(numerator, denominator)
+            .with_position(beat, self.beats_to_seconds(beat))
+            .with_bar(bar_start, bar_number)
+    }
+
+    /// Split a block of `num_samples` starting at `start_beat` wherever the
+    /// tempo or time signature changes. Each segment starts on the first
+    /// sample at or after its change, with the position at that sample.
+    pub fn split_block(
+        &self,
+        start_beat: f64,
+        num_samples: u32,
+        sample_rate: f64,
+    ) -> Vec<TempoSegment> {
+        let sample_rate = valid_sample_rate(sample_rate);
+        let mut segments = Vec::new();
+        let mut beat = start_beat;
+        let mut offset = 0u32;
+        while offset < num_samples {
+            let remaining = num_samples - offset;
+            let until_change = self
+                .next_change(beat)
+                .map(|change| (change, self.samples_until(beat, change, sample_rate)));
+            let (length, next_beat) = match until_change {
+                Some((change, (samples, past))) if samples <= remaining => {
+                    (samples, self.advance(change, past, sample_rate))
+                }
+                _ => (remaining, self.advance(beat, remaining as f64, sample_rate)),
+            };
+            segments.push(TempoSegment {
+                sample_offset: offset,
+                num_samples: length,
+                transport: self.transport_at(beat).with_playing(true),
+            });
+            beat = next_beat;
+            offset += length;
+        }
+        segments
+    }
+
+    /// The transport events to send inside a block starting at
+    /// `start_beat`: one at each segment boundary of
+    /// [`split_block`](Self::split_block). Pass them as
+    /// [`ProcessContext::transport_changes`](crate::ProcessContext::transport_changes),
+    /// with the first segment's transport as the block's transport.
+    pub fn transport_changes(
+        &self,
+        start_beat: f64,
+        num_samples: u32,
+        sample_rate: f64,
+    ) -> Vec<TransportChange> {
+        self.split_block(start_beat, num_samples, sample_rate)
+            .into_iter()
+            .skip(1)
+            .map(|segment| TransportChange {
+                sample_offset: segment.sample_offset,
+                transport: segment.transport,
+            })
+            .collect()
+    }
+}
+
+impl Default for TempoMap {
+    fn default() -> Self {
+        Self::new(120.0)
+    }
+}
@@ -3,17 +3,186 @@
 use bitflags::bitflags;
 use smallvec::SmallVec;
 use std::fmt;
+use std::sync::Arc;
 
+/// Audio as flat channel lists. Channels are assigned to the plugin's ports
+/// in order and missing ones are padded with scratch memory; use
+/// [`PortBuffer`] to route ports explicitly.
 pub struct AudioBuffer<'a, T = f32> {
     pub inputs: &'a [&'a [T]],
     pub outputs: &'a mut [&'a mut [T]],
     pub num_samples: usize,
     pub sample_rate: f64,
+    /// Bit N set: input channel N holds the same value for the whole block.
+    /// Passed to the plugin as each port's `constant_mask`.
+    pub input_constant_mask: u64,
+    /// Bit N set: the plugin reported output channel N as constant for the
+    /// block. Written by every process call.
+    pub output_constant_mask: u64,
+    /// Latency in samples from the audio interface to each input port,
+    /// indexed by port. Missing ports are passed as 0.
+    pub input_latencies: &'a [u32],
+}
+
+impl<'a, T> AudioBuffer<'a, T> {
+    /// A buffer with no channels flagged constant.
+    pub fn new(
+        inputs: &'a [&'a [T]],
+        outputs: &'a mut [&'a mut [T]],
+        num_samples: usize,
+        sample_rate: f64,
+    ) -> Self {
+        Self {
+            inputs,
+            outputs,
+            num_samples,
+            sample_rate,
+            input_constant_mask: 0,
+            output_constant_mask: 0,
+            input_latencies: &[],
+        }
+    }
+
+    pub fn with_input_latencies(mut self, latencies: &'a [u32]) -> Self {
+        self.input_latencies = latencies;
+        self
+    }
+
+    pub fn is_input_constant(&self, channel: usize) -> bool {
+        channel < 64 && self.input_constant_mask & (1 << channel) != 0
+    }
+
+    pub fn is_output_constant(&self, channel: usize) -> bool {
+        channel < 64 && self.output_constant_mask & (1 << channel) != 0
+    }
+}
+
+impl<T: Copy + PartialEq> AudioBuffer<'_, T> {
+    /// Scan the inputs and flag every channel whose samples are all equal,
+    /// e.g. silence or DC. Replaces [`input_constant_mask`](Self::input_constant_mask).
+    pub fn detect_constant_inputs(&mut self) {
+        self.input_constant_mask = constant_mask(self.inputs, self.num_samples);
+    }
+}
+
+/// Bit N set for every channel whose first `num_samples` samples are equal.
+fn constant_mask<T: Copy + PartialEq>(channels: &[&[T]], num_samples: usize) -> u64 {
+    let mut mask = 0;
+    for (i, channel) in channels.iter().enumerate().take(64) {
+        let channel = &channel[..num_samples.min(channel.len())];
+        if channel.iter().all(|&s| s == channel[0]) {
+            mask |= 1 << i;
+        }
+    }
+    mask
 }
 
 pub type AudioBuffer32<'a> = AudioBuffer<'a, f32>;
 pub type AudioBuffer64<'a> = AudioBuffer<'a, f64>;
 
+/// Audio laid out by port, matching the plugin's
+/// [`audio_port_info`](crate::ClapInstance::audio_port_info): one entry per
+/// declared port, each with exactly that port's channels. Used by
+/// [`ClapInstance::process_ports`](crate::ClapInstance::process_ports) to
+/// route sidechains, aux outputs and multi-out instruments explicitly.
+pub struct PortBuffer<'a, T = f32> {
+    pub inputs: &'a [InputPort<'a, T>],
+    pub outputs: &'a mut [OutputPort<'a, T>],
+    pub num_samples: usize,
+    pub sample_rate: f64,
+}
+
+impl<'a, T> PortBuffer<'a, T> {
+    pub fn new(
+        inputs: &'a [InputPort<'a, T>],
+        outputs: &'a mut [OutputPort<'a, T>],
+        num_samples: usize,
+        sample_rate: f64,
+    ) -> Self {
+        Self {
+            inputs,
+            outputs,
+            num_samples,
+            sample_rate,
+        }
+    }
+}
+
+/// The channels of one input port.
+pub struct InputPort<'a, T = f32> {
+    pub channels: &'a [&'a [T]],
+    /// Bit N set: channel N holds the same value for the whole block.
+    pub constant_mask: u64,
+    /// Latency in samples from the audio interface to this port.
+    pub latency: u32,
+    /// Set by [`in_place`](Self::in_place); `channels` is then unused.
+    pub in_place: bool,
+}
+
+impl<'a, T> InputPort<'a, T> {
+    pub fn new(channels: &'a [&'a [T]]) -> Self {
+        Self {
+            channels,
+            constant_mask: 0,
+            latency: 0,
+            in_place: false,
+        }
+    }
+
+    /// An input port processed in place: the plugin reads it from the
+    /// channels of its paired output port (see
+    /// [`ClapInstance::in_place_pairs`](crate::ClapInstance::in_place_pairs)),
+    /// which must hold the input audio when the process call is made.
+    pub fn in_place() -> Self {
+        Self {
+            in_place: true,
+            ..Self::new(&[])
+        }
+    }
+
+    pub fn is_in_place(&self) -> bool {
+        self.in_place
+    }
+
+    pub fn with_constant_mask(mut self, mask: u64) -> Self {
+        self.constant_mask = mask;
+        self
+    }
+
+    pub fn with_latency(mut self, latency: u32) -> Self {
+        self.latency = latency;
+        self
+    }
+}
+
+impl<T: Copy + PartialEq> InputPort<'_, T> {
+    /// Flag every channel whose first `num_samples` samples are equal.
+    pub fn detect_constant(mut self, num_samples: usize) -> Self {
+        self.constant_mask = constant_mask(self.channels, num_samples);
+        self
+    }
+}
+
+/// The channels of one output port. `constant_mask` and `latency` are
+/// written back by every process call.
+pub struct OutputPort<'a, T = f32> {
+    pub channels: &'a mut [&'a mut [T]],
+    /// Bit N set: the plugin reported channel N as constant for the block.
+    pub constant_mask: u64,
+    /// Latency in samples the plugin reported for this port.
+    pub latency: u32,
+}
+
+impl<'a, T> OutputPort<'a, T> {
+    pub fn new(channels: &'a mut [&'a mut [T]]) -> Self {
+        Self {
+            channels,
+            constant_mask: 0,
+            latency: 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PluginInfo {
     pub id: String,
@@ -80,18 +249,41 @@ impl fmt::Display for PluginInfo {
     }
 }
 
-#[derive(Debug, Clone, Copy, Default)]
+bitflags! {
+    /// Which [`TransportInfo`] fields hold known values. Only known fields
+    /// are flagged to the plugin with `CLAP_TRANSPORT_HAS_*`.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct TransportFields: u32 {
+        /// `tempo` and `tempo_inc`.
+        const TEMPO = 1 << 0;
+        /// `song_pos_beats`, `loop_*_beats`, `bar_start` and `bar_number`.
+        const BEATS_TIMELINE = 1 << 1;
+        /// `song_pos_seconds` and `loop_*_seconds`.
+        const SECONDS_TIMELINE = 1 << 2;
+        const TIME_SIGNATURE = 1 << 3;
+    }
+}
+
+/// Transport state at the start of a block. [`new`](Self::new) and
+/// `Default` mark every field known; starting from
+/// [`unknown`](Self::unknown), only the fields set through the builders are.
+#[derive(Debug, Clone, Copy)]
 pub struct TransportInfo {
+    pub known: TransportFields,
     pub playing: bool,
     pub recording: bool,
     pub cycle_active: bool,
     pub tempo: f64,
+    /// Tempo change per sample, for tempo ramps.
+    pub tempo_inc: f64,
     pub time_sig_numerator: i32,
     pub time_sig_denominator: i32,
     pub song_pos_beats: f64,
     pub song_pos_seconds: f64,
     pub loop_start_beats: f64,
     pub loop_end_beats: f64,
+    pub loop_start_seconds: f64,
+    pub loop_end_seconds: f64,
     pub bar_start: f64,
     pub bar_number: i32,
 }
@@ -99,15 +291,44 @@ pub struct TransportInfo {
 impl TransportInfo {
     pub fn new() -> Self {
         Self {
+            known: TransportFields::all(),
+            playing: false,
+            recording: false,
+            cycle_active: false,
             tempo: 120.0,
+            tempo_inc: 0.0,
             time_sig_numerator: 4,
             time_sig_denominator: 4,
-            ..Default::default()
+            song_pos_beats: 0.0,
+            song_pos_seconds: 0.0,
+            loop_start_beats: 0.0,
+            loop_end_beats: 0.0,
+            loop_start_seconds: 0.0,
+            loop_end_seconds: 0.0,
+            bar_start: 0.0,
+            bar_number: 0,
+        }
+    }
+
+    /// A transport with no field known, for hosts that only know some of
+    /// them.
+    pub fn unknown() -> Self {
+        Self {
+            known: TransportFields::empty(),
+            ..Self::new()
         }
     }
 
     pub fn with_tempo(mut self, tempo: f64) -> Self {
         self.tempo = tempo;
+        self.known |= TransportFields::TEMPO;
+        self
+    }
+
+    /// Ramp the tempo by `tempo_inc` BPM per sample from the block start.
+    pub fn with_tempo_inc(mut self, tempo_inc: f64) -> Self {
+        self.tempo_inc = tempo_inc;
+        self.known |= TransportFields::TEMPO;
         self
     }
 
@@ -128,33 +349,78 @@ impl TransportInfo {
         self
     }
 
+    /// Loop bounds on the seconds timeline, alongside [`with_loop`](Self::with_loop).
+    pub fn with_loop_seconds(mut self, start: f64, end: f64) -> Self {
+        self.loop_start_seconds = start;
+        self.loop_end_seconds = end;
+        self
+    }
+
     pub fn with_time_signature(mut self, numerator: i32, denominator: i32) -> Self {
         self.time_sig_numerator = numerator;
         self.time_sig_denominator = denominator;
+        self.known |= TransportFields::TIME_SIGNATURE;
         self
     }
 
     pub fn with_position(mut self, beats: f64, seconds: f64) -> Self {
         self.song_pos_beats = beats;
         self.song_pos_seconds = seconds;
+        self.known |= TransportFields::BEATS_TIMELINE | TransportFields::SECONDS_TIMELINE;
+        self
+    }
+
+    pub fn with_beats(mut self, beats: f64) -> Self {
+        self.song_pos_beats = beats;
+        self.known |= TransportFields::BEATS_TIMELINE;
+        self
+    }
+
+    pub fn with_seconds(mut self, seconds: f64) -> Self {
+        self.song_pos_seconds = seconds;
+        self.known |= TransportFields::SECONDS_TIMELINE;
         self
     }
 
     pub fn with_bar(mut self, bar_start: f64, bar_number: i32) -> Self {
         self.bar_start = bar_start;
         self.bar_number = bar_number;
+        self.known |= TransportFields::BEATS_TIMELINE;
         self
     }
 }
 
+impl Default for TransportInfo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A transport change inside a block, sent to the plugin as a
+/// `CLAP_EVENT_TRANSPORT` at `sample_offset`.
 #[derive(Debug, Clone, Copy)]
+pub struct TransportChange {
+    pub sample_offset: u32,
+    pub transport: TransportInfo,
+}
+
+#[derive(Debug, Clone)]
 pub struct MidiEvent {
     pub sample_offset: i32,
     pub channel: u8,
     pub data: MidiData,
+    /// CLAP note ID for note on/off, or -1 for none. Lets per-note
+    /// expressions and modulation target this voice.
+    pub note_id: i32,
+    /// Note port (or MIDI port) the event is sent on. On a note event read
+    /// back from the plugin, -1 means any port, as in CLAP.
+    pub port_index: i16,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// `Sysex` holds a complete message, including the `0xF0` and `0xF7`
+/// framing bytes. It is shared, so a patch dump can be resent without
+/// copying.
+#[derive(Debug, Clone)]
 pub enum MidiData {
     NoteOn { key: u8, velocity: f64 },
     NoteOff { key: u8, velocity: f64 },
@@ -163,6 +429,7 @@ pub enum MidiData {
     ProgramChange { program: u8 },
     ChannelPressure { pressure: u8 },
     PitchBend { value: u16 },
+    Sysex(Arc<[u8]>),
 }
 
 impl MidiEvent {
@@ -170,6 +437,8 @@ impl MidiEvent {
         Self {
             sample_offset,
             channel,
+            note_id: -1,
+            port_index: 0,
             data: MidiData::NoteOn {
                 key,
                 velocity: velocity as f64 / 127.0,
@@ -181,6 +450,8 @@ impl MidiEvent {
         Self {
             sample_offset,
             channel,
+            note_id: -1,
+            port_index: 0,
             data: MidiData::NoteOff {
                 key,
                 velocity: velocity as f64 / 127.0,
@@ -192,6 +463,8 @@ impl MidiEvent {
         Self {
             sample_offset,
             channel,
+            note_id: -1,
+            port_index: 0,
             data: MidiData::ControlChange { controller, value },
         }
     }
@@ -200,6 +473,8 @@ impl MidiEvent {
         Self {
             sample_offset,
             channel,
+            note_id: -1,
+            port_index: 0,
             data: MidiData::ProgramChange { program },
         }
     }
@@ -208,9 +483,45 @@ impl MidiEvent {
         Self {
             sample_offset,
             channel,
+            note_id: -1,
+            port_index: 0,
             data: MidiData::PitchBend { value },
         }
     }
+
+    /// A sysex message from owned or borrowed bytes, e.g. a `Vec<u8>` or
+    /// `&[u8]`. The channel is unused.
+    pub fn sysex(sample_offset: i32, data: impl Into<Arc<[u8]>>) -> Self {
+        Self {
+            sample_offset,
+            channel: 0,
+            note_id: -1,
+            port_index: 0,
+            data: MidiData::Sysex(data.into()),
+        }
+    }
+
+    pub fn with_note_id(mut self, note_id: i32) -> Self {
+        self.note_id = note_id;
+        self
+    }
+
+    pub fn with_port_index(mut self, port_index: i16) -> Self {
+        self.port_index = port_index;
+        self
+    }
+}
+
+/// A plugin reporting that a voice finished, from a `CLAP_EVENT_NOTE_END`
+/// output event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteEnd {
+    pub sample_offset: i32,
+    pub port_index: i16,
+    pub channel: i16,
+    pub key: i16,
+    /// The note ID the voice was started with, or -1.
+    pub note_id: i32,
 }
 
 pub trait ClapMidiEvent {
@@ -229,7 +540,7 @@ impl ClapMidiEvent for MidiEvent {
     }
 
     fn to_midi_data(&self) -> Option<MidiData> {
-        Some(self.data)
+        Some(self.data.clone())
     }
 }
 
@@ -289,10 +600,57 @@ impl NoteExpressionValue {
     }
 }
 
+/// A parameter value at a sample offset. Note ID, port, channel and key of
+/// -1 apply it to the whole parameter; otherwise it only changes the value
+/// for matching voices, for plugins with per-note automation
+/// (`CLAP_PARAM_IS_AUTOMATABLE_PER_NOTE_ID`, `_PER_KEY`, ...).
 #[derive(Debug, Clone, Copy)]
 pub struct ParameterPoint {
     pub sample_offset: i32,
     pub value: f64,
+    pub note_id: i32,
+    pub port_index: i16,
+    pub channel: i16,
+    pub key: i16,
+}
+
+impl ParameterPoint {
+    pub fn new(sample_offset: i32, value: f64) -> Self {
+        Self {
+            sample_offset,
+            value,
+            note_id: -1,
+            port_index: -1,
+            channel: -1,
+            key: -1,
+        }
+    }
+
+    pub fn for_note(mut self, note_id: i32) -> Self {
+        self.note_id = note_id;
+        self
+    }
+
+    pub fn port(mut self, port_index: i16) -> Self {
+        self.port_index = port_index;
+        self
+    }
+
+    pub fn on_channel(mut self, channel: i16) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    pub fn on_key(mut self, key: i16) -> Self {
+        self.key = key;
+        self
+    }
+
+    /// Whether the point targets specific voices rather than the whole
+    /// parameter.
+    pub fn is_per_note(&self) -> bool {
+        self.note_id >= 0 || self.port_index >= 0 || self.channel >= 0 || self.key >= 0
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -310,10 +668,14 @@ impl ParameterQueue {
     }
 
     pub fn add_point(&mut self, sample_offset: i32, value: f64) -> &mut Self {
-        self.points.push(ParameterPoint {
-            sample_offset,
-            value,
-        });
+        self.points.push(ParameterPoint::new(sample_offset, value));
+        self
+    }
+
+    /// Add a point built with [`ParameterPoint::new`], e.g. one targeting a
+    /// single note.
+    pub fn push_point(&mut self, point: ParameterPoint) -> &mut Self {
+        self.points.push(point);
         self
     }
 }
@@ -338,6 +700,91 @@ impl ParameterChanges {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct ModulationPoint {
+    pub sample_offset: i32,
+    /// Offset the plugin adds to the parameter's value, in the parameter's
+    /// plain units.
+    pub amount: f64,
+}
+
+/// Modulation of one parameter, sent as `CLAP_EVENT_PARAM_MOD` events. The
+/// plugin applies it on top of the parameter's value without changing the
+/// value itself. Note ID, port, channel and key of -1 match all voices.
+#[derive(Debug, Clone)]
+pub struct ModulationQueue {
+    pub param_id: u32,
+    pub note_id: i32,
+    pub port_index: i16,
+    pub channel: i16,
+    pub key: i16,
+    pub points: SmallVec<[ModulationPoint; 8]>,
+}
+
+impl ModulationQueue {
+    /// Modulation applying to every voice.
+    pub fn new(param_id: u32) -> Self {
+        Self {
+            param_id,
+            note_id: -1,
+            port_index: -1,
+            channel: -1,
+            key: -1,
+            points: SmallVec::new(),
+        }
+    }
+
+    pub fn for_note(mut self, note_id: i32) -> Self {
+        self.note_id = note_id;
+        self
+    }
+
+    pub fn port(mut self, port_index: i16) -> Self {
+        self.port_index = port_index;
+        self
+    }
+
+    pub fn on_channel(mut self, channel: i16) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    pub fn on_key(mut self, key: i16) -> Self {
+        self.key = key;
+        self
+    }
+
+    pub fn add_point(&mut self, sample_offset: i32, amount: f64) -> &mut Self {
+        self.points.push(ModulationPoint {
+            sample_offset,
+            amount,
+        });
+        self
+    }
+}
+
+/// Parameter modulation for one process call, alongside the absolute
+/// values in [`ParameterChanges`].
+#[derive(Debug, Clone, Default)]
+pub struct ParameterModulations {
+    pub queues: SmallVec<[ModulationQueue; 16]>,
+}
+
+impl ParameterModulations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_queue(&mut self, queue: ModulationQueue) -> &mut Self {
+        self.queues.push(queue);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queues.is_empty()
+    }
+}
+
 bitflags! {
     #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
     pub struct ParameterFlags: u32 {
@@ -482,6 +929,38 @@ pub struct NoteName {
     pub key: i16,
 }
 
+/// What the plugin asked of the host after a successful process call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProcessStatus {
+    /// Keep calling process.
+    #[default]
+    Continue,
+    /// Keep calling process while the output is not quiet.
+    ContinueIfNotQuiet,
+    /// Keep calling process for the plugin's tail length once input is quiet.
+    Tail,
+    /// No processing needed until the next event or non-silent input.
+    Sleep,
+}
+
+impl ProcessStatus {
+    /// Convert a `clap_process_status`. Returns `None` for
+    /// `CLAP_PROCESS_ERROR` and unknown values.
+    pub fn from_raw(status: clap_sys::process::clap_process_status) -> Option<Self> {
+        use clap_sys::process::{
+            CLAP_PROCESS_CONTINUE, CLAP_PROCESS_CONTINUE_IF_NOT_QUIET, CLAP_PROCESS_SLEEP,
+            CLAP_PROCESS_TAIL,
+        };
+        match status {
+            CLAP_PROCESS_CONTINUE => Some(Self::Continue),
+            CLAP_PROCESS_CONTINUE_IF_NOT_QUIET => Some(Self::ContinueIfNotQuiet),
+            CLAP_PROCESS_TAIL => Some(Self::Tail),
+            CLAP_PROCESS_SLEEP => Some(Self::Sleep),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum StateContext {
     ForPreset,
@@ -503,6 +982,129 @@ impl From<StateContext> for clap_sys::ext::state_context::clap_plugin_state_cont
     }
 }
 
+bitflags! {
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct PresetFlags: u32 {
+        const FACTORY_CONTENT = 1 << 0;
+        const USER_CONTENT    = 1 << 1;
+        const DEMO_CONTENT    = 1 << 2;
+        const FAVORITE        = 1 << 3;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetLocationKind {
+    /// A file or directory on disk.
+    File,
+    /// Presets bundled inside the plugin itself.
+    Plugin,
+}
+
+impl From<PresetLocationKind>
+    for clap_sys::factory::preset_discovery::clap_preset_discovery_location_kind
+{
+    fn from(kind: PresetLocationKind) -> Self {
+        match kind {
+            PresetLocationKind::File => {
+                clap_sys::factory::preset_discovery::CLAP_PRESET_DISCOVERY_LOCATION_FILE
+            }
+            PresetLocationKind::Plugin => {
+                clap_sys::factory::preset_discovery::CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN
+            }
+        }
+    }
+}
+
+/// Descriptor of a provider in a bundle's preset-discovery factory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresetProviderInfo {
+    pub id: String,
+    pub name: String,
+    pub vendor: String,
+}
+
+/// A location declared by a preset-discovery provider.
+#[derive(Debug, Clone)]
+pub struct PresetLocation {
+    pub flags: PresetFlags,
+    pub name: String,
+    pub kind: PresetLocationKind,
+    /// File or directory path. `None` for [`PresetLocationKind::Plugin`].
+    pub location: Option<String>,
+}
+
+/// A preset file type declared by a preset-discovery provider.
+#[derive(Debug, Clone)]
+pub struct PresetFiletype {
+    pub name: String,
+    pub description: String,
+    /// Extension without the leading dot. Empty matches any file.
+    pub file_extension: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Soundpack {
+    pub flags: PresetFlags,
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub homepage_url: String,
+    pub vendor: String,
+    pub image_path: String,
+    /// Seconds since the Unix epoch, if known.
+    pub release_timestamp: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PresetPluginId {
+    /// Plugin ABI, e.g. `"clap"`.
+    pub abi: String,
+    pub id: String,
+}
+
+/// A preset found by crawling a provider's locations.
+#[derive(Debug, Clone)]
+pub struct PresetInfo {
+    pub name: String,
+    pub location_kind: PresetLocationKind,
+    /// Path of the file holding the preset. `None` for presets bundled
+    /// inside the plugin.
+    pub location: Option<String>,
+    /// Identifies the preset within a container file or the plugin.
+    pub load_key: Option<String>,
+    pub plugin_ids: Vec<PresetPluginId>,
+    pub soundpack_id: Option<String>,
+    pub flags: PresetFlags,
+    pub creators: Vec<String>,
+    pub description: Option<String>,
+    /// Seconds since the Unix epoch, if known.
+    pub creation_time: Option<u64>,
+    /// Seconds since the Unix epoch, if known.
+    pub modification_time: Option<u64>,
+    pub features: Vec<String>,
+    pub extra_info: Vec<(String, String)>,
+}
+
+impl PresetInfo {
+    pub fn new(name: impl Into<String>, location_kind: PresetLocationKind) -> Self {
+        Self {
+            name: name.into(),
+            location_kind,
+            location: None,
+            load_key: None,
+            plugin_ids: Vec::new(),
+            soundpack_id: None,
+            flags: PresetFlags::default(),
+            creators: Vec::new(),
+            description: None,
+            creation_time: None,
+            modification_time: None,
+            features: Vec::new(),
+            extra_info: Vec::new(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Color {
     pub alpha: u8,
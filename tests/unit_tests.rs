@@ -1,4 +1,5 @@
 use std::ffi::c_void;
+use std::path::Path;
 
 use clap_host::{
     ClapEvent, ClapHost, EventList, HostState, InputEventList, InputStream, MidiData, MidiEvent,
@@ -209,6 +210,50 @@ fn test_input_event_list_sort_by_time() {
     assert_eq!(events[2].header().time, 200);
 }
 
+#[test]
+fn test_input_event_list_sort_by_time_is_stable() {
+    let mut list = InputEventList::with_capacity(8);
+    list.add_midi(&MidiEvent::note_on(100, 0, 60, 100));
+    list.add_midi(&MidiEvent::note_off(50, 0, 62, 0));
+    list.add_midi(&MidiEvent::note_on(50, 0, 62, 90));
+    list.add_midi(&MidiEvent::note_on(0, 0, 64, 80));
+    list.sort_by_time();
+
+    let events = list.events();
+    let times: Vec<u32> = events.iter().map(|e| e.header().time).collect();
+    assert_eq!(times, vec![0, 50, 50, 100]);
+    // Note-off stays ahead of the note-on at the same time
+    assert!(matches!(events[1], ClapEvent::NoteOff(_)));
+    assert!(matches!(events[2], ClapEvent::NoteOn(_)));
+}
+
+#[test]
+fn test_input_event_list_sort_by_time_merges_runs() {
+    // One run per event type, as the host appends them
+    let runs: [&[u32]; 3] = [&[10, 40, 40, 90], &[0, 40, 60], &[5, 40, 95]];
+    let mut list = InputEventList::new();
+    let mut expected = Vec::new();
+    for (run, times) in runs.iter().enumerate() {
+        for (i, &time) in times.iter().enumerate() {
+            let key = (run * 10 + i) as u8;
+            list.add_midi(&MidiEvent::note_on(time as i32, 0, key, 100));
+            expected.push((time, key as i16));
+        }
+    }
+    expected.sort_by_key(|&(time, _)| time);
+    list.sort_by_time();
+
+    let sorted: Vec<(u32, i16)> = list
+        .events()
+        .iter()
+        .map(|e| match e {
+            ClapEvent::NoteOn(n) => (n.header.time, n.key),
+            _ => unreachable!(),
+        })
+        .collect();
+    assert_eq!(sorted, expected);
+}
+
 // ── Parameter changes grouping ──
 
 #[test]
@@ -1043,7 +1088,7 @@ fn test_output_list_push_midi_sysex() {
         ClapEvent::MidiSysex { inner, _data } => {
             assert_eq!(inner.header.time, 25);
             assert_eq!(inner.port_index, 0);
-            assert_eq!(_data, &sysex_data);
+            assert_eq!(&_data[..], &sysex_data[..]);
         }
         _ => panic!("Expected MidiSysex"),
     }
@@ -2078,6 +2123,74 @@ fn test_host_preset_load_on_error_doesnt_panic() {
     }
 }
 
+#[test]
+fn test_host_preset_load_on_error_records_message() {
+    use clap_sys::ext::preset_load::{clap_host_preset_load, CLAP_EXT_PRESET_LOAD};
+    use std::sync::Arc;
+
+    let state = Arc::new(HostState::new());
+    let host = ClapHost::new(state.clone());
+    let raw = host.as_raw();
+    let get_ext = unsafe { (*raw).get_extension.unwrap() };
+    let pl_ptr = unsafe { get_ext(raw, CLAP_EXT_PRESET_LOAD.as_ptr()) };
+    let pl = unsafe { &*(pl_ptr as *const clap_host_preset_load) };
+
+    let location = c"/path/to/bank.fxb";
+    let load_key = c"12";
+    let msg = c"Preset not found in bank";
+    unsafe {
+        pl.on_error.unwrap()(
+            raw,
+            0,
+            location.as_ptr(),
+            load_key.as_ptr(),
+            0,
+            msg.as_ptr(),
+        );
+    }
+    assert_eq!(
+        state.processing.preset_error.lock().unwrap().as_deref(),
+        Some("Preset not found in bank")
+    );
+
+    unsafe {
+        pl.on_error.unwrap()(raw, 0, location.as_ptr(), std::ptr::null(), 2, msg.as_ptr());
+    }
+    assert_eq!(
+        state.processing.preset_error.lock().unwrap().as_deref(),
+        Some("Preset not found in bank (os error 2)")
+    );
+}
+
+#[test]
+fn test_host_preset_load_on_error_null_message() {
+    use clap_sys::ext::preset_load::{clap_host_preset_load, CLAP_EXT_PRESET_LOAD};
+    use std::sync::Arc;
+
+    let state = Arc::new(HostState::new());
+    let host = ClapHost::new(state.clone());
+    let raw = host.as_raw();
+    let get_ext = unsafe { (*raw).get_extension.unwrap() };
+    let pl_ptr = unsafe { get_ext(raw, CLAP_EXT_PRESET_LOAD.as_ptr()) };
+    let pl = unsafe { &*(pl_ptr as *const clap_host_preset_load) };
+
+    let location = c"/path/to/preset.clap";
+    unsafe {
+        pl.on_error.unwrap()(
+            raw,
+            0,
+            location.as_ptr(),
+            std::ptr::null(),
+            0,
+            std::ptr::null(),
+        );
+    }
+    assert_eq!(
+        state.processing.preset_error.lock().unwrap().as_deref(),
+        Some("Failed to load preset /path/to/preset.clap")
+    );
+}
+
 // ── ParamMod event FFI ──
 
 #[test]
@@ -2123,3 +2236,1909 @@ fn test_param_mod_event_ffi() {
         _ => panic!("Expected ParamMod"),
     }
 }
+
+// ── Loading plugins from multi-plugin bundles ──
+
+#[test]
+fn test_load_with_id_missing_file_fails_at_opening() {
+    use clap_host::{ClapError, ClapInstance, LoadStage};
+
+    let result = ClapInstance::load_with_id(
+        "/nonexistent/plugin.clap",
+        "com.example.synth",
+        44100.0,
+        512,
+    );
+    match result {
+        Err(ClapError::LoadFailed { stage, .. }) => assert_eq!(stage, LoadStage::Opening),
+        _ => panic!("Expected LoadFailed at Opening"),
+    }
+}
+
+#[test]
+fn test_load_with_index_missing_file_fails_at_opening() {
+    use clap_host::{ClapError, ClapInstance, LoadStage};
+
+    let result = ClapInstance::load_with_index("/nonexistent/plugin.clap", 1, 44100.0, 512);
+    match result {
+        Err(ClapError::LoadFailed { stage, .. }) => assert_eq!(stage, LoadStage::Opening),
+        _ => panic!("Expected LoadFailed at Opening"),
+    }
+}
+
+#[test]
+fn test_descriptors_of_non_library_fails_at_opening() {
+    use clap_host::{ClapError, ClapInstance, LoadStage};
+    use std::io::Write;
+
+    let mut file = tempfile::Builder::new().suffix(".clap").tempfile().unwrap();
+    file.write_all(b"not a shared library").unwrap();
+
+    match ClapInstance::descriptors(file.path()) {
+        Err(ClapError::LoadFailed { path, stage, .. }) => {
+            assert_eq!(path, file.path());
+            assert_eq!(stage, LoadStage::Opening);
+        }
+        _ => panic!("Expected LoadFailed at Opening"),
+    }
+}
+
+// ── Bundle scanning ──
+
+#[test]
+fn test_find_bundles_recurses_and_skips_other_files() {
+    use clap_host::scan::find_bundles;
+
+    let dir = tempfile::tempdir().unwrap();
+    let nested = dir.path().join("Vendor");
+    std::fs::create_dir(&nested).unwrap();
+    std::fs::write(dir.path().join("a.clap"), b"").unwrap();
+    std::fs::write(nested.join("b.CLAP"), b"").unwrap();
+    std::fs::write(dir.path().join("readme.txt"), b"").unwrap();
+
+    let bundles = find_bundles(&[dir.path(), Path::new("/nonexistent/clap/dir")]);
+    assert_eq!(bundles.len(), 2);
+    assert!(bundles.contains(&dir.path().join("a.clap")));
+    assert!(bundles.contains(&nested.join("b.CLAP")));
+}
+
+#[test]
+fn test_find_bundles_does_not_descend_into_bundle_dirs() {
+    use clap_host::scan::find_bundles;
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = dir.path().join("Synth.clap");
+    std::fs::create_dir_all(bundle.join("Contents/MacOS")).unwrap();
+    std::fs::write(bundle.join("Contents/MacOS/inner.clap"), b"").unwrap();
+
+    assert_eq!(find_bundles(&[dir.path()]), vec![bundle]);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_find_bundles_survives_symlink_cycle() {
+    use clap_host::scan::find_bundles;
+
+    let dir = tempfile::tempdir().unwrap();
+    let nested = dir.path().join("Vendor");
+    std::fs::create_dir(&nested).unwrap();
+    std::fs::write(nested.join("synth.clap"), b"").unwrap();
+    std::os::unix::fs::symlink(dir.path(), nested.join("loop")).unwrap();
+
+    assert_eq!(find_bundles(&[dir.path()]), vec![nested.join("synth.clap")]);
+}
+
+#[test]
+fn test_scan_paths_reports_failures_without_aborting() {
+    use clap_host::scan::scan_paths;
+    use clap_host::LoadStage;
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("broken1.clap"), b"garbage").unwrap();
+    std::fs::write(dir.path().join("broken2.clap"), b"garbage").unwrap();
+
+    let report = scan_paths(&[dir.path()]);
+    assert!(report.plugins.is_empty());
+    assert_eq!(report.failures.len(), 2);
+    for failure in &report.failures {
+        assert_eq!(failure.stage, LoadStage::Opening);
+        assert!(failure.to_string().contains("opening"));
+    }
+}
+
+// ── Out-of-process scanning ──
+
+#[test]
+fn test_isolated_scan_reports_bad_bundle_at_opening() {
+    use clap_host::scan::IsolatedScanner;
+    use clap_host::LoadStage;
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = dir.path().join("broken.clap");
+    std::fs::write(&bundle, b"garbage").unwrap();
+
+    let scanner = IsolatedScanner::new(env!("CARGO_BIN_EXE_clap-scan-helper"));
+    let failure = scanner.scan_bundle(&bundle).unwrap_err();
+    assert_eq!(failure.path, bundle);
+    assert_eq!(failure.stage, LoadStage::Opening);
+    assert!(failure.reason.contains("Failed to load library"));
+}
+
+#[test]
+fn test_isolated_scan_missing_helper_fails() {
+    use clap_host::scan::IsolatedScanner;
+
+    let scanner = IsolatedScanner::new("/nonexistent/clap-scan-helper");
+    let failure = scanner.scan_bundle("/some/plugin.clap").unwrap_err();
+    assert!(failure.reason.contains("Failed to spawn"));
+    assert!(failure.transient);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_isolated_scan_detects_crashed_child() {
+    use clap_host::scan::IsolatedScanner;
+    use clap_host::LoadStage;
+    use std::os::unix::fs::PermissionsExt;
+
+    // A fake helper that gets as far as the factory stage, then dies.
+    let dir = tempfile::tempdir().unwrap();
+    let helper = dir.path().join("helper.sh");
+    std::fs::write(
+        &helper,
+        "#!/bin/sh\nprintf 'stage\\topening\\nstage\\tfactory\\n'\nkill -SEGV $$\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let failure = IsolatedScanner::new(&helper)
+        .scan_bundle("/some/plugin.clap")
+        .unwrap_err();
+    assert_eq!(failure.stage, LoadStage::Factory);
+    assert!(failure.reason.contains("crashed"));
+    assert!(!failure.transient);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_isolated_scan_kills_hung_child() {
+    use clap_host::scan::IsolatedScanner;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, Instant};
+
+    let dir = tempfile::tempdir().unwrap();
+    let helper = dir.path().join("helper.sh");
+    std::fs::write(&helper, "#!/bin/sh\nexec sleep 30\n").unwrap();
+    std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let start = Instant::now();
+    let failure = IsolatedScanner::new(&helper)
+        .timeout(Duration::from_millis(200))
+        .scan_bundle("/some/plugin.clap")
+        .unwrap_err();
+    assert!(failure.reason.contains("timed out"));
+    assert!(failure.transient);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_isolated_scan_kills_child_hung_after_closing_stdout() {
+    use clap_host::scan::IsolatedScanner;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, Instant};
+
+    // Reports a finished scan, then hangs as if stuck in deinit
+    let dir = tempfile::tempdir().unwrap();
+    let helper = dir.path().join("helper.sh");
+    std::fs::write(
+        &helper,
+        "#!/bin/sh\nprintf 'done\\n'\nexec >&-\nexec sleep 30\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let start = Instant::now();
+    let failure = IsolatedScanner::new(&helper)
+        .timeout(Duration::from_millis(200))
+        .scan_bundle("/some/plugin.clap")
+        .unwrap_err();
+    assert!(
+        failure.reason.contains("did not exit"),
+        "{}",
+        failure.reason
+    );
+    assert!(!failure.transient);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_isolated_scan_collects_invalidation_sources() {
+    use clap_host::scan::IsolatedScanner;
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = dir.path().join("scripts.clap");
+    std::fs::write(&bundle, b"").unwrap();
+    let helper = dir.path().join("helper.sh");
+    std::fs::write(
+        &helper,
+        concat!(
+            "#!/bin/sh\n",
+            "printf 'plugin\\t0\\nid\\tcom.example.script\\nend\\t\\n'\n",
+            "printf 'source\\t/home/user/scripts\\nglob\\t*.lua\\nrecursive\\t1\\n'\n",
+            "printf 'done\\t\\n'\n",
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&helper, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let report = IsolatedScanner::new(&helper).scan_paths(&[dir.path()]);
+    assert_eq!(report.plugins.len(), 1);
+    assert_eq!(report.invalidation_sources.len(), 1);
+    let source = &report.invalidation_sources[0];
+    assert_eq!(source.bundle, bundle);
+    assert_eq!(source.directory, Path::new("/home/user/scripts"));
+    assert_eq!(source.filename_glob, "*.lua");
+    assert!(source.recursive);
+}
+
+// ── Scan cache ──
+
+fn fake_scan(path: &Path) -> Result<Vec<clap_host::ScannedPlugin>, clap_host::ScanFailure> {
+    use clap_host::{LoadStage, PluginInfo, ScanFailure, ScannedPlugin};
+
+    if path.to_string_lossy().contains("broken") {
+        return Err(ScanFailure {
+            path: path.to_path_buf(),
+            stage: LoadStage::Factory,
+            reason: "No plugin factory".to_string(),
+            transient: false,
+        });
+    }
+    if path.to_string_lossy().contains("busy") {
+        return Err(ScanFailure {
+            path: path.to_path_buf(),
+            stage: LoadStage::Opening,
+            reason: "Scanner timed out after 10s".to_string(),
+            transient: true,
+        });
+    }
+    Ok(vec![ScannedPlugin {
+        path: path.to_path_buf(),
+        index: 0,
+        info: PluginInfo::new("com.example.synth", "Synth")
+            .vendor("Example")
+            .features(vec!["instrument".to_string()]),
+        audio_io: None,
+    }])
+}
+
+#[test]
+fn test_scan_cache_skips_unchanged_bundles() {
+    use clap_host::scan::ScanCache;
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("synth.clap"), b"v1").unwrap();
+
+    let mut cache = ScanCache::new();
+    let mut calls = 0;
+    let report = cache.scan_paths(&[dir.path()], |p| {
+        calls += 1;
+        fake_scan(p)
+    });
+    assert_eq!(report.plugins.len(), 1);
+    assert_eq!(calls, 1);
+
+    let report = cache.scan_paths(&[dir.path()], |p| {
+        calls += 1;
+        fake_scan(p)
+    });
+    assert_eq!(report.plugins.len(), 1);
+    assert_eq!(calls, 1);
+}
+
+#[test]
+fn test_scan_cache_invalidates_changed_bundle() {
+    use clap_host::scan::ScanCache;
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = dir.path().join("synth.clap");
+    std::fs::write(&bundle, b"v1").unwrap();
+
+    let mut cache = ScanCache::new();
+    cache.insert(&bundle, fake_scan(&bundle));
+    assert!(cache.get(&bundle).is_some());
+
+    std::fs::write(&bundle, b"version two").unwrap();
+    assert!(cache.get(&bundle).is_none());
+}
+
+#[test]
+fn test_scan_cache_save_load_roundtrip() {
+    use clap_host::scan::ScanCache;
+    use clap_host::LoadStage;
+
+    let dir = tempfile::tempdir().unwrap();
+    let good = dir.path().join("synth.clap");
+    let bad = dir.path().join("broken.clap");
+    std::fs::write(&good, b"v1").unwrap();
+    std::fs::write(&bad, b"v1").unwrap();
+
+    let mut cache = ScanCache::new();
+    cache.insert(&good, fake_scan(&good));
+    cache.insert(&bad, fake_scan(&bad));
+
+    let cache_file = dir.path().join("scan-cache.txt");
+    cache.save(&cache_file).unwrap();
+    let loaded = ScanCache::load(&cache_file).unwrap();
+    assert_eq!(loaded.len(), 2);
+
+    let plugins = loaded.get(&good).unwrap().as_ref().unwrap();
+    assert_eq!(plugins[0].info.id, "com.example.synth");
+    assert_eq!(plugins[0].info.vendor, "Example");
+    assert_eq!(plugins[0].info.features, vec!["instrument".to_string()]);
+    assert_eq!(plugins[0].audio_io, None);
+
+    assert!(loaded.is_blacklisted(&bad));
+    let failure = loaded.blacklist().next().unwrap();
+    assert_eq!(failure.stage, LoadStage::Factory);
+    assert_eq!(failure.reason, "No plugin factory");
+}
+
+#[test]
+fn test_scan_cache_audio_io_only_saved_once_known() {
+    use clap_host::scan::ScanCache;
+
+    let dir = tempfile::tempdir().unwrap();
+    let good = dir.path().join("synth.clap");
+    let bad = dir.path().join("broken.clap");
+    std::fs::write(&good, b"v1").unwrap();
+    std::fs::write(&bad, b"v1").unwrap();
+
+    let mut cache = ScanCache::new();
+    cache.insert(&good, fake_scan(&good));
+    cache.insert(&bad, fake_scan(&bad));
+
+    let cache_file = dir.path().join("scan-cache.txt");
+    cache.save(&cache_file).unwrap();
+    assert!(!std::fs::read_to_string(&cache_file)
+        .unwrap()
+        .contains("audio_io"));
+
+    assert!(!cache.set_audio_io(&bad, 0, 0, 2));
+    assert!(!cache.set_audio_io(&good, 1, 0, 2));
+    assert!(cache.set_audio_io(&good, 0, 0, 2));
+    cache.save(&cache_file).unwrap();
+
+    let loaded = ScanCache::load(&cache_file).unwrap();
+    let plugins = loaded.get(&good).unwrap().as_ref().unwrap();
+    assert_eq!(plugins[0].audio_io, Some((0, 2)));
+    assert_eq!(plugins[0].info.audio_inputs, 0);
+    assert_eq!(plugins[0].info.audio_outputs, 2);
+}
+
+#[test]
+fn test_scan_cache_blacklisted_bundle_not_rescanned() {
+    use clap_host::scan::ScanCache;
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("broken.clap"), b"v1").unwrap();
+
+    let mut cache = ScanCache::new();
+    let mut calls = 0;
+    for _ in 0..2 {
+        let report = cache.scan_paths(&[dir.path()], |p| {
+            calls += 1;
+            fake_scan(p)
+        });
+        assert_eq!(report.failures.len(), 1);
+    }
+    assert_eq!(calls, 1);
+
+    cache.clear_blacklist();
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn test_scan_cache_retries_transient_failures() {
+    use clap_host::scan::ScanCache;
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("busy.clap"), b"v1").unwrap();
+
+    let mut cache = ScanCache::new();
+    let mut calls = 0;
+    for _ in 0..2 {
+        let report = cache.scan_paths(&[dir.path()], |p| {
+            calls += 1;
+            fake_scan(p)
+        });
+        assert_eq!(report.failures.len(), 1);
+        assert!(report.failures[0].transient);
+    }
+    assert_eq!(calls, 2);
+    assert!(cache.is_empty());
+    assert!(!cache.is_blacklisted(dir.path().join("busy.clap")));
+}
+
+#[test]
+fn test_scan_cache_save_replaces_file_without_leftovers() {
+    use clap_host::scan::ScanCache;
+
+    let dir = tempfile::tempdir().unwrap();
+    let good = dir.path().join("synth.clap");
+    std::fs::write(&good, b"v1").unwrap();
+    let cache_file = dir.path().join("scan-cache.txt");
+    std::fs::write(&cache_file, b"stale contents").unwrap();
+
+    let mut cache = ScanCache::new();
+    cache.insert(&good, fake_scan(&good));
+    cache.save(&cache_file).unwrap();
+
+    assert_eq!(ScanCache::load(&cache_file).unwrap().len(), 1);
+    let mut files: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    files.sort();
+    assert_eq!(files, vec!["scan-cache.txt", "synth.clap"]);
+}
+
+#[test]
+fn test_scan_cache_load_missing_file_is_empty() {
+    use clap_host::scan::ScanCache;
+
+    let cache = ScanCache::load("/nonexistent/scan-cache.txt").unwrap();
+    assert!(cache.is_empty());
+}
+
+// ── Preset discovery ──
+
+#[test]
+fn test_preset_discovery_open_missing_bundle() {
+    use clap_host::{ClapError, LoadStage, PresetDiscovery};
+
+    match PresetDiscovery::open("/nonexistent/plugin.clap") {
+        Err(ClapError::LoadFailed { stage, .. }) => assert_eq!(stage, LoadStage::Opening),
+        _ => panic!("Expected LoadFailed at Opening"),
+    }
+}
+
+#[test]
+fn test_preset_flags_match_clap_constants() {
+    use clap_host::PresetFlags;
+    use clap_sys::factory::preset_discovery::*;
+
+    assert_eq!(
+        PresetFlags::FACTORY_CONTENT.bits(),
+        CLAP_PRESET_DISCOVERY_IS_FACTORY_CONTENT
+    );
+    assert_eq!(
+        PresetFlags::USER_CONTENT.bits(),
+        CLAP_PRESET_DISCOVERY_IS_USER_CONTENT
+    );
+    assert_eq!(
+        PresetFlags::DEMO_CONTENT.bits(),
+        CLAP_PRESET_DISCOVERY_IS_DEMO_CONTENT
+    );
+    assert_eq!(
+        PresetFlags::FAVORITE.bits(),
+        CLAP_PRESET_DISCOVERY_IS_FAVORITE
+    );
+}
+
+#[test]
+fn test_preset_location_kind_to_clap() {
+    use clap_host::PresetLocationKind;
+    use clap_sys::factory::preset_discovery::*;
+
+    let file: clap_preset_discovery_location_kind = PresetLocationKind::File.into();
+    let plugin: clap_preset_discovery_location_kind = PresetLocationKind::Plugin.into();
+    assert_eq!(file, CLAP_PRESET_DISCOVERY_LOCATION_FILE);
+    assert_eq!(plugin, CLAP_PRESET_DISCOVERY_LOCATION_PLUGIN);
+}
+
+// ── Test plugin fixture ──
+
+/// Copy the `test_plugin` example, a stereo pass-through plugin, into `dir`
+/// as a `.clap` bundle. Each copy is loaded as a separate library.
+fn test_plugin(dir: &Path) -> std::path::PathBuf {
+    let examples = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .join("examples");
+    let library = examples.join(format!(
+        "{}test_plugin{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    ));
+    assert!(
+        library.is_file(),
+        "{} is missing; build it with `cargo test` or `cargo build --examples`",
+        library.display()
+    );
+    let bundle = dir.join("test_plugin.clap");
+    std::fs::copy(&library, &bundle).unwrap();
+    bundle
+}
+
+#[test]
+fn test_plugin_fixture_loads() {
+    use clap_host::ClapInstance;
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = test_plugin(dir.path());
+    let plugin = ClapInstance::load(&bundle, 48000.0, 512).unwrap();
+    assert_eq!(plugin.info().id, "com.clap-host.test-plugin");
+    assert_eq!(plugin.info().audio_inputs, 2);
+    assert_eq!(plugin.info().audio_outputs, 2);
+}
+
+#[test]
+fn test_process_with_sink_delivers_plugin_events() {
+    use clap_host::{AudioBuffer, ClapInstance, ProcessContext, ProcessStatus};
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut plugin = ClapInstance::load(test_plugin(dir.path()), 48000.0, 64).unwrap();
+    plugin.activate().unwrap();
+
+    let left: Vec<f32> = (0..64).map(|i| i as f32).collect();
+    let right = vec![0.5f32; 64];
+    let inputs: [&[f32]; 2] = [&left, &right];
+    let (mut out_l, mut out_r) = (vec![0.0f32; 64], vec![0.0f32; 64]);
+    let mut outputs: [&mut [f32]; 2] = [&mut out_l, &mut out_r];
+    let mut buffer = AudioBuffer::new(&inputs, &mut outputs, 64, 48000.0);
+    let midi = [
+        MidiEvent::note_on(0, 0, 60, 100).with_note_id(7),
+        MidiEvent::note_off(40, 0, 60, 0).with_note_id(7),
+    ];
+
+    // Record what the sink sees as (time, kind, id, value)
+    let mut events = Vec::new();
+    let status = plugin
+        .process_with_sink(
+            &mut buffer,
+            &ProcessContext {
+                midi: &midi,
+                ..Default::default()
+            },
+            &mut |event: &ClapEvent| {
+                events.push(match event {
+                    ClapEvent::ParamValue(e) => {
+                        (e.header.time, "param", e.param_id as i32, e.value)
+                    }
+                    ClapEvent::NoteEnd(e) => (e.header.time, "end", e.note_id, e.key as f64),
+                    _ => (event.header().time, "other", 0, 0.0),
+                })
+            },
+        )
+        .unwrap();
+    assert_eq!(status, ProcessStatus::Continue);
+
+    // Every event the plugin pushed, in order, including the note end
+    // ProcessOutput has no field for
+    assert_eq!(
+        events,
+        [
+            (0, "param", 0, 0.0),
+            (0, "param", 1, 64.0),
+            (40, "end", 7, 60.0),
+        ]
+    );
+    assert_eq!(plugin.output_events().events().len(), 3);
+
+    assert_eq!(&*buffer.outputs[0], &left[..]);
+    assert_eq!(&*buffer.outputs[1], &right[..]);
+}
+
+/// Run the test plugin over `num_samples` of silence and return the
+/// `(sample_offset, value)` points it reported for `param_id`.
+fn process_test_plugin(
+    plugin: &mut clap_host::ClapInstance,
+    num_samples: usize,
+    ctx: &clap_host::ProcessContext<'_>,
+    param_id: u32,
+) -> Vec<(i32, f64)> {
+    use clap_host::AudioBuffer;
+
+    let silence = vec![0.0f32; num_samples];
+    let inputs: [&[f32]; 2] = [&silence, &silence];
+    let (mut out_l, mut out_r) = (silence.clone(), silence.clone());
+    let mut outputs: [&mut [f32]; 2] = [&mut out_l, &mut out_r];
+    let mut buffer = AudioBuffer::new(&inputs, &mut outputs, num_samples, 48000.0);
+    let output = plugin.process(&mut buffer, ctx).unwrap();
+    output
+        .param_changes
+        .queues
+        .iter()
+        .filter(|q| q.param_id == param_id)
+        .flat_map(|q| q.points.iter().map(|p| (p.sample_offset, p.value)))
+        .collect()
+}
+
+#[test]
+fn test_steady_time_advances_by_frames_processed() {
+    use clap_host::{ClapInstance, ProcessContext};
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut plugin = ClapInstance::load(test_plugin(dir.path()), 48000.0, 64).unwrap();
+    plugin.activate().unwrap();
+    let ctx = ProcessContext::default();
+
+    // Split into sub-blocks of 64, 64 and 32, each told its own start
+    assert_eq!(
+        process_test_plugin(&mut plugin, 160, &ctx, 0),
+        [(0, 0.0), (64, 64.0), (128, 128.0)]
+    );
+    assert_eq!(
+        process_test_plugin(&mut plugin, 160, &ctx, 1),
+        [(0, 64.0), (64, 64.0), (128, 32.0)]
+    );
+    assert_eq!(plugin.steady_time(), 320);
+    assert_eq!(process_test_plugin(&mut plugin, 16, &ctx, 0), [(0, 320.0)]);
+    assert_eq!(plugin.steady_time(), 336);
+}
+
+#[test]
+fn test_steady_time_resets_on_activate() {
+    use clap_host::{ClapInstance, ProcessContext};
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut plugin = ClapInstance::load(test_plugin(dir.path()), 48000.0, 64).unwrap();
+    plugin.activate().unwrap();
+    let ctx = ProcessContext::default();
+    process_test_plugin(&mut plugin, 48, &ctx, 0);
+    assert_eq!(plugin.steady_time(), 48);
+
+    plugin.deactivate();
+    plugin.activate().unwrap();
+    assert_eq!(plugin.steady_time(), 0);
+    assert_eq!(process_test_plugin(&mut plugin, 16, &ctx, 0), [(0, 0.0)]);
+}
+
+#[test]
+fn test_steady_time_override() {
+    use clap_host::{ClapInstance, ProcessContext};
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut plugin = ClapInstance::load(test_plugin(dir.path()), 48000.0, 64).unwrap();
+    plugin.activate().unwrap();
+    let ctx = ProcessContext::default();
+    process_test_plugin(&mut plugin, 32, &ctx, 0);
+
+    plugin.set_steady_time(48000);
+    assert_eq!(plugin.steady_time(), 48000);
+    assert_eq!(
+        process_test_plugin(&mut plugin, 96, &ctx, 0),
+        [(0, 48000.0), (64, 48064.0)]
+    );
+    assert_eq!(plugin.steady_time(), 48096);
+}
+
+// ── Plugin invalidation ──
+
+#[test]
+fn test_bundle_watcher_reports_rebuilt_bundle() {
+    use clap_host::scan::BundleWatcher;
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = dir.path().join("dev.clap");
+    let other = dir.path().join("other.clap");
+    std::fs::write(&bundle, b"v1").unwrap();
+    std::fs::write(&other, b"v1").unwrap();
+
+    let mut watcher = BundleWatcher::new();
+    watcher.watch_bundle(&bundle);
+    watcher.watch_bundle(&other);
+    assert!(watcher.poll().is_empty());
+
+    std::fs::write(&bundle, b"v2 rebuilt").unwrap();
+    assert_eq!(watcher.poll(), vec![bundle.clone()]);
+    // Reported once per change
+    assert!(watcher.poll().is_empty());
+
+    std::fs::remove_file(&other).unwrap();
+    assert_eq!(watcher.poll(), vec![other]);
+}
+
+#[test]
+fn test_bundle_watcher_reports_changed_sources() {
+    use clap_host::scan::{BundleWatcher, InvalidationSource};
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = dir.path().join("scripts.clap");
+    std::fs::write(&bundle, b"host").unwrap();
+    let scripts = dir.path().join("scripts");
+    std::fs::create_dir_all(scripts.join("nested")).unwrap();
+    std::fs::write(scripts.join("a.lua"), b"one").unwrap();
+
+    let mut watcher = BundleWatcher::new();
+    watcher.watch_source(InvalidationSource {
+        bundle: bundle.clone(),
+        directory: scripts.clone(),
+        filename_glob: "*.lua".to_string(),
+        recursive: false,
+    });
+    assert_eq!(watcher.watched().count(), 1);
+
+    // Non-matching and non-recursive files are ignored
+    std::fs::write(scripts.join("notes.txt"), b"ignored").unwrap();
+    std::fs::write(scripts.join("nested").join("b.lua"), b"ignored").unwrap();
+    assert!(watcher.poll().is_empty());
+
+    std::fs::write(scripts.join("c.lua"), b"new script").unwrap();
+    assert_eq!(watcher.poll(), vec![bundle.clone()]);
+
+    std::fs::write(scripts.join("a.lua"), b"edited script").unwrap();
+    assert_eq!(watcher.poll(), vec![bundle.clone()]);
+
+    watcher.unwatch(&bundle);
+    std::fs::remove_file(scripts.join("a.lua")).unwrap();
+    assert!(watcher.poll().is_empty());
+}
+
+#[test]
+fn test_bundle_watcher_from_report_includes_failures() {
+    use clap_host::scan::{scan_paths, BundleWatcher};
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = dir.path().join("broken.clap");
+    std::fs::write(&bundle, b"garbage").unwrap();
+
+    let report = scan_paths(&[dir.path()]);
+    assert_eq!(report.failures.len(), 1);
+    assert!(report.invalidation_sources.is_empty());
+
+    let mut watcher = BundleWatcher::from_report(&report);
+    assert_eq!(
+        watcher.watched().collect::<Vec<_>>(),
+        vec![bundle.as_path()]
+    );
+    std::fs::write(&bundle, b"fixed build").unwrap();
+    assert_eq!(watcher.poll(), vec![bundle]);
+}
+
+#[test]
+fn test_bundle_watcher_holds_back_reload_while_instances_live() {
+    use clap_host::scan::BundleWatcher;
+    use clap_host::ClapInstance;
+    use std::io::Write;
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = test_plugin(dir.path());
+    let plugin = ClapInstance::load(&bundle, 48000.0, 512).unwrap();
+
+    let mut watcher = BundleWatcher::new();
+    watcher.watch_bundle(&bundle);
+    assert!(watcher.poll().is_empty());
+
+    // Simulate a rebuild while the old library is still in use
+    std::fs::OpenOptions::new()
+        .append(true)
+        .open(&bundle)
+        .unwrap()
+        .write_all(b"rebuilt")
+        .unwrap();
+    assert_eq!(watcher.poll(), vec![bundle.clone()]);
+    assert_eq!(
+        watcher.reload_required().collect::<Vec<_>>(),
+        vec![bundle.as_path()]
+    );
+
+    // The loaded entry is reused rather than initialized a second time
+    assert_eq!(ClapInstance::descriptors(&bundle).unwrap().len(), 1);
+    assert!(watcher.poll().is_empty());
+
+    drop(plugin);
+    assert_eq!(watcher.poll(), vec![bundle.clone()]);
+    assert_eq!(watcher.reload_required().count(), 0);
+    assert_eq!(ClapInstance::descriptors(&bundle).unwrap().len(), 1);
+}
+
+#[test]
+fn test_scan_cache_keeps_invalidation_sources() {
+    use clap_host::scan::{InvalidationSource, ScanCache};
+    use clap_host::BundleScan;
+
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = dir.path().join("scripts.clap");
+    std::fs::write(&bundle, b"v1").unwrap();
+    let source = InvalidationSource {
+        bundle: bundle.clone(),
+        directory: dir.path().join("scripts"),
+        filename_glob: "*.lua".to_string(),
+        recursive: true,
+    };
+    let scan = |path: &Path| {
+        fake_scan(path).map(|plugins| BundleScan {
+            plugins,
+            invalidation_sources: vec![source.clone()],
+        })
+    };
+
+    let mut cache = ScanCache::new();
+    let report = cache.scan_paths(&[dir.path()], scan);
+    assert_eq!(report.invalidation_sources, vec![source.clone()]);
+
+    let cache_file = dir.path().join("scan-cache.txt");
+    cache.save(&cache_file).unwrap();
+    let mut loaded = ScanCache::load(&cache_file).unwrap();
+    assert_eq!(
+        loaded.invalidation_sources(&bundle),
+        Some(&[source.clone()][..])
+    );
+
+    let report = loaded.scan_paths(&[dir.path()], |_| -> Result<BundleScan, _> {
+        panic!("cached bundle rescanned")
+    });
+    assert_eq!(report.plugins.len(), 1);
+    assert_eq!(report.invalidation_sources, vec![source]);
+}
+
+#[test]
+fn test_invalidation_source_matches() {
+    use clap_host::scan::InvalidationSource;
+
+    let source = InvalidationSource {
+        bundle: "/plugins/scripts.clap".into(),
+        directory: "/scripts".into(),
+        filename_glob: "*.lua".to_string(),
+        recursive: true,
+    };
+    assert!(source.matches("synth.lua"));
+    assert!(!source.matches("synth.py"));
+}
+
+#[test]
+fn test_invalidation_sources_missing_bundle() {
+    use clap_host::scan::invalidation_sources;
+    use clap_host::LoadStage;
+
+    let failure = invalidation_sources("/nonexistent/plugin.clap").unwrap_err();
+    assert_eq!(failure.stage, LoadStage::Opening);
+}
+
+// ── Output event sinks ──
+
+#[test]
+fn test_output_event_sink_closure() {
+    use clap_host::OutputEventSink;
+
+    let mut output = OutputEventList::new();
+    let list_ptr = output.as_raw_mut();
+    for event in [
+        ClapEvent::note_on(0, 0, 60, 0.8),
+        ClapEvent::param_value(4, 7, 0.25),
+    ] {
+        unsafe {
+            (*list_ptr).try_push.unwrap()(list_ptr, event.header());
+        }
+    }
+
+    let mut times = Vec::new();
+    let mut sink = |event: &ClapEvent| times.push(event.header().time);
+    for event in output.events() {
+        sink.event(event);
+    }
+    assert_eq!(times, vec![0, 4]);
+}
+
+#[test]
+fn test_output_event_sink_receives_note_end() {
+    use clap_host::OutputEventSink;
+
+    #[derive(Default)]
+    struct EndedNotes(Vec<i32>);
+
+    impl OutputEventSink for EndedNotes {
+        fn event(&mut self, event: &ClapEvent) {
+            if let ClapEvent::NoteEnd(e) = event {
+                self.0.push(e.note_id);
+            }
+        }
+    }
+
+    let note_end = clap_event_note {
+        header: clap_event_header {
+            size: std::mem::size_of::<clap_event_note>() as u32,
+            time: 12,
+            space_id: CLAP_CORE_EVENT_SPACE_ID,
+            type_: CLAP_EVENT_NOTE_END,
+            flags: 0,
+        },
+        note_id: 42,
+        port_index: 0,
+        channel: 0,
+        key: 60,
+        velocity: 0.0,
+    };
+    let mut output = OutputEventList::new();
+    let list_ptr = output.as_raw_mut();
+    unsafe {
+        assert!((*list_ptr).try_push.unwrap()(list_ptr, &note_end.header));
+    }
+
+    let mut sink = EndedNotes::default();
+    let dyn_sink: &mut dyn OutputEventSink = &mut sink;
+    for event in output.events() {
+        dyn_sink.event(event);
+    }
+    assert_eq!(sink.0, vec![42]);
+}
+
+// ── Process status and sleep tracking ──
+
+#[test]
+fn test_process_status_from_raw() {
+    use clap_host::{ProcessOutput, ProcessStatus};
+    use clap_sys::process::*;
+
+    assert_eq!(ProcessStatus::from_raw(CLAP_PROCESS_ERROR), None);
+    assert_eq!(
+        ProcessStatus::from_raw(CLAP_PROCESS_CONTINUE),
+        Some(ProcessStatus::Continue)
+    );
+    assert_eq!(
+        ProcessStatus::from_raw(CLAP_PROCESS_CONTINUE_IF_NOT_QUIET),
+        Some(ProcessStatus::ContinueIfNotQuiet)
+    );
+    assert_eq!(
+        ProcessStatus::from_raw(CLAP_PROCESS_TAIL),
+        Some(ProcessStatus::Tail)
+    );
+    assert_eq!(
+        ProcessStatus::from_raw(CLAP_PROCESS_SLEEP),
+        Some(ProcessStatus::Sleep)
+    );
+    assert_eq!(ProcessStatus::from_raw(99), None);
+    assert_eq!(ProcessOutput::default().status, ProcessStatus::Continue);
+}
+
+#[test]
+fn test_sleep_tracker_sleep_until_event() {
+    use clap_host::{AudioBuffer, ProcessContext, ProcessStatus, SleepTracker};
+
+    let silent = [0.0f32; 64];
+    let inputs: [&[f32]; 1] = [&silent];
+    let buffer = AudioBuffer::new(&inputs, &mut [], 64, 48000.0);
+    let empty = ProcessContext::default();
+
+    let mut tracker = SleepTracker::new();
+    assert!(tracker.should_process(&buffer, &empty));
+    tracker.update(ProcessStatus::Sleep, &buffer, 0);
+    assert!(tracker.is_sleeping());
+    assert!(!tracker.should_process(&buffer, &empty));
+
+    let midi = [MidiEvent::note_on(0, 0, 60, 100)];
+    let with_note = ProcessContext {
+        midi: &midi,
+        ..Default::default()
+    };
+    assert!(tracker.should_process(&buffer, &with_note));
+    assert!(!tracker.is_sleeping());
+}
+
+#[test]
+fn test_sleep_tracker_wakes_on_input_audio() {
+    use clap_host::{AudioBuffer, ProcessContext, ProcessStatus, SleepTracker};
+
+    let mut signal = [0.0f32; 64];
+    signal[10] = 0.5;
+    let inputs: [&[f32]; 1] = [&signal];
+    let loud = AudioBuffer::new(&inputs, &mut [], 64, 48000.0);
+
+    let mut tracker = SleepTracker::new();
+    tracker.update(ProcessStatus::Sleep, &loud, 0);
+    assert!(tracker.is_sleeping());
+    assert!(tracker.should_process(&loud, &ProcessContext::default()));
+}
+
+#[test]
+fn test_sleep_tracker_tail_counts_down_after_input_goes_quiet() {
+    use clap_host::{AudioBuffer, ProcessStatus, SleepTracker};
+
+    let silent = [0.0f32; 64];
+    let inputs: [&[f32]; 1] = [&silent];
+    let buffer = AudioBuffer::new(&inputs, &mut [], 64, 48000.0);
+
+    let mut tracker = SleepTracker::new();
+    // 150 samples of tail: asleep after the third quiet 64-sample block
+    tracker.update(ProcessStatus::Tail, &buffer, 150);
+    assert!(!tracker.is_sleeping());
+    tracker.update(ProcessStatus::Tail, &buffer, 150);
+    assert!(!tracker.is_sleeping());
+    tracker.update(ProcessStatus::Tail, &buffer, 150);
+    assert!(tracker.is_sleeping());
+
+    // Infinite tail never sleeps
+    let mut tracker = SleepTracker::new();
+    for _ in 0..10 {
+        tracker.update(ProcessStatus::Tail, &buffer, u32::MAX);
+    }
+    assert!(!tracker.is_sleeping());
+}
+
+#[test]
+fn test_sleep_tracker_continue_if_not_quiet() {
+    use clap_host::{AudioBuffer, ProcessStatus, SleepTracker};
+
+    let mut ringing = vec![0.0f32; 32];
+    ringing[31] = 0.1;
+    let mut outputs: [&mut [f32]; 1] = [&mut ringing];
+    let buffer = AudioBuffer::new(&[], &mut outputs, 32, 48000.0);
+    let mut tracker = SleepTracker::new();
+    tracker.update(ProcessStatus::ContinueIfNotQuiet, &buffer, 0);
+    assert!(!tracker.is_sleeping());
+
+    buffer.outputs[0][31] = 0.0;
+    tracker.update(ProcessStatus::ContinueIfNotQuiet, &buffer, 0);
+    assert!(tracker.is_sleeping());
+
+    tracker.wake();
+    assert!(!tracker.is_sleeping());
+}
+
+// ── Constant Masks ──
+
+#[test]
+fn test_audio_buffer_detect_constant_inputs() {
+    use clap_host::AudioBuffer;
+
+    let silent = vec![0.0f32; 32];
+    let dc = vec![0.25f32; 32];
+    let mut signal = vec![0.0f32; 32];
+    signal[7] = 1.0;
+    let inputs: [&[f32]; 3] = [&silent, &signal, &dc];
+    let mut buffer = AudioBuffer::new(&inputs, &mut [], 32, 48000.0);
+    assert_eq!(buffer.input_constant_mask, 0);
+    assert_eq!(buffer.output_constant_mask, 0);
+
+    buffer.detect_constant_inputs();
+    assert_eq!(buffer.input_constant_mask, 0b101);
+    assert!(buffer.is_input_constant(0));
+    assert!(!buffer.is_input_constant(1));
+    assert!(buffer.is_input_constant(2));
+    assert!(!buffer.is_input_constant(64));
+    assert!(!buffer.is_output_constant(0));
+}
+
+#[test]
+fn test_audio_buffer_detect_constant_inputs_respects_num_samples() {
+    use clap_host::AudioBuffer;
+
+    let mut tail = vec![0.0f32; 32];
+    tail[20] = 1.0;
+    let inputs: [&[f32]; 1] = [&tail];
+    let mut buffer = AudioBuffer::new(&inputs, &mut [], 16, 48000.0);
+    buffer.detect_constant_inputs();
+    assert!(buffer.is_input_constant(0));
+}
+
+// ── Port Latency ──
+
+#[test]
+fn test_audio_buffer_input_latencies() {
+    use clap_host::{AudioBuffer, ProcessOutput};
+
+    let buffer = AudioBuffer::<f32>::new(&[], &mut [], 16, 48000.0);
+    assert!(buffer.input_latencies.is_empty());
+
+    let latencies = [32, 0, 256];
+    let buffer =
+        AudioBuffer::<f32>::new(&[], &mut [], 16, 48000.0).with_input_latencies(&latencies);
+    assert_eq!(buffer.input_latencies, &[32, 0, 256]);
+
+    assert!(ProcessOutput::default().output_latencies.is_empty());
+}
+
+// ── Port Buffers ──
+
+#[test]
+fn test_port_buffer_construction() {
+    use clap_host::{InputPort, OutputPort, PortBuffer};
+
+    let silent = vec![0.0f32; 16];
+    let mut signal = vec![0.0f32; 16];
+    signal[3] = 0.5;
+    let channels: [&[f32]; 2] = [&silent, &signal];
+    let port = InputPort::new(&channels[..]).with_latency(128);
+    assert_eq!(port.constant_mask, 0);
+    assert_eq!(port.latency, 128);
+    let port = port.detect_constant(16);
+    assert_eq!(port.constant_mask, 0b01);
+
+    let mut left = vec![0.0f32; 16];
+    let mut output_channels: [&mut [f32]; 1] = [&mut left];
+    let inputs = [port];
+    let mut outputs = [OutputPort::new(&mut output_channels)];
+    let buffer = PortBuffer::new(&inputs, &mut outputs, 16, 48000.0);
+    assert_eq!(buffer.inputs.len(), 1);
+    assert_eq!(buffer.outputs[0].channels.len(), 1);
+    assert_eq!(buffer.outputs[0].constant_mask, 0);
+    assert_eq!(buffer.outputs[0].latency, 0);
+}
+
+// ── Transport Fidelity ──
+
+#[test]
+fn test_transport_info_known_fields() {
+    use clap_host::{TransportFields, TransportInfo};
+
+    assert_eq!(TransportInfo::new().known, TransportFields::all());
+    assert_eq!(TransportInfo::default().known, TransportFields::all());
+    assert!(TransportInfo::unknown().known.is_empty());
+
+    let transport = TransportInfo::unknown()
+        .with_beats(16.0)
+        .with_time_signature(7, 8);
+    assert_eq!(
+        transport.known,
+        TransportFields::BEATS_TIMELINE | TransportFields::TIME_SIGNATURE
+    );
+
+    let transport = TransportInfo::unknown()
+        .with_seconds(1.5)
+        .with_tempo_inc(0.01);
+    assert_eq!(
+        transport.known,
+        TransportFields::SECONDS_TIMELINE | TransportFields::TEMPO
+    );
+    assert!((transport.tempo_inc - 0.01).abs() < f64::EPSILON);
+}
+
+// ── Transport Driver ──
+
+#[test]
+fn test_transport_advances_while_playing() {
+    use clap_host::Transport;
+
+    // 120 BPM at 48 kHz: 24000 samples per beat
+    let mut transport = Transport::new(48000.0);
+    transport.next_block(24000);
+    assert_eq!(transport.position_beats(), 0.0);
+    assert!(!transport.block_info().playing);
+
+    transport.play();
+    transport.next_block(24000);
+    assert_eq!(transport.block_info().song_pos_beats, 0.0);
+    assert!(transport.block_info().playing);
+    assert!((transport.position_beats() - 1.0).abs() < 1e-9);
+
+    transport.next_block(24000);
+    let info = transport.block_info();
+    assert!((info.song_pos_beats - 1.0).abs() < 1e-9);
+    assert!((info.song_pos_seconds - 0.5).abs() < 1e-9);
+}
+
+#[test]
+fn test_transport_counts_bars() {
+    use clap_host::Transport;
+
+    let mut transport = Transport::new(48000.0).with_time_signature(3, 4);
+    transport.seek(7.5);
+    transport.next_block(64);
+    let info = transport.block_info();
+    assert_eq!(info.bar_number, 2);
+    assert!((info.bar_start - 6.0).abs() < 1e-9);
+
+    let mut transport = Transport::new(48000.0).with_time_signature(6, 8);
+    transport.seek(3.0);
+    transport.next_block(64);
+    assert_eq!(transport.block_info().bar_number, 1);
+    assert!((transport.block_info().bar_start - 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_transport_wraps_loop_mid_block() {
+    use clap_host::Transport;
+
+    let mut transport = Transport::new(48000.0).with_loop(0.0, 1.0);
+    transport.play();
+    transport.seek(0.5);
+    // Loop end is reached 12000 samples into a 24000-sample block
+    transport.next_block(24000);
+    let changes = transport.block_changes();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].sample_offset, 12000);
+    assert_eq!(changes[0].transport.song_pos_beats, 0.0);
+    assert!((transport.position_beats() - 0.5).abs() < 1e-9);
+
+    let ctx = transport.context();
+    assert_eq!(ctx.transport_changes.len(), 1);
+    assert!(ctx.transport.unwrap().cycle_active);
+}
+
+#[test]
+fn test_transport_wrap_on_block_boundary() {
+    use clap_host::Transport;
+
+    let mut transport = Transport::new(48000.0).with_loop(0.0, 1.0);
+    transport.play();
+    transport.next_block(24000);
+    assert!(transport.block_changes().is_empty());
+    assert_eq!(transport.position_beats(), 0.0);
+}
+
+#[test]
+fn test_transport_applies_requests() {
+    use clap_host::{Transport, TransportRequest};
+
+    let mut transport = Transport::new(44100.0);
+    transport.apply_requests([
+        TransportRequest::Start,
+        TransportRequest::Jump {
+            position_beats: 16.0,
+        },
+        TransportRequest::LoopRegion {
+            start_beats: 8.0,
+            duration_beats: 4.0,
+        },
+        TransportRequest::EnableLoop(true),
+        TransportRequest::ToggleRecord,
+    ]);
+    assert!(transport.is_playing());
+    assert!(transport.is_recording());
+    assert!(transport.is_loop_active());
+    assert_eq!(transport.position_beats(), 16.0);
+
+    transport.next_block(32);
+    let info = transport.block_info();
+    assert_eq!(info.loop_start_beats, 8.0);
+    assert_eq!(info.loop_end_beats, 12.0);
+    // Started past the loop end: no wrap
+    assert!(transport.block_changes().is_empty());
+
+    transport.apply(TransportRequest::Stop);
+    assert!(!transport.is_playing());
+    assert!(!transport.is_recording());
+}
+
+// ── Tempo Map ──
+
+#[test]
+fn test_tempo_map_conversions() {
+    use clap_host::TempoMap;
+
+    // 4 beats at 120 BPM (2 s), then 60 BPM
+    let map = TempoMap::new(120.0).with_tempo(4.0, 60.0);
+    assert_eq!(map.tempo_at(3.9), 120.0);
+    assert_eq!(map.tempo_at(4.0), 60.0);
+    assert!((map.beats_to_seconds(2.0) - 1.0).abs() < 1e-9);
+    assert!((map.beats_to_seconds(6.0) - 4.0).abs() < 1e-9);
+    assert!((map.seconds_to_beats(4.0) - 6.0).abs() < 1e-9);
+    assert!((map.seconds_to_beats(1.0) - 2.0).abs() < 1e-9);
+    assert!((map.beats_to_samples(5.0, 48000.0) - 144000.0).abs() < 1e-6);
+    assert!((map.samples_to_beats(144000.0, 48000.0) - 5.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_tempo_map_bars_across_meter_changes() {
+    use clap_host::TempoMap;
+
+    // Two bars of 4/4, then 3/4 starting mid-bar at beat 10
+    let map = TempoMap::new(120.0).with_time_signature(10.0, 3, 4);
+    assert_eq!(map.time_signature_at(9.0), (4, 4));
+    assert_eq!(map.time_signature_at(10.0), (3, 4));
+    assert_eq!(map.bar_at(9.0), (2, 8.0));
+    // The change opens bar 3
+    assert_eq!(map.bar_at(10.0), (3, 10.0));
+    assert_eq!(map.bar_at(14.0), (4, 13.0));
+
+    let info = map.transport_at(14.0);
+    assert_eq!(info.bar_number, 4);
+    assert_eq!(info.time_sig_numerator, 3);
+}
+
+#[test]
+fn test_tempo_map_split_block() {
+    use clap_host::TempoMap;
+
+    // Tempo doubles at beat 1, i.e. 24000 samples in at 48 kHz
+    let map = TempoMap::new(120.0).with_tempo(1.0, 240.0);
+    let segments = map.split_block(0.5, 24000, 48000.0);
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].sample_offset, 0);
+    assert_eq!(segments[0].num_samples, 12000);
+    assert_eq!(segments[0].transport.tempo, 120.0);
+    assert!(segments[0].transport.playing);
+    assert_eq!(segments[1].sample_offset, 12000);
+    assert_eq!(segments[1].num_samples, 12000);
+    assert_eq!(segments[1].transport.tempo, 240.0);
+    assert_eq!(segments[1].transport.song_pos_beats, 1.0);
+
+    let changes = map.transport_changes(0.5, 24000, 48000.0);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].sample_offset, 12000);
+
+    // No change inside the block
+    let segments = map.split_block(2.0, 512, 48000.0);
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].num_samples, 512);
+}
+
+#[test]
+fn test_transport_follows_tempo_map() {
+    use clap_host::{TempoMap, Transport};
+
+    let map = TempoMap::new(120.0)
+        .with_tempo(1.0, 60.0)
+        .with_time_signature(1.0, 3, 4);
+    let mut transport = Transport::new(48000.0).with_tempo_map(map);
+    transport.play();
+    transport.next_block(48000);
+    let changes = transport.block_changes();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].sample_offset, 24000);
+    assert_eq!(changes[0].transport.tempo, 60.0);
+    assert_eq!(changes[0].transport.time_sig_numerator, 3);
+    // Half a beat at 60 BPM after the change
+    assert!((transport.position_beats() - 1.5).abs() < 1e-9);
+    assert_eq!(transport.tempo(), 60.0);
+}
+
+#[test]
+fn test_tempo_map_clamps_invalid_tempo() {
+    use clap_host::{TempoMap, Transport};
+
+    let map = TempoMap::new(0.0)
+        .with_tempo(4.0, -10.0)
+        .with_tempo(8.0, f64::NAN);
+    assert_eq!(map.tempo_at(0.0), 1.0);
+    assert_eq!(map.tempo_at(4.0), 1.0);
+    assert_eq!(map.tempo_at(8.0), 1.0);
+    for segment in map.split_block(3.9, 48000, 48000.0) {
+        assert!(segment.transport.song_pos_beats.is_finite());
+        assert!(segment.transport.song_pos_seconds.is_finite());
+    }
+
+    let mut transport = Transport::new(0.0);
+    assert_eq!(transport.sample_rate(), 1.0);
+    transport.play();
+    transport.next_block(512);
+    assert!(transport.position_beats().is_finite());
+}
+
+#[test]
+fn test_tempo_map_split_block_carries_sample_remainder() {
+    use clap_host::TempoMap;
+
+    // The change falls 12000.24 samples in, so the second segment starts
+    // 0.76 samples past it
+    let map = TempoMap::new(120.0).with_tempo(0.50001, 60.0);
+    let segments = map.split_block(0.0, 24000, 48000.0);
+    assert_eq!(segments[1].sample_offset, 12001);
+    let beat = segments[1].transport.song_pos_beats;
+    assert!(beat > 0.50001);
+    assert!((beat - map.samples_to_beats(12001.0, 48000.0)).abs() < 1e-12);
+}
+
+#[test]
+fn test_transport_position_does_not_drift_across_changes() {
+    use clap_host::{TempoMap, Transport};
+
+    let map = TempoMap::new(120.0)
+        .with_tempo(0.50001, 97.0)
+        .with_tempo(1.30007, 151.0)
+        .with_tempo(2.70003, 83.0);
+    let mut transport = Transport::new(48000.0).with_tempo_map(map.clone());
+    transport.play();
+    for block in 1..=200u32 {
+        transport.next_block(512);
+        let expected = map.samples_to_beats((block * 512) as f64, 48000.0);
+        assert!(
+            (transport.position_beats() - expected).abs() < 1e-9,
+            "block {}: {} != {}",
+            block,
+            transport.position_beats(),
+            expected
+        );
+        for change in transport.block_changes() {
+            let at = ((block - 1) * 512 + change.sample_offset) as f64;
+            let expected = map.samples_to_beats(at, 48000.0);
+            assert!((change.transport.song_pos_beats - expected).abs() < 1e-9);
+        }
+    }
+}
+
+#[test]
+fn test_transport_loop_wrap_carries_sample_remainder() {
+    use clap_host::Transport;
+
+    // The loop end falls 12000.24 samples in; the wrap lands on sample
+    // 12001, 0.76 samples into the loop
+    let mut transport = Transport::new(48000.0).with_loop(0.0, 0.50001);
+    transport.play();
+    transport.next_block(24000);
+    let changes = transport.block_changes();
+    assert_eq!(changes[0].sample_offset, 12001);
+    let past = 12001.0 - 0.50001 * 24000.0;
+    assert!((changes[0].transport.song_pos_beats - past / 24000.0).abs() < 1e-9);
+    assert!((transport.position_beats() - (24000.0 - 12000.0 - 0.24) / 24000.0).abs() < 1e-9);
+}
+
+// ── Sysex Input ──
+
+#[test]
+fn test_sysex_input_reaches_plugin() {
+    use clap_host::{InputEventList, MidiData, MidiEvent};
+    use clap_sys::events::{clap_event_midi_sysex, CLAP_EVENT_MIDI_SYSEX};
+    use std::sync::Arc;
+
+    let dump: Arc<[u8]> = Arc::from(&[0xF0, 0x43, 0x00, 0x09, 0x20, 0x00, 0xF7][..]);
+    let midi = [
+        MidiEvent::note_on(0, 0, 60, 100),
+        MidiEvent::sysex(32, dump.clone()),
+        // Borrowed bytes are copied in
+        MidiEvent::sysex(64, &[0xF0, 0x7E, 0x7F, 0x06, 0x01, 0xF7][..]),
+        // Empty sysex is dropped
+        MidiEvent::sysex(96, Vec::new()),
+    ];
+    assert!(matches!(&midi[1].data, MidiData::Sysex(d) if Arc::ptr_eq(d, &dump)));
+
+    let mut list = InputEventList::new();
+    list.add_midi_events(&midi);
+    let raw = list.as_raw();
+    unsafe {
+        assert_eq!((*raw).size.unwrap()(raw), 3);
+        let header = &*(*raw).get.unwrap()(raw, 1);
+        assert_eq!(header.type_, CLAP_EVENT_MIDI_SYSEX);
+        assert_eq!(header.time, 32);
+        let sysex = &*(header as *const _ as *const clap_event_midi_sysex);
+        let bytes = std::slice::from_raw_parts(sysex.buffer, sysex.size as usize);
+        assert_eq!(bytes, &dump[..]);
+        // The plugin reads the shared buffer itself, not a copy
+        assert_eq!(sysex.buffer, dump.as_ptr());
+    }
+}
+
+// ── Note IDs and Ports ──
+
+#[test]
+fn test_midi_event_note_id_and_port_roundtrip() {
+    use clap_host::{ClapEvent, MidiEvent};
+
+    let on = MidiEvent::note_on(16, 3, 64, 127)
+        .with_note_id(42)
+        .with_port_index(1);
+    let event = ClapEvent::from_midi_event(&on).unwrap();
+    match &event {
+        ClapEvent::NoteOn(e) => {
+            assert_eq!(e.note_id, 42);
+            assert_eq!(e.port_index, 1);
+            assert_eq!(e.channel, 3);
+        }
+        _ => panic!("Expected NoteOn"),
+    }
+    let back = event.to_midi_event().unwrap();
+    assert_eq!(back.note_id, 42);
+    assert_eq!(back.port_index, 1);
+
+    // Plain constructors keep the wildcard note ID on port 0
+    let off = MidiEvent::note_off(0, 0, 64, 0);
+    assert_eq!(off.note_id, -1);
+    match ClapEvent::from_midi_event(&off).unwrap() {
+        ClapEvent::NoteOff(e) => {
+            assert_eq!(e.note_id, -1);
+            assert_eq!(e.port_index, 0);
+        }
+        _ => panic!("Expected NoteOff"),
+    }
+
+    // MIDI-dialect events carry the port too
+    let cc = MidiEvent::control_change(0, 0, 74, 10).with_port_index(2);
+    match ClapEvent::from_midi_event(&cc).unwrap() {
+        ClapEvent::Midi(e) => assert_eq!(e.port_index, 2),
+        _ => panic!("Expected Midi"),
+    }
+
+    // The any-port wildcard survives on notes; MIDI has none and uses port 0
+    let any = ClapEvent::note_off(0, 0, 64, 0.0).with_port_index(-1);
+    assert_eq!(any.to_midi_event().unwrap().port_index, -1);
+    let cc = cc.with_port_index(-1);
+    match ClapEvent::from_midi_event(&cc).unwrap() {
+        ClapEvent::Midi(e) => assert_eq!(e.port_index, 0),
+        _ => panic!("Expected Midi"),
+    }
+}
+
+#[test]
+fn test_output_note_end_carries_note_id() {
+    use clap_host::{ClapEvent, NoteEnd, OutputEventList};
+
+    let mut list = OutputEventList::new();
+    let raw = list.as_raw_mut();
+    let end = ClapEvent::note_on(99, 2, 72, 0.0)
+        .with_note_id(7)
+        .with_port_index(1);
+    let ClapEvent::NoteOn(mut note) = end else {
+        unreachable!()
+    };
+    note.header.type_ = CLAP_EVENT_NOTE_END;
+    unsafe {
+        assert!((*raw).try_push.unwrap()(raw as *const _, &note.header));
+    }
+
+    assert_eq!(
+        list.to_note_ends(),
+        [NoteEnd {
+            sample_offset: 99,
+            port_index: 1,
+            channel: 2,
+            key: 72,
+            note_id: 7,
+        }]
+    );
+    assert!(list.to_midi_events().is_empty());
+}
+
+// ── Voice Tracking ──
+
+#[test]
+fn test_voice_tracker_follows_note_ends() {
+    use clap_host::{MidiEvent, NoteEnd, OutputEventSink, ProcessOutput, VoiceTracker};
+
+    let mut voices = VoiceTracker::new();
+    voices.track_input(&[
+        MidiEvent::note_on(0, 0, 60, 100).with_note_id(1),
+        MidiEvent::note_on(0, 0, 64, 100).with_note_id(2),
+        MidiEvent::note_on(0, 1, 67, 100),
+    ]);
+    assert_eq!(voices.len(), 3);
+    assert!(voices.is_sounding(0, 64));
+
+    // A note-off releases the voice, but it sounds until the plugin ends it
+    voices.track_event(&MidiEvent::note_off(10, 0, 60, 0).with_note_id(1));
+    assert!(voices.voices()[0].released);
+    assert_eq!(voices.len(), 3);
+
+    let output = ProcessOutput {
+        note_ends: vec![NoteEnd {
+            sample_offset: 20,
+            port_index: 0,
+            channel: 0,
+            key: 60,
+            note_id: 1,
+        }],
+        ..Default::default()
+    };
+    voices.track_output(&output);
+    assert_eq!(voices.len(), 2);
+    assert!(!voices.is_sounding(0, 60));
+
+    // Choke through the sink with a wildcard key on channel 1
+    let choke = match ClapEvent::note_on(0, 1, -1, 0.0) {
+        ClapEvent::NoteOn(mut e) => {
+            e.port_index = -1;
+            ClapEvent::NoteChoke(e)
+        }
+        _ => unreachable!(),
+    };
+    voices.event(&choke);
+    assert_eq!(voices.len(), 1);
+    assert_eq!(voices.voices()[0].note_id, 2);
+}
+
+#[test]
+fn test_voice_tracker_releases_on_stop() {
+    use clap_host::{MidiData, MidiEvent, VoiceTracker};
+
+    let mut voices = VoiceTracker::new();
+    voices.track_input(&[
+        MidiEvent::note_on(0, 0, 60, 100).with_note_id(5),
+        MidiEvent::note_on(0, 0, 62, 100).with_port_index(1),
+        MidiEvent::note_off(8, 0, 60, 0).with_note_id(5),
+    ]);
+
+    let offs = voices.release_all(0);
+    assert_eq!(offs.len(), 1);
+    assert!(matches!(offs[0].data, MidiData::NoteOff { key: 62, .. }));
+    assert_eq!(offs[0].port_index, 1);
+    assert!(voices.release_all(0).is_empty());
+    assert_eq!(voices.len(), 2);
+}
+
+#[test]
+fn test_voice_tracker_without_note_ends() {
+    use clap_host::{MidiEvent, VoiceTracker};
+
+    let mut voices = VoiceTracker::new();
+    voices.track_input(&[
+        MidiEvent::note_on(0, 0, 60, 100),
+        MidiEvent::note_on(0, 0, 64, 100),
+        MidiEvent::note_off(8, 0, 60, 0),
+    ]);
+    assert!(voices.note_ends_reported());
+    assert_eq!(voices.len(), 2);
+
+    // Turning note ends off drops the voices already released
+    voices.set_note_ends_reported(false);
+    assert_eq!(voices.len(), 1);
+    assert!(!voices.is_sounding(0, 60));
+
+    voices.track_event(&MidiEvent::note_on(16, 0, 67, 100));
+    voices.track_event(&MidiEvent::note_off(24, 0, 67, 0));
+    assert_eq!(voices.len(), 1);
+    assert_eq!(voices.release_all(0).len(), 1);
+    assert!(voices.is_empty());
+}
+
+#[test]
+fn test_voice_tracker_uses_voice_info() {
+    use clap_host::{MidiEvent, VoiceInfo, VoiceTracker};
+
+    let mut voices = VoiceTracker::new();
+    voices.set_voice_info(VoiceInfo {
+        voice_count: 2,
+        voice_capacity: 2,
+        supports_overlapping_notes: false,
+    });
+    voices.track_input(&[
+        MidiEvent::note_on(0, 0, 60, 100),
+        MidiEvent::note_on(4, 0, 60, 90),
+    ]);
+    // Without overlapping notes, the retrigger replaces the voice
+    assert_eq!(voices.len(), 1);
+    assert!(!voices.is_full());
+    voices.track_event(&MidiEvent::note_on(8, 0, 62, 100));
+    assert!(voices.is_full());
+}
+
+// ── Parameter Modulation ──
+
+#[test]
+fn test_param_modulations_sent_as_param_mod() {
+    use clap_host::{InputEventList, ModulationQueue, ParameterModulations};
+
+    let mut modulations = ParameterModulations::new();
+    let mut global = ModulationQueue::new(3);
+    global.add_point(0, 0.25).add_point(64, -0.5);
+    let mut voice = ModulationQueue::new(3)
+        .for_note(11)
+        .on_channel(0)
+        .on_key(60);
+    voice.add_point(32, 1.0);
+    modulations.add_queue(global).add_queue(voice);
+
+    let mut list = InputEventList::new();
+    list.add_param_modulations(&modulations).sort_by_time();
+
+    let events = list.events();
+    assert_eq!(events.len(), 3);
+    match &events[1] {
+        ClapEvent::ParamMod(e) => {
+            assert_eq!(e.header.type_, CLAP_EVENT_PARAM_MOD);
+            assert_eq!(e.header.time, 32);
+            assert_eq!(e.param_id, 3);
+            assert_eq!(e.note_id, 11);
+            assert_eq!(e.port_index, -1);
+            assert_eq!(e.channel, 0);
+            assert_eq!(e.key, 60);
+            assert_eq!(e.amount, 1.0);
+        }
+        _ => panic!("Expected ParamMod"),
+    }
+    match &events[2] {
+        ClapEvent::ParamMod(e) => {
+            assert_eq!(e.note_id, -1);
+            assert_eq!(e.key, -1);
+            assert_eq!(e.amount, -0.5);
+        }
+        _ => panic!("Expected ParamMod"),
+    }
+}
+
+#[test]
+fn test_modulations_wake_sleeping_plugin() {
+    use clap_host::{
+        AudioBuffer, ModulationQueue, ParameterModulations, ProcessContext, ProcessStatus,
+        SleepTracker,
+    };
+
+    let mut out = vec![0.0f32; 16];
+    let mut outputs: [&mut [f32]; 1] = [&mut out];
+    let buffer = AudioBuffer::new(&[], &mut outputs, 16, 48000.0);
+    let mut sleep = SleepTracker::new();
+    sleep.update(ProcessStatus::Sleep, &buffer, 0);
+    assert!(!sleep.should_process(&buffer, &ProcessContext::default()));
+
+    let mut modulations = ParameterModulations::new();
+    let mut queue = ModulationQueue::new(1);
+    queue.add_point(0, 0.1);
+    modulations.add_queue(queue);
+    let ctx = ProcessContext {
+        modulations: Some(&modulations),
+        ..Default::default()
+    };
+    assert!(sleep.should_process(&buffer, &ctx));
+}
+
+// ── Per-Note Automation ──
+
+#[test]
+fn test_per_note_parameter_points() {
+    use clap_host::{InputEventList, ParameterChanges, ParameterPoint, ParameterQueue};
+
+    let mut queue = ParameterQueue::new(9);
+    queue
+        .add_point(0, 0.5)
+        .push_point(ParameterPoint::new(16, 0.8).for_note(4))
+        .push_point(
+            ParameterPoint::new(32, 0.1)
+                .port(0)
+                .on_channel(2)
+                .on_key(67),
+        );
+    assert!(!queue.points[0].is_per_note());
+    assert!(queue.points[1].is_per_note());
+    let mut changes = ParameterChanges::new();
+    changes.add_queue(queue);
+
+    let mut list = InputEventList::new();
+    list.add_param_changes(&changes);
+    let targets: Vec<_> = list
+        .events()
+        .iter()
+        .map(|event| match event {
+            ClapEvent::ParamValue(e) => (e.note_id, e.port_index, e.channel, e.key),
+            _ => panic!("Expected ParamValue"),
+        })
+        .collect();
+    assert_eq!(targets, [(-1, -1, -1, -1), (4, -1, -1, -1), (-1, 0, 2, 67)]);
+}
+
+#[test]
+fn test_output_param_values_keep_note_target() {
+    use clap_host::OutputEventList;
+
+    let mut list = OutputEventList::new();
+    let raw = list.as_raw_mut();
+    let mut event = match ClapEvent::param_value(8, 2, 0.3) {
+        ClapEvent::ParamValue(e) => e,
+        _ => unreachable!(),
+    };
+    event.note_id = 12;
+    event.key = 48;
+    unsafe {
+        assert!((*raw).try_push.unwrap()(raw as *const _, &event.header));
+    }
+
+    let changes = list.to_param_changes();
+    let point = changes.queues[0].points[0];
+    assert_eq!(point.sample_offset, 8);
+    assert_eq!(point.note_id, 12);
+    assert_eq!(point.key, 48);
+    assert_eq!(point.channel, -1);
+}
+
+#[test]
+fn test_output_param_changes_read_in_place() {
+    use clap_host::OutputEventList;
+
+    let mut list = OutputEventList::new();
+    let raw = list.as_raw_mut();
+    for (time, id, value) in [(0, 3, 0.1), (4, 1, 0.2), (8, 3, 0.3)] {
+        let event = ClapEvent::param_value(time, id, value);
+        unsafe {
+            assert!((*raw).try_push.unwrap()(raw as *const _, event.header()));
+        }
+    }
+
+    // Queues follow first appearance
+    let changes = list.to_param_changes();
+    let ids: Vec<_> = changes.queues.iter().map(|q| q.param_id).collect();
+    assert_eq!(ids, [3, 1]);
+    assert_eq!(changes.queues[0].points.len(), 2);
+
+    // Reading into existing changes appends to the matching queue
+    let mut changes = ParameterChanges::new();
+    let mut queue = ParameterQueue::new(1);
+    queue.add_point(0, 0.5);
+    changes.add_queue(queue);
+    list.read_param_changes(&mut changes);
+    assert_eq!(changes.queues.len(), 2);
+    assert_eq!(changes.queues[0].param_id, 1);
+    assert_eq!(changes.queues[0].points.len(), 2);
+    assert_eq!(changes.queues[1].param_id, 3);
+}
+
+// ── Allocation-free processing ──
+
+/// Counts heap allocations made by the current thread, so tests running in
+/// parallel don't disturb each other.
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+fn count_allocation() {
+    let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+}
+
+unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        count_allocation();
+        std::alloc::System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: std::alloc::Layout) -> *mut u8 {
+        count_allocation();
+        std::alloc::System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: std::alloc::Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        std::alloc::System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+        std::alloc::System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|n| n.get())
+}
+
+#[test]
+fn test_process_with_mixed_events_does_not_allocate() {
+    use clap_host::{
+        AudioBuffer, ClapInstance, ModulationQueue, NoteExpressionValue, ParameterModulations,
+        ProcessContext, TransportChange, TransportInfo,
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut plugin = ClapInstance::load(test_plugin(dir.path()), 48000.0, 64).unwrap();
+    plugin.activate().unwrap();
+
+    // Enough events per type that a general-purpose sort would allocate
+    let mut midi: Vec<MidiEvent> = (0..32)
+        .map(|i| MidiEvent::note_on(127 - i * 4, 0, i as u8, 100))
+        .collect();
+    midi.push(MidiEvent::sysex(70, vec![0xF0, 0x7E, 0xF7]));
+    let mut params = ParameterChanges::new();
+    let mut queue = ParameterQueue::new(1);
+    for i in 0..32 {
+        queue.add_point(i * 4, 0.5);
+    }
+    params.add_queue(queue);
+    let mut modulations = ParameterModulations::new();
+    let mut modulation = ModulationQueue::new(1);
+    modulation.add_point(20, 0.25);
+    modulations.add_queue(modulation);
+    let expressions = [NoteExpressionValue::new(NoteExpressionType::Volume, 1, 0.5).at(30)];
+    let transport = TransportInfo::default();
+    let transport_changes = [TransportChange {
+        sample_offset: 90,
+        transport,
+    }];
+    let ctx = ProcessContext {
+        midi: &midi,
+        params: Some(&params),
+        modulations: Some(&modulations),
+        expressions: &expressions,
+        transport: Some(&transport),
+        transport_changes: &transport_changes,
+    };
+
+    let silence = vec![0.0f32; 128];
+    let inputs: [&[f32]; 2] = [&silence, &silence];
+    let (mut out_l, mut out_r) = (silence.clone(), silence.clone());
+    let mut outputs: [&mut [f32]; 2] = [&mut out_l, &mut out_r];
+    let mut buffer = AudioBuffer::new(&inputs, &mut outputs, 128, 48000.0);
+    plugin.process(&mut buffer, &ctx).unwrap();
+
+    let before = allocations();
+    let output = plugin.process(&mut buffer, &ctx).unwrap();
+    assert_eq!(allocations(), before);
+    // The plugin's parameter reports fit ProcessOutput's inline storage
+    assert!(!output.param_changes.queues.is_empty());
+}